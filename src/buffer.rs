@@ -9,13 +9,19 @@ pub struct InputBuffer {
     pub buffer: String,
 }
 
+impl Default for InputBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl InputBuffer {
     /// Returns a new InputBuffer which contains an empty buffer
     /// 
     /// # Example
     /// 
     /// ```
-    /// use crate::buffer::InputBuffer;
+    /// use rsqlite::buffer::InputBuffer;
     /// let input_buffer = InputBuffer::new();
     /// ```
     pub fn new() -> Self {
@@ -28,9 +34,9 @@ impl InputBuffer {
     /// 
     /// # Example
     /// 
-    /// ```
-    /// use crate::buffer::InputBuffer;
-    /// let input_buffer = InputBuffer::new();
+    /// ```no_run
+    /// use rsqlite::buffer::InputBuffer;
+    /// let mut input_buffer = InputBuffer::new();
     /// // Input from stdin present in input_buffer.buffer
     /// input_buffer.read_input();
     /// ```
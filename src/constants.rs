@@ -1,39 +1,289 @@
 //! This file specifies the various constants used across the files.
 
-/// Set page size to be used internally.
+/// Page size of a new database file, unless another one is chosen.
 /// 4KB is the most common page size
-pub const PAGE_SIZE: u32 = 4096;
+pub const DEFAULT_PAGE_SIZE: u32 = 4096;
 
-/// Maximum amount of pages a table can hold or store at a time
-pub const TABLE_MAX_PAGES: u32 = 100;
+/// Smallest page size of a database file
+pub const MIN_PAGE_SIZE: u32 = 512;
 
-/// Number of rows that can fit into a page
-pub const ROWS_PER_PAGE: u32 = PAGE_SIZE / ROW_SIZE;
+/// Largest page size of a database file
+pub const MAX_PAGE_SIZE: u32 = 65536;
 
-/// Maximum rows a table can hold or store
-pub const TABLE_MAX_ROWS: u32 = ROWS_PER_PAGE * TABLE_MAX_PAGES;
+/// Number of pages the pager holds in memory, unless another number is chosen
+pub const DEFAULT_CACHE_SIZE: usize = 2000;
 
-/// Size of the id field in bytes
-pub const ID_SIZE: usize = 4;
+/// Size of the pages of the temporary file a sort writes its runs to
+pub const SORTER_PAGE_SIZE: u32 = 4096;
 
-/// Size of the username field in bytes
-pub const USERNAME_SIZE: usize = 32;
+// Page 0 of the database file starts with a header describing the file.
+// The following constants specify its layout.
 
-/// Size of the email field in bytes
-pub const EMAIL_SIZE: usize = 255;
+/// Magic string identifying a database file
+pub const HEADER_MAGIC: &[u8; HEADER_MAGIC_SIZE] = b"rsqlite format\0\0";
 
-// Since all the fields are converted to bytes and stored in a single byte array.
-// The three fields have to be deserialized from different offsets in the byte array.
-// The following constants specify those offsets.
+/// Size of the magic string in bytes
+pub const HEADER_MAGIC_SIZE: usize = 16;
 
-/// The offset in the byte array where bytes of the id field start
-pub const ID_OFFSET: usize = 0;
+/// The offset in the header where the magic string is stored
+pub const HEADER_MAGIC_OFFSET: usize = 0;
 
-/// The offset in the byte array where bytes of the username field start
-pub const USERNAME_OFFSET: usize = ID_OFFSET + ID_SIZE;
+/// Version of the file format written by this build
+pub const FORMAT_VERSION: u32 = 10;
 
-/// The offset in the byte array where bytes of the email field start
-pub const EMAIL_OFFSET: usize = USERNAME_OFFSET + USERNAME_SIZE;
+/// Oldest version of the file format this build can read. Every change
+/// of the layout of the file must raise both versions.
+pub const MIN_FORMAT_VERSION: u32 = 10;
 
-/// Total amount in bytes that a row will occupy in memory
-pub const ROW_SIZE: u32 = (ID_SIZE + USERNAME_SIZE + EMAIL_SIZE) as u32;
+/// The offset in the header where the file format version is stored
+pub const HEADER_FORMAT_VERSION_OFFSET: usize = HEADER_MAGIC_OFFSET + HEADER_MAGIC_SIZE;
+
+/// The offset in the header where the page size is stored
+pub const HEADER_PAGE_SIZE_OFFSET: usize = HEADER_FORMAT_VERSION_OFFSET + 4;
+
+/// The offset in the header where the number of pages in the file is stored
+pub const HEADER_PAGE_COUNT_OFFSET: usize = HEADER_PAGE_SIZE_OFFSET + 4;
+
+/// The offset in the header where the page number of the catalog is stored
+pub const HEADER_ROOT_PAGE_OFFSET: usize = HEADER_PAGE_COUNT_OFFSET + 4;
+
+/// The offset in the header where the schema cookie is stored
+pub const HEADER_SCHEMA_COOKIE_OFFSET: usize = HEADER_ROOT_PAGE_OFFSET + 4;
+
+/// The offset in the header where the page number of the first free page is stored
+pub const HEADER_FREELIST_HEAD_OFFSET: usize = HEADER_SCHEMA_COOKIE_OFFSET + 4;
+
+/// The offset in the header where the number of free pages is stored
+pub const HEADER_FREELIST_COUNT_OFFSET: usize = HEADER_FREELIST_HEAD_OFFSET + 4;
+
+/// The offset in the header where the journal mode is stored
+pub const HEADER_JOURNAL_MODE_OFFSET: usize = HEADER_FREELIST_COUNT_OFFSET + 4;
+
+/// Total size of the header in bytes
+pub const HEADER_SIZE: usize = HEADER_JOURNAL_MODE_OFFSET + 4;
+
+// Every page of the table is a node of a B+tree. The following constants
+// describe the layout of the header shared by all nodes.
+
+/// Size of the node type field in bytes
+pub const NODE_TYPE_SIZE: usize = 1;
+
+/// The offset in the page where the node type is stored
+pub const NODE_TYPE_OFFSET: usize = 0;
+
+/// Size of the is root flag in bytes
+pub const IS_ROOT_SIZE: usize = 1;
+
+/// The offset in the page where the is root flag is stored
+pub const IS_ROOT_OFFSET: usize = NODE_TYPE_OFFSET + NODE_TYPE_SIZE;
+
+/// Size of the parent pointer in bytes
+pub const PARENT_POINTER_SIZE: usize = 4;
+
+/// The offset in the page where the parent pointer is stored
+pub const PARENT_POINTER_OFFSET: usize = IS_ROOT_OFFSET + IS_ROOT_SIZE;
+
+/// Size of the field holding the size of the keys of the tree
+pub const KEY_SIZE_SIZE: usize = 4;
+
+/// The offset in the page where the size of the keys of the tree is stored
+pub const KEY_SIZE_OFFSET: usize = PARENT_POINTER_OFFSET + PARENT_POINTER_SIZE;
+
+/// Total size of the header common to all nodes
+pub const COMMON_NODE_HEADER_SIZE: usize =
+    NODE_TYPE_SIZE + IS_ROOT_SIZE + PARENT_POINTER_SIZE + KEY_SIZE_SIZE;
+
+// Leaf node header layout
+
+/// Size of the field holding the number of cells in a leaf node
+pub const LEAF_NODE_NUM_CELLS_SIZE: usize = 4;
+
+/// The offset in the page where the number of cells of a leaf node is stored
+pub const LEAF_NODE_NUM_CELLS_OFFSET: usize = COMMON_NODE_HEADER_SIZE;
+
+/// Size of the field holding the page number of the next leaf
+pub const LEAF_NODE_NEXT_LEAF_SIZE: usize = 4;
+
+/// The offset in the page where the page number of the next leaf is stored
+pub const LEAF_NODE_NEXT_LEAF_OFFSET: usize = LEAF_NODE_NUM_CELLS_OFFSET + LEAF_NODE_NUM_CELLS_SIZE;
+
+/// Size of the field holding the offset where the cells of a leaf node start
+pub const LEAF_NODE_CELL_CONTENT_SIZE: usize = 4;
+
+/// The offset in the page where the offset of the first byte of the cells is stored
+pub const LEAF_NODE_CELL_CONTENT_OFFSET: usize =
+    LEAF_NODE_NEXT_LEAF_OFFSET + LEAF_NODE_NEXT_LEAF_SIZE;
+
+/// Total size of the header of a leaf node
+pub const LEAF_NODE_HEADER_SIZE: usize = COMMON_NODE_HEADER_SIZE
+    + LEAF_NODE_NUM_CELLS_SIZE
+    + LEAF_NODE_NEXT_LEAF_SIZE
+    + LEAF_NODE_CELL_CONTENT_SIZE;
+
+// Leaf node body layout. The header is followed by an array holding the
+// offset of every cell, sorted by key. The cells are packed at the end of
+// the page, each cell holds a key, the length of the value and the value.
+// The keys of a tree all have the same size, the values vary in size.
+// How many bytes a leaf node holds depends on the page size of the file,
+// see `table::node`.
+
+/// Size of the offset of a cell in the array following the header of a leaf node
+pub const LEAF_NODE_CELL_POINTER_SIZE: usize = 4;
+
+/// Size of the length of the value of a cell
+pub const LEAF_NODE_VALUE_LENGTH_SIZE: usize = 4;
+
+/// Size of the key of a row in the B+tree of a table
+pub const ROW_KEY_SIZE: usize = 8;
+
+// Every value in the key of an index starts with a tag naming its storage
+// class, text and blobs are followed by their length.
+
+/// Size of the tag naming the storage class of a value in the key of an index
+pub const STORAGE_CLASS_SIZE: usize = 1;
+
+/// Size of the length stored after text and blobs in the key of an index
+pub const VALUE_LENGTH_SIZE: usize = 4;
+
+/// The largest number of bytes of text or a blob kept in the key of an index,
+/// longer values only keep their first bytes
+pub const INDEX_MAX_VALUE_SIZE: usize = 64;
+
+// A row is stored as a record, a header of serial types, one per value,
+// followed by the values. The following serial types name the storage
+// class of a value, see `table::record`.
+
+/// Serial type of `NULL`
+pub const SERIAL_TYPE_NULL: u64 = 0;
+
+/// Serial type of a 64 bit floating point number
+pub const SERIAL_TYPE_REAL: u64 = 7;
+
+/// Serial type of the integer 0, which takes no bytes
+pub const SERIAL_TYPE_ZERO: u64 = 8;
+
+/// Serial type of the integer 1, which takes no bytes
+pub const SERIAL_TYPE_ONE: u64 = 9;
+
+/// Serial type of an empty blob, a blob of n bytes has the serial type 12 + 2n
+pub const SERIAL_TYPE_BLOB: u64 = 12;
+
+/// Serial type of empty text, text of n bytes has the serial type 13 + 2n
+pub const SERIAL_TYPE_TEXT: u64 = 13;
+
+// A value too large for its cell to fit into half a leaf node keeps
+// its first bytes in the cell, followed by the page number of the first of a
+// chain of overflow pages holding the rest. Every overflow page starts with
+// the page number of the next page of the chain, 0 on the last page.
+
+/// Size of the page number of the first overflow page, stored at the end of a cell
+pub const LEAF_NODE_OVERFLOW_POINTER_SIZE: usize = 4;
+
+/// The offset in an overflow page where the page number of the next page is stored
+pub const OVERFLOW_PAGE_NEXT_OFFSET: usize = 0;
+
+/// Size of the page number of the next overflow page
+pub const OVERFLOW_PAGE_NEXT_SIZE: usize = 4;
+
+// Internal node header layout
+
+/// Size of the field holding the number of keys in an internal node
+pub const INTERNAL_NODE_NUM_KEYS_SIZE: usize = 4;
+
+/// The offset in the page where the number of keys of an internal node is stored
+pub const INTERNAL_NODE_NUM_KEYS_OFFSET: usize = COMMON_NODE_HEADER_SIZE;
+
+/// Size of the field holding the page number of the right child
+pub const INTERNAL_NODE_RIGHT_CHILD_SIZE: usize = 4;
+
+/// The offset in the page where the page number of the right child is stored
+pub const INTERNAL_NODE_RIGHT_CHILD_OFFSET: usize =
+    INTERNAL_NODE_NUM_KEYS_OFFSET + INTERNAL_NODE_NUM_KEYS_SIZE;
+
+/// Total size of the header of an internal node
+pub const INTERNAL_NODE_HEADER_SIZE: usize =
+    COMMON_NODE_HEADER_SIZE + INTERNAL_NODE_NUM_KEYS_SIZE + INTERNAL_NODE_RIGHT_CHILD_SIZE;
+
+// Internal node body layout. The body is an array of cells, each cell holds
+// a child pointer followed by the largest key present in that child.
+
+/// Size of the child pointer of an internal node cell
+pub const INTERNAL_NODE_CHILD_SIZE: usize = 4;
+
+/// Number of keys an internal node must be able to hold for it to be split
+pub const INTERNAL_NODE_MIN_CAPACITY: usize = 4;
+
+/// The offset in a free page where the page number of the next free page is stored
+pub const FREE_PAGE_NEXT_OFFSET: usize = 0;
+
+/// Page number used to mark a missing child, e.g. the right child of an empty internal node
+pub const INVALID_PAGE_NUM: u32 = u32::MAX;
+
+/// Number of bytes of rows a sort holds in memory,
+/// more rows are sorted in runs written to temporary pages
+pub const SORTER_MEMORY_LIMIT: usize = 64 * SORTER_PAGE_SIZE as usize;
+
+// The rollback journal starts with a header, followed by records holding
+// the original image of a page. The following constants specify its layout.
+
+/// Magic string identifying a rollback journal
+pub const JOURNAL_MAGIC: &[u8; JOURNAL_MAGIC_SIZE] = b"rsqljrnl";
+
+/// Size of the magic string of the journal in bytes
+pub const JOURNAL_MAGIC_SIZE: usize = 8;
+
+/// The offset in the journal header where the page size is stored
+pub const JOURNAL_PAGE_SIZE_OFFSET: usize = JOURNAL_MAGIC_SIZE;
+
+/// The offset in the journal header where the number of pages the database file
+/// had before the transaction is stored
+pub const JOURNAL_PAGE_COUNT_OFFSET: usize = JOURNAL_PAGE_SIZE_OFFSET + 4;
+
+/// Total size of the journal header in bytes
+pub const JOURNAL_HEADER_SIZE: usize = JOURNAL_PAGE_COUNT_OFFSET + 4;
+
+/// Size of the page number stored before the image of a page in a journal record
+pub const JOURNAL_RECORD_PAGE_NUM_SIZE: usize = 4;
+
+/// Size of the checksum stored after the image of a page in a journal record
+pub const JOURNAL_RECORD_CHECKSUM_SIZE: usize = 4;
+
+// In WAL mode, committed pages are appended to the write-ahead log as frames.
+// The log starts with a header, every frame starts with a header followed
+// by the image of the page. The following constants specify their layout.
+
+/// Magic string identifying a write-ahead log
+pub const WAL_MAGIC: &[u8; WAL_MAGIC_SIZE] = b"rsqlwal\0";
+
+/// Size of the magic string of the write-ahead log in bytes
+pub const WAL_MAGIC_SIZE: usize = 8;
+
+/// The offset in the log header where the page size is stored
+pub const WAL_PAGE_SIZE_OFFSET: usize = WAL_MAGIC_SIZE;
+
+/// The offset in the log header where the salt is stored,
+/// which changes every time the log is reset by a checkpoint
+pub const WAL_SALT_OFFSET: usize = WAL_PAGE_SIZE_OFFSET + 4;
+
+/// Total size of the log header in bytes
+pub const WAL_HEADER_SIZE: usize = WAL_SALT_OFFSET + 4;
+
+/// The offset in a frame header where the page number is stored
+pub const WAL_FRAME_PAGE_NUM_OFFSET: usize = 0;
+
+/// The offset in a frame header where the number of pages of the database
+/// after the commit is stored, 0 in frames which do not end a commit
+pub const WAL_FRAME_COMMIT_SIZE_OFFSET: usize = WAL_FRAME_PAGE_NUM_OFFSET + 4;
+
+/// The offset in a frame header where the salt of the log is stored
+pub const WAL_FRAME_SALT_OFFSET: usize = WAL_FRAME_COMMIT_SIZE_OFFSET + 4;
+
+/// The offset in a frame header where the checksum of the frame is stored,
+/// which covers the checksum of the previous frame, the frame header and the page
+pub const WAL_FRAME_CHECKSUM_OFFSET: usize = WAL_FRAME_SALT_OFFSET + 4;
+
+/// Total size of a frame header in bytes
+pub const WAL_FRAME_HEADER_SIZE: usize = WAL_FRAME_CHECKSUM_OFFSET + 4;
+
+/// Number of frames in the log after which a commit runs a checkpoint
+pub const WAL_AUTOCHECKPOINT: u32 = 1000;
@@ -0,0 +1,298 @@
+//! # Database
+//!
+//! A database file holding any number of named tables and indexes,
+//! each stored in its own B+tree and listed in the catalog table
+
+use std::collections::HashMap;
+
+use crate::constants::ROW_KEY_SIZE;
+use crate::table::catalog::{
+    catalog_schema, delete_catalog_entry, insert_catalog_entry, read_catalog, CatalogEntry,
+    CATALOG_TABLE_NAME,
+};
+use crate::table::index::Index;
+use crate::table::node::{initialize_leaf_node, set_node_root};
+use crate::table::pager::Pager;
+use crate::table::schema::Schema;
+use crate::table::Table;
+use crate::vm::statement::CreateIndex;
+use crate::vm::Transaction;
+
+/// Structure to store the pager holding the pages of the database file
+/// and the tables stored in it
+pub struct Database {
+    pub pager: Pager,
+    /// The catalog table, listing the tables of the database
+    pub catalog: Table,
+    /// The user tables along with their indexes, keyed by their lowercase name
+    pub tables: HashMap<String, Table>,
+    /// Whether statements are committed one by one or together
+    pub transaction: Transaction,
+}
+
+impl Database {
+    /// Opens a file to load the database from,
+    /// if the file is not present, a new file is created
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - A string slice holding the file name
+    /// * `page_size` - The page size of a new file, which must be valid.
+    ///   An existing file keeps the page size it was created with.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsqlite::database::Database;
+    /// let database = Database::open("test.db", 4096);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Function might panic if there is some problem in creating or opening a file
+    pub fn open(filename: &str, page_size: u32) -> Self {
+        let mut pager = Pager::open(filename, page_size);
+        let catalog = Table {
+            root_page_num: pager.header.root_page_num,
+            schema: catalog_schema(),
+            indexes: Vec::new(),
+        };
+
+        if pager.num_pages == 0 {
+            initialize_file(&mut pager);
+        }
+        let tables = load_tables(&mut pager);
+
+        Database {
+            pager,
+            catalog,
+            tables,
+            transaction: Transaction::Autocommit,
+        }
+    }
+
+    /// Returns the table with the given name, which may be the catalog table
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the table, compared case-insensitively
+    pub fn table(&self, name: &str) -> Option<&Table> {
+        if name.eq_ignore_ascii_case(CATALOG_TABLE_NAME) {
+            return Some(&self.catalog);
+        }
+        self.tables.get(&name.to_ascii_lowercase())
+    }
+
+    /// Returns true if a table with the given name exists,
+    /// including the catalog table
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the table, compared case-insensitively
+    pub fn has_table(&self, name: &str) -> bool {
+        self.table(name).is_some()
+    }
+
+    /// Returns the index with the given name along with the name of its table
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the index, compared case-insensitively
+    pub fn index(&self, name: &str) -> Option<(&str, &Index)> {
+        self.tables.iter().find_map(|(table_name, table)| {
+            table
+                .indexes
+                .iter()
+                .find(|index| index.name.eq_ignore_ascii_case(name))
+                .map(|index| (table_name.as_str(), index))
+        })
+    }
+
+    /// Returns true if an index with the given name exists
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the index, compared case-insensitively
+    pub fn has_index(&self, name: &str) -> bool {
+        self.index(name).is_some()
+    }
+
+    /// Creates the table described by the schema,
+    /// allocating its root page and storing it in the catalog
+    ///
+    /// # Arguments
+    ///
+    /// * `schema` - The definition of the table
+    pub fn create_table(&mut self, schema: Schema) {
+        let root_page_num = self.pager.get_unused_page_num();
+        let root_node = self.pager.get_page_mut(root_page_num);
+        initialize_leaf_node(root_node, ROW_KEY_SIZE);
+        set_node_root(root_node, true);
+
+        let entry = CatalogEntry {
+            entry_type: "table".to_string(),
+            name: schema.name.clone(),
+            root_page_num,
+            sql: schema.sql.clone(),
+        };
+        insert_catalog_entry(&mut self.pager, &entry);
+        self.pager.header.schema_cookie += 1;
+
+        self.tables.insert(
+            schema.name.to_ascii_lowercase(),
+            Table {
+                root_page_num,
+                schema,
+                indexes: Vec::new(),
+            },
+        );
+    }
+
+    /// Creates an empty index on a table,
+    /// allocating its root page and storing it in the catalog.
+    /// Returns the new index, which the caller fills with the rows of the table.
+    ///
+    /// # Arguments
+    ///
+    /// * `create` - The definition of the index, on a table and columns which exist
+    pub fn create_index(&mut self, create: &CreateIndex) -> Index {
+        let root_page_num = self.pager.get_unused_page_num();
+        let table = self
+            .tables
+            .get_mut(&create.table_name.to_ascii_lowercase())
+            .unwrap();
+        let index = Index::new(create, &table.schema, root_page_num).unwrap();
+
+        let root_node = self.pager.get_page_mut(root_page_num);
+        initialize_leaf_node(root_node, index.key_size(&table.schema));
+        set_node_root(root_node, true);
+
+        let entry = CatalogEntry {
+            entry_type: "index".to_string(),
+            name: create.name.clone(),
+            root_page_num,
+            sql: create.sql.clone(),
+        };
+        insert_catalog_entry(&mut self.pager, &entry);
+        self.pager.header.schema_cookie += 1;
+
+        table.indexes.push(index.clone());
+        index
+    }
+
+    /// Removes an index, freeing the pages of its B+tree
+    /// and deleting it from the catalog
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the index, which must exist
+    pub fn drop_index(&mut self, name: &str) {
+        for table in self.tables.values_mut() {
+            let position = table
+                .indexes
+                .iter()
+                .position(|index| index.name.eq_ignore_ascii_case(name));
+            if let Some(position) = position {
+                let index = table.indexes.remove(position);
+                delete_catalog_entry(&mut self.pager, index.root_page_num);
+                self.pager.free_tree(index.root_page_num);
+                self.pager.header.schema_cookie += 1;
+                return;
+            }
+        }
+    }
+
+    /// Changes the page size of a database which holds no tables yet.
+    /// The file is emptied and starts over with pages of the new size.
+    ///
+    /// # Arguments
+    ///
+    /// * `page_size` - The new page size, which must be valid
+    pub fn set_page_size(&mut self, page_size: u32) {
+        self.pager.reset(page_size);
+        initialize_file(&mut self.pager);
+        self.catalog.root_page_num = self.pager.header.root_page_num;
+    }
+
+    /// Commits the changes made since the last commit to the file on the disk
+    pub fn commit(&mut self) {
+        self.pager.commit();
+    }
+
+    /// Undoes the changes made since the last commit. The file is opened again,
+    /// so that the tables are those of the last commit.
+    pub fn rollback(&mut self) {
+        self.pager.rollback();
+        if self.pager.num_pages == 0 {
+            initialize_file(&mut self.pager);
+        }
+        self.catalog.root_page_num = self.pager.header.root_page_num;
+        self.tables = load_tables(&mut self.pager);
+    }
+
+    /// Safely closes the database. A transaction which was
+    /// started but not committed is rolled back.
+    pub fn close(&mut self) {
+        match self.transaction {
+            Transaction::Autocommit => self.commit(),
+            Transaction::Active => self.rollback(),
+        }
+    }
+}
+
+/// Sets up the pages of a new database file. Page 0 holds the header,
+/// the catalog table starts out as an empty root leaf.
+///
+/// # Arguments
+///
+/// * `pager` - The pager of the new file, which holds no pages yet
+fn initialize_file(pager: &mut Pager) {
+    pager.get_page(0);
+    let catalog_root = pager.get_page_mut(pager.header.root_page_num);
+    initialize_leaf_node(catalog_root, ROW_KEY_SIZE);
+    set_node_root(catalog_root, true);
+}
+
+/// Loads the tables listed in the catalog, along with their indexes
+///
+/// # Arguments
+///
+/// * `pager` - The pager of the database file
+fn load_tables(pager: &mut Pager) -> HashMap<String, Table> {
+    // A page freed by one object may be reused by an object created later,
+    // so all the tables are loaded before their indexes
+    let entries = read_catalog(pager);
+    let mut tables = HashMap::new();
+    for entry in entries.iter().filter(|entry| entry.entry_type == "table") {
+        match Schema::parse(&entry.sql) {
+            Some(schema) => {
+                let table = Table {
+                    root_page_num: entry.root_page_num,
+                    schema,
+                    indexes: Vec::new(),
+                };
+                tables.insert(entry.name.to_ascii_lowercase(), table);
+            }
+            None => {
+                println!("Error: malformed schema '{}'.", entry.sql);
+                std::process::exit(1);
+            }
+        }
+    }
+    for entry in entries.iter().filter(|entry| entry.entry_type == "index") {
+        let create = Index::parse(&entry.sql);
+        let table = create
+            .as_ref()
+            .and_then(|create| tables.get_mut(&create.table_name.to_ascii_lowercase()));
+        let index = match (&create, table) {
+            (Some(create), Some(table)) => Index::new(create, &table.schema, entry.root_page_num)
+                .map(|index| table.indexes.push(index)),
+            _ => None,
+        };
+        if index.is_none() {
+            println!("Error: malformed schema '{}'.", entry.sql);
+            std::process::exit(1);
+        }
+    }
+    tables
+}
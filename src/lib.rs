@@ -0,0 +1,10 @@
+//! # RSQLite
+//!
+//! A simple SQLite clone. The modules are exposed as a library,
+//! the REPL in `main.rs` is one consumer of them.
+
+pub mod buffer;
+pub mod constants;
+pub mod database;
+pub mod table;
+pub mod vm;
@@ -1,17 +1,14 @@
 use std::env;
 use std::io::{self, Write};
 
-mod buffer;
-mod constants;
-mod table;
-mod vm;
-
-use buffer::InputBuffer;
-use table::Table;
-use vm::statement::Statement;
-use vm::{
+use rsqlite::buffer::InputBuffer;
+use rsqlite::constants::DEFAULT_PAGE_SIZE;
+use rsqlite::database::Database;
+use rsqlite::table::pager::is_valid_page_size;
+use rsqlite::vm::statement::Statement;
+use rsqlite::vm::{
     do_meta_command, execute_statement, prepare_statement, ExecuteResult, MetaCommandResult,
-    PrepareResult,
+    PrepareResult, Settings,
 };
 
 /// Prints basic prompt onto stdout
@@ -21,18 +18,36 @@ fn print_prompt() {
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut filename = None;
+    let mut page_size = DEFAULT_PAGE_SIZE;
 
-    if args.len() < 2 {
-        println!("Must supply database filename.");
-        println!("cargo run <filename>");
-        std::process::exit(1);
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--page-size" {
+            page_size = match args.next().and_then(|size| size.parse().ok()) {
+                Some(size) if is_valid_page_size(size) => size,
+                _ => {
+                    println!("Page size must be a power of two between 512 and 65536.");
+                    std::process::exit(1);
+                }
+            };
+        } else {
+            filename = Some(arg);
+        }
     }
 
-    let filename = &args[1];
+    let filename = match filename {
+        Some(filename) => filename,
+        None => {
+            println!("Must supply database filename.");
+            println!("cargo run [--page-size <size>] <filename>");
+            std::process::exit(1);
+        }
+    };
 
     let mut input_buffer = InputBuffer::new();
-    let mut table = Table::db_open(filename);
+    let mut database = Database::open(&filename, page_size);
+    let mut settings = Settings::default();
 
     loop {
         print_prompt();
@@ -43,7 +58,10 @@ fn main() {
         }
 
         if input_buffer.buffer.starts_with('.') {
-            match do_meta_command(&input_buffer, &mut table) {
+            match do_meta_command(&input_buffer, &mut database, &mut settings) {
+                MetaCommandResult::Success => {
+                    continue;
+                }
                 MetaCommandResult::UnrecognizedCommand => {
                     println!("Unrecognized command '{}'.", input_buffer.buffer);
                     continue;
@@ -53,35 +71,109 @@ fn main() {
 
         let mut statement: Statement = Statement::new();
 
-        match prepare_statement(&input_buffer, &mut statement) {
+        match prepare_statement(&input_buffer, &mut statement, &database) {
             PrepareResult::Success => (),
-            PrepareResult::UnrecognizedStatement => {
-                println!(
-                    "Unrecognized keyword at the start of '{}'.",
-                    input_buffer.buffer
-                );
-                continue;
-            }
-            PrepareResult::SyntaxError => {
-                println!("Syntax error. Could not parse statement.");
+            PrepareResult::SyntaxError(error) => {
+                println!("{}", error);
                 continue;
             }
             PrepareResult::StringTooLong => {
                 println!("String is too long.");
                 continue;
             }
-            PrepareResult::NegativeID => {
-                println!("ID must be positive.");
+            PrepareResult::NoSuchTable => {
+                println!("Error: No such table.");
+                continue;
+            }
+            PrepareResult::NoSuchIndex => {
+                println!("Error: No such index.");
+                continue;
+            }
+            PrepareResult::ReadOnlyTable => {
+                println!("Error: Table may not be modified.");
+                continue;
+            }
+            PrepareResult::KeyTooLarge => {
+                println!("Error: Index key is too large.");
+                continue;
+            }
+            PrepareResult::ValueCountMismatch => {
+                println!("Error: Wrong number of values for the columns of the table.");
+                continue;
+            }
+            PrepareResult::NoSuchColumn(name) => {
+                println!("Error: No such column: {}.", name);
+                continue;
+            }
+            PrepareResult::MisusedAggregate(name) => {
+                println!("Error: Misuse of aggregate function {}().", name);
+                continue;
+            }
+            PrepareResult::UnknownPragma(name) => {
+                println!("Error: Unknown pragma: {}.", name);
+                continue;
+            }
+            PrepareResult::InvalidPageSize => {
+                println!("Error: Page size must be a power of two between 512 and 65536.");
+                continue;
+            }
+            PrepareResult::InvalidCacheSize => {
+                println!("Error: Cache size must be a positive number of pages.");
+                continue;
+            }
+            PrepareResult::InvalidJournalMode => {
+                println!("Error: Journal mode must be delete or wal.");
                 continue;
             }
         }
 
-        match execute_statement(&statement, &mut table) {
+        match execute_statement(&statement, &mut database) {
             ExecuteResult::Success => {
                 println!("Executed.");
             }
-            ExecuteResult::TableFull => {
-                println!("Error: Table full.");
+            ExecuteResult::Rows(result_set) => {
+                result_set.print(settings.headers);
+                println!("Executed.");
+            }
+            ExecuteResult::RowsAffected(count) => {
+                let noun = if count == 1 { "row" } else { "rows" };
+                println!("Executed. {} {} affected.", count, noun);
+            }
+            ExecuteResult::DuplicateKey => {
+                println!("Error: Duplicate key.");
+            }
+            ExecuteResult::UniqueConstraint(name) => {
+                println!("Error: Duplicate values in unique index {}.", name);
+            }
+            ExecuteResult::TableExists => {
+                println!("Error: Table already exists.");
+            }
+            ExecuteResult::IndexExists => {
+                println!("Error: Index already exists.");
+            }
+            ExecuteResult::StringTooLong => {
+                println!("String is too long.");
+            }
+            ExecuteResult::TypeMismatch => {
+                println!("Error: Value does not match the type of its column.");
+            }
+            ExecuteResult::DatabaseNotEmpty => {
+                println!("Error: Page size can only be changed before tables are created.");
+            }
+            ExecuteResult::PageSizeInWalMode => {
+                println!("Error: Page size cannot be changed in WAL mode.");
+            }
+            ExecuteResult::TransactionActive => {
+                println!("Error: Cannot start a transaction within a transaction.");
+            }
+            ExecuteResult::NoTransaction => {
+                println!("Error: No transaction is active.");
+            }
+            ExecuteResult::JournalModeInTransaction => {
+                println!("Error: Cannot change the journal mode within a transaction.");
+            }
+            ExecuteResult::IntegerOverflow => {
+                println!("Error: Integer overflow.");
             }
         }
     }
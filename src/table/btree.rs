@@ -0,0 +1,583 @@
+//! # B+tree
+//!
+//! Operations to insert into, delete from and print a B+tree
+//! whose nodes are pages managed by the pager
+
+use super::node::{
+    compare_keys, get_node_type, initialize_internal_node, initialize_leaf_node,
+    internal_node_cell_offset, internal_node_cell_size, internal_node_child,
+    internal_node_child_index, internal_node_find_child, internal_node_key,
+    internal_node_max_cells, internal_node_min_cells, internal_node_num_keys,
+    internal_node_remove_cell, internal_node_right_child, is_node_root, leaf_node_cells,
+    leaf_node_free_space, leaf_node_insert_cell, leaf_node_is_underfull, leaf_node_key,
+    leaf_node_local_size, leaf_node_new_cell, leaf_node_next_leaf, leaf_node_num_cells,
+    leaf_node_overflow_page, leaf_node_remove_cell, leaf_node_space_for_cells, leaf_node_value,
+    leaf_node_value_size, node_key_size, node_parent, set_internal_node_child,
+    set_internal_node_key, set_internal_node_num_keys, set_internal_node_right_child,
+    set_leaf_node_cells, set_leaf_node_next_leaf, set_node_parent, set_node_root,
+    update_internal_node_key, NodeType,
+};
+use super::pager::Pager;
+use super::read_row_key;
+use crate::constants::{INVALID_PAGE_NUM, LEAF_NODE_CELL_POINTER_SIZE};
+use std::cmp::Ordering;
+
+impl Pager {
+    /// Returns the largest key present in the subtree rooted at the given page
+    pub fn get_node_max_key(&mut self, page_num: u32) -> Vec<u8> {
+        let node = self.get_page(page_num);
+        match get_node_type(node) {
+            NodeType::Leaf => leaf_node_key(node, leaf_node_num_cells(node) - 1).to_vec(),
+            NodeType::Internal => {
+                let right_child = internal_node_right_child(node);
+                self.get_node_max_key(right_child)
+            }
+        }
+    }
+
+    /// Inserts a value at the given position of a leaf node,
+    /// splitting the node if it is full
+    ///
+    /// # Arguments
+    ///
+    /// * `page_num` - The page number of the leaf node
+    /// * `cell_num` - The index of the cell where the value must be stored
+    /// * `key` - The key of the value
+    /// * `value` - The value to be stored, e.g. a serialized row
+    pub fn leaf_node_insert(&mut self, page_num: u32, cell_num: u32, key: &[u8], value: &[u8]) {
+        let local_size = leaf_node_local_size(self.page_size, key.len(), value.len());
+        let overflow_page_num = if local_size < value.len() {
+            self.write_overflow_chain(&value[local_size..])
+        } else {
+            0
+        };
+        let cell = leaf_node_new_cell(self.page_size, key, value, overflow_page_num);
+        let node = self.get_page_mut(page_num);
+
+        if cell.len() + LEAF_NODE_CELL_POINTER_SIZE > leaf_node_free_space(node) {
+            self.leaf_node_split_and_insert(page_num, cell_num, cell);
+            return;
+        }
+        leaf_node_insert_cell(node, cell_num, &cell);
+    }
+
+    /// Replaces the value of the cell at the given position of a leaf node,
+    /// splitting the node if the new value does not fit
+    ///
+    /// # Arguments
+    ///
+    /// * `page_num` - The page number of the leaf node
+    /// * `cell_num` - The index of the cell whose value is replaced
+    /// * `value` - The new value
+    pub fn leaf_node_update(&mut self, page_num: u32, cell_num: u32, value: &[u8]) {
+        let node = self.get_page_mut(page_num);
+        let key = leaf_node_key(node, cell_num).to_vec();
+        let overflow_page_num = leaf_node_overflow_page(node, cell_num);
+        leaf_node_remove_cell(node, cell_num);
+        self.free_overflow_chain(overflow_page_num);
+        self.leaf_node_insert(page_num, cell_num, &key, value);
+    }
+
+    /// Returns the whole value of the cell at the given position of a leaf node,
+    /// reading the bytes which do not fit into the cell from its overflow pages
+    ///
+    /// # Arguments
+    ///
+    /// * `page_num` - The page number of the leaf node
+    /// * `cell_num` - The index of the cell
+    pub fn leaf_node_read_value(&mut self, page_num: u32, cell_num: u32) -> Vec<u8> {
+        let node = self.get_page(page_num);
+        let value_size = leaf_node_value_size(node, cell_num);
+        let overflow_page_num = leaf_node_overflow_page(node, cell_num);
+        let mut value = Vec::with_capacity(value_size);
+        value.extend_from_slice(leaf_node_value(node, cell_num));
+
+        let overflow_size = value_size - value.len();
+        self.read_overflow_chain(overflow_page_num, overflow_size, &mut value);
+        value
+    }
+
+    /// Creates a new leaf node and moves about half of the bytes of the cells
+    /// over, inserting the new cell in one of the two nodes.
+    /// Then updates the parent or creates a new root.
+    fn leaf_node_split_and_insert(&mut self, old_page_num: u32, cell_num: u32, cell: Vec<u8>) {
+        let old_max = self.get_node_max_key(old_page_num);
+        let new_page_num = self.get_unused_page_num();
+
+        let old_node = self.get_page(old_page_num).to_vec();
+        let mut cells = leaf_node_cells(&old_node);
+        cells.insert(cell_num as usize, cell);
+        let left_split_count = balanced_split(&cells, leaf_node_space_for_cells(self.page_size));
+
+        let mut left_node = old_node.clone();
+        let mut right_node = vec![0u8; old_node.len()];
+        initialize_leaf_node(&mut right_node, node_key_size(&old_node));
+        set_node_parent(&mut right_node, node_parent(&old_node));
+        set_leaf_node_next_leaf(&mut right_node, leaf_node_next_leaf(&old_node));
+        set_leaf_node_next_leaf(&mut left_node, new_page_num);
+
+        set_leaf_node_cells(&mut left_node, &cells[..left_split_count]);
+        set_leaf_node_cells(&mut right_node, &cells[left_split_count..]);
+
+        self.get_page_mut(old_page_num).copy_from_slice(&left_node);
+        self.get_page_mut(new_page_num).copy_from_slice(&right_node);
+
+        if is_node_root(&old_node) {
+            self.create_new_root(old_page_num, new_page_num);
+        } else {
+            let parent_page_num = node_parent(&old_node);
+            let new_max = self.get_node_max_key(old_page_num);
+            update_internal_node_key(self.get_page_mut(parent_page_num), &old_max, &new_max);
+            self.internal_node_insert(parent_page_num, new_page_num);
+        }
+    }
+
+    /// Handles splitting the root.
+    /// The old root is copied to a new page and becomes the left child.
+    /// The root page is reinitialized to contain the new root node,
+    /// which points to the two children.
+    fn create_new_root(&mut self, root_page_num: u32, right_child_page_num: u32) {
+        let root = self.get_page(root_page_num).to_vec();
+        let key_size = node_key_size(&root);
+
+        // Loading the right child first makes sure it is allocated
+        // before picking a page for the left child
+        let right_child = self.get_page_mut(right_child_page_num);
+        if get_node_type(&root) == NodeType::Internal {
+            initialize_internal_node(right_child, key_size);
+        }
+        let left_child_page_num = self.get_unused_page_num();
+
+        // The left child has data copied from old root
+        let left_child = self.get_page_mut(left_child_page_num);
+        left_child.copy_from_slice(&root);
+        set_node_root(left_child, false);
+        set_node_parent(left_child, root_page_num);
+
+        if get_node_type(left_child) == NodeType::Internal {
+            // The children of the copied node have a new parent
+            let num_keys = internal_node_num_keys(left_child);
+            for i in 0..=num_keys {
+                let child_page_num = internal_node_child(self.get_page(left_child_page_num), i);
+                set_node_parent(self.get_page_mut(child_page_num), left_child_page_num);
+            }
+        }
+
+        set_node_parent(self.get_page_mut(right_child_page_num), root_page_num);
+
+        // Root node is a new internal node with one key and two children
+        let left_child_max_key = self.get_node_max_key(left_child_page_num);
+        let root = self.get_page_mut(root_page_num);
+        initialize_internal_node(root, key_size);
+        set_node_root(root, true);
+        set_internal_node_num_keys(root, 1);
+        set_internal_node_child(root, 0, left_child_page_num);
+        set_internal_node_key(root, 0, &left_child_max_key);
+        set_internal_node_right_child(root, right_child_page_num);
+    }
+
+    /// Adds a new child/key pair to the parent that corresponds to the child
+    fn internal_node_insert(&mut self, parent_page_num: u32, child_page_num: u32) {
+        let child_max_key = self.get_node_max_key(child_page_num);
+        let parent = self.get_page_mut(parent_page_num);
+        let index = internal_node_find_child(parent, &child_max_key);
+        let original_num_keys = internal_node_num_keys(parent);
+
+        if original_num_keys >= internal_node_max_cells(parent) {
+            self.internal_node_split_and_insert(parent_page_num, child_page_num);
+            return;
+        }
+
+        let right_child_page_num = internal_node_right_child(parent);
+        // An internal node with an invalid right child is empty
+        if right_child_page_num == INVALID_PAGE_NUM {
+            set_internal_node_right_child(parent, child_page_num);
+            return;
+        }
+
+        let right_max_key = self.get_node_max_key(right_child_page_num);
+        let parent = self.get_page_mut(parent_page_num);
+        set_internal_node_num_keys(parent, original_num_keys + 1);
+
+        if compare_keys(&child_max_key, &right_max_key) == Ordering::Greater {
+            // Replace right child
+            set_internal_node_child(parent, original_num_keys, right_child_page_num);
+            set_internal_node_key(parent, original_num_keys, &right_max_key);
+            set_internal_node_right_child(parent, child_page_num);
+        } else {
+            // Make room for the new cell
+            parent.copy_within(
+                internal_node_cell_offset(parent, index)
+                    ..internal_node_cell_offset(parent, original_num_keys),
+                internal_node_cell_offset(parent, index) + internal_node_cell_size(parent),
+            );
+            set_internal_node_child(parent, index, child_page_num);
+            set_internal_node_key(parent, index, &child_max_key);
+        }
+    }
+
+    /// Splits a full internal node into two and inserts the new child
+    /// into whichever of them it belongs to
+    fn internal_node_split_and_insert(&mut self, parent_page_num: u32, child_page_num: u32) {
+        let mut old_page_num = parent_page_num;
+        let old_max = self.get_node_max_key(old_page_num);
+        let child_max = self.get_node_max_key(child_page_num);
+        let new_page_num = self.get_unused_page_num();
+        let old_node = self.get_page(old_page_num);
+        let key_size = node_key_size(old_node);
+        let max_cells = internal_node_max_cells(old_node);
+
+        // Declaring a flag before updating pointers which records whether
+        // this operation involves splitting the root. If it does, we will
+        // insert our newly created node during the step where the table's
+        // new root is created. If it does not, we have to insert the newly
+        // created node into its parent after the old node's keys have been
+        // transferred over.
+        let splitting_root = is_node_root(self.get_page(old_page_num));
+
+        let parent_page_num = if splitting_root {
+            let root_page_num = old_page_num;
+            self.create_new_root(root_page_num, new_page_num);
+            // The old node is now the left child of the new root
+            old_page_num = internal_node_child(self.get_page(root_page_num), 0);
+            root_page_num
+        } else {
+            initialize_internal_node(self.get_page_mut(new_page_num), key_size);
+            node_parent(self.get_page(old_page_num))
+        };
+
+        // First put the right child into the new node and set the right child of the old node to invalid
+        let cur_page_num = internal_node_right_child(self.get_page(old_page_num));
+        self.internal_node_insert(new_page_num, cur_page_num);
+        set_node_parent(self.get_page_mut(cur_page_num), new_page_num);
+        set_internal_node_right_child(self.get_page_mut(old_page_num), INVALID_PAGE_NUM);
+
+        // For each key until you get to the middle key, move the key and the child to the new node
+        for i in (max_cells / 2 + 1..max_cells).rev() {
+            let cur_page_num = internal_node_child(self.get_page(old_page_num), i);
+            self.internal_node_insert(new_page_num, cur_page_num);
+            set_node_parent(self.get_page_mut(cur_page_num), new_page_num);
+
+            let old_node = self.get_page_mut(old_page_num);
+            let old_num_keys = internal_node_num_keys(old_node);
+            set_internal_node_num_keys(old_node, old_num_keys - 1);
+        }
+
+        // Set child before middle key, which is now the highest key, to be node's right child,
+        // and decrement number of keys
+        let old_node = self.get_page_mut(old_page_num);
+        let old_num_keys = internal_node_num_keys(old_node);
+        let new_right_child = internal_node_child(old_node, old_num_keys - 1);
+        set_internal_node_right_child(old_node, new_right_child);
+        set_internal_node_num_keys(old_node, old_num_keys - 1);
+
+        // Determine which of the two nodes after the split should contain the child to be inserted,
+        // and insert the child
+        let max_after_split = self.get_node_max_key(old_page_num);
+        let destination_page_num = if compare_keys(&child_max, &max_after_split) == Ordering::Less {
+            old_page_num
+        } else {
+            new_page_num
+        };
+        self.internal_node_insert(destination_page_num, child_page_num);
+        set_node_parent(self.get_page_mut(child_page_num), destination_page_num);
+
+        let new_old_max = self.get_node_max_key(old_page_num);
+        update_internal_node_key(self.get_page_mut(parent_page_num), &old_max, &new_old_max);
+
+        if !splitting_root {
+            // Splitting the parent in turn may move the new node to a new parent
+            set_node_parent(self.get_page_mut(new_page_num), parent_page_num);
+            self.internal_node_insert(parent_page_num, new_page_num);
+        }
+    }
+
+    /// Removes the cell at the given position of a leaf node,
+    /// then rebalances the tree if the node is left with too few cells
+    ///
+    /// # Arguments
+    ///
+    /// * `page_num` - The page number of the leaf node
+    /// * `cell_num` - The index of the cell to be removed
+    pub fn leaf_node_delete(&mut self, page_num: u32, cell_num: u32) {
+        let node = self.get_page_mut(page_num);
+        let num_cells = leaf_node_num_cells(node);
+        let overflow_page_num = leaf_node_overflow_page(node, cell_num);
+        leaf_node_remove_cell(node, cell_num);
+        self.free_overflow_chain(overflow_page_num);
+
+        let node = self.get_page(page_num);
+        if is_node_root(node) {
+            return;
+        }
+        if leaf_node_is_underfull(node) {
+            self.rebalance(page_num);
+        } else if cell_num == num_cells - 1 {
+            // The largest key of the node is gone
+            self.update_parent_keys(page_num);
+        }
+    }
+
+    /// Fixes a node left with too few cells by a deletion. The node is merged
+    /// with a sibling if both fit into one node, otherwise the cells of both
+    /// are divided evenly between them. Merging removes a child from the parent,
+    /// so the parent is fixed in turn. A root left with a single child
+    /// is replaced by that child.
+    fn rebalance(&mut self, page_num: u32) {
+        let node = self.get_page(page_num);
+        let node_type = get_node_type(node);
+
+        if is_node_root(node) {
+            if node_type == NodeType::Internal && internal_node_num_keys(node) == 0 {
+                self.collapse_root(page_num);
+            }
+            return;
+        }
+
+        let underfull = match node_type {
+            NodeType::Leaf => leaf_node_is_underfull(node),
+            NodeType::Internal => internal_node_num_keys(node) < internal_node_min_cells(node),
+        };
+        if !underfull {
+            return;
+        }
+
+        // Pair the node with its right sibling,
+        // or with its left sibling if it is the right child
+        let parent_page_num = node_parent(node);
+        let parent = self.get_page(parent_page_num);
+        let index = internal_node_child_index(parent, page_num);
+        let left_index = if index < internal_node_num_keys(parent) {
+            index
+        } else {
+            index - 1
+        };
+        let left_page_num = internal_node_child(parent, left_index);
+        let right_page_num = internal_node_child(parent, left_index + 1);
+
+        let merged = match node_type {
+            NodeType::Leaf => self.leaf_nodes_rebalance(left_page_num, right_page_num),
+            NodeType::Internal => self.internal_nodes_rebalance(left_page_num, right_page_num),
+        };
+
+        if merged {
+            // The left node takes the place of the right one, which had the larger key
+            let parent = self.get_page_mut(parent_page_num);
+            set_internal_node_child(parent, left_index + 1, left_page_num);
+            internal_node_remove_cell(parent, left_index);
+            self.free_page(right_page_num);
+            self.update_parent_keys(left_page_num);
+            self.rebalance(parent_page_num);
+        } else {
+            self.update_parent_keys(left_page_num);
+            self.update_parent_keys(right_page_num);
+        }
+    }
+
+    /// Moves all the cells of two neighbouring leaves into the left one if they fit,
+    /// otherwise divides the bytes of the cells evenly between them.
+    /// Returns true if the leaves were merged.
+    fn leaf_nodes_rebalance(&mut self, left_page_num: u32, right_page_num: u32) -> bool {
+        let right = self.get_page(right_page_num).to_vec();
+        let left = self.get_page_mut(left_page_num);
+        let mut cells = leaf_node_cells(left);
+        cells.extend(leaf_node_cells(&right));
+
+        let space_for_cells = leaf_node_space_for_cells(left.len());
+        let merged = cells_size(&cells) <= space_for_cells;
+        if merged {
+            set_leaf_node_cells(left, &cells);
+            set_leaf_node_next_leaf(left, leaf_node_next_leaf(&right));
+        } else {
+            let left_split_count = balanced_split(&cells, space_for_cells);
+            set_leaf_node_cells(left, &cells[..left_split_count]);
+            set_leaf_node_cells(
+                self.get_page_mut(right_page_num),
+                &cells[left_split_count..],
+            );
+        }
+        merged
+    }
+
+    /// Moves all the children of two neighbouring internal nodes into the left one
+    /// if they fit, otherwise divides the children evenly between them.
+    /// Returns true if the nodes were merged.
+    fn internal_nodes_rebalance(&mut self, left_page_num: u32, right_page_num: u32) -> bool {
+        let mut children = self.internal_node_children(left_page_num);
+        children.extend(self.internal_node_children(right_page_num));
+
+        // A node holds one more child than keys
+        let max_cells = internal_node_max_cells(self.get_page(left_page_num)) as usize;
+        let merged = children.len() <= max_cells + 1;
+        if merged {
+            self.set_internal_node_children(left_page_num, &children);
+        } else {
+            let (left, right) = children.split_at(children.len() / 2);
+            self.set_internal_node_children(left_page_num, left);
+            self.set_internal_node_children(right_page_num, right);
+        }
+        merged
+    }
+
+    /// Returns the page numbers of all the children of an internal node, in order
+    fn internal_node_children(&mut self, page_num: u32) -> Vec<u32> {
+        let node = self.get_page(page_num);
+        (0..=internal_node_num_keys(node))
+            .map(|i| internal_node_child(node, i))
+            .collect()
+    }
+
+    /// Fills an internal node with the given children,
+    /// each keyed by the largest key in its subtree
+    fn set_internal_node_children(&mut self, page_num: u32, children: &[u32]) {
+        let (&right_child, children) = children.split_last().unwrap();
+        let keys: Vec<Vec<u8>> = children
+            .iter()
+            .map(|&child| self.get_node_max_key(child))
+            .collect();
+
+        let node = self.get_page_mut(page_num);
+        set_internal_node_num_keys(node, keys.len() as u32);
+        for (i, (&child, key)) in children.iter().zip(&keys).enumerate() {
+            set_internal_node_child(node, i as u32, child);
+            set_internal_node_key(node, i as u32, key);
+        }
+        set_internal_node_right_child(node, right_child);
+
+        for &child in children.iter().chain(Some(&right_child)) {
+            set_node_parent(self.get_page_mut(child), page_num);
+        }
+    }
+
+    /// Replaces an internal root without keys by its only child.
+    /// The root keeps its page, so the child is copied into it.
+    fn collapse_root(&mut self, root_page_num: u32) {
+        let child_page_num = internal_node_right_child(self.get_page(root_page_num));
+        let child = self.get_page(child_page_num).to_vec();
+
+        let root = self.get_page_mut(root_page_num);
+        root.copy_from_slice(&child);
+        set_node_root(root, true);
+
+        if get_node_type(&child) == NodeType::Internal {
+            for grandchild in self.internal_node_children(root_page_num) {
+                set_node_parent(self.get_page_mut(grandchild), root_page_num);
+            }
+        }
+        self.free_page(child_page_num);
+    }
+
+    /// Sets the key pointing to the node in its parent to the largest key of the node.
+    /// The right child has no key of its own, so the key pointing to the parent is set instead.
+    fn update_parent_keys(&mut self, page_num: u32) {
+        let mut child_page_num = page_num;
+        while !is_node_root(self.get_page(child_page_num)) {
+            let parent_page_num = node_parent(self.get_page(child_page_num));
+            let parent = self.get_page(parent_page_num);
+            let index = internal_node_child_index(parent, child_page_num);
+            if index < internal_node_num_keys(parent) {
+                let max_key = self.get_node_max_key(child_page_num);
+                set_internal_node_key(self.get_page_mut(parent_page_num), index, &max_key);
+                return;
+            }
+            child_page_num = parent_page_num;
+        }
+    }
+
+    /// Adds every page of the subtree rooted at the given page,
+    /// and the overflow pages of its cells, to the free list
+    ///
+    /// # Arguments
+    ///
+    /// * `page_num` - The page number of the root of the subtree
+    pub fn free_tree(&mut self, page_num: u32) {
+        let node = self.get_page(page_num);
+        match get_node_type(node) {
+            NodeType::Internal => {
+                for child in self.internal_node_children(page_num) {
+                    self.free_tree(child);
+                }
+            }
+            NodeType::Leaf => {
+                let overflow_page_nums: Vec<u32> = (0..leaf_node_num_cells(node))
+                    .map(|i| leaf_node_overflow_page(node, i))
+                    .collect();
+                for overflow_page_num in overflow_page_nums {
+                    self.free_overflow_chain(overflow_page_num);
+                }
+            }
+        }
+        self.free_page(page_num);
+    }
+
+    /// Prints the structure of the subtree rooted at the given page
+    /// of the B+tree of a table, whose keys are the ids of the rows
+    ///
+    /// # Arguments
+    ///
+    /// * `page_num` - The page number of the root of the subtree
+    /// * `indentation_level` - How deep the subtree is in the whole tree
+    pub fn print_tree(&mut self, page_num: u32, indentation_level: usize) {
+        let indent = "  ".repeat(indentation_level);
+        let node = self.get_page(page_num);
+
+        match get_node_type(node) {
+            NodeType::Leaf => {
+                let num_keys = leaf_node_num_cells(node);
+                println!("{}- leaf (size {})", indent, num_keys);
+                for i in 0..num_keys {
+                    println!("{}  - {}", indent, read_row_key(leaf_node_key(node, i)));
+                }
+            }
+            NodeType::Internal => {
+                let num_keys = internal_node_num_keys(node);
+                println!("{}- internal (size {})", indent, num_keys);
+                if num_keys > 0 {
+                    for i in 0..num_keys {
+                        let node = self.get_page(page_num);
+                        let child = internal_node_child(node, i);
+                        let key = read_row_key(internal_node_key(node, i));
+                        self.print_tree(child, indentation_level + 1);
+                        println!("{}  - key {}", indent, key);
+                    }
+                    let right_child = internal_node_right_child(self.get_page(page_num));
+                    self.print_tree(right_child, indentation_level + 1);
+                }
+            }
+        }
+    }
+}
+
+/// Returns the number of bytes the cells take in a leaf node, with their offsets
+fn cells_size(cells: &[Vec<u8>]) -> usize {
+    cells
+        .iter()
+        .map(|cell| cell.len() + LEAF_NODE_CELL_POINTER_SIZE)
+        .sum()
+}
+
+/// Returns the number of cells to keep in the left node when dividing
+/// the cells between two leaf nodes, such that both nodes hold about
+/// the same number of bytes. Neither node is left empty and both fit,
+/// as no cell is larger than `leaf_node_max_cell_size`.
+///
+/// # Arguments
+///
+/// * `cells` - The cells of both nodes, in order
+/// * `space_for_cells` - The number of bytes a leaf node has for its cells
+fn balanced_split(cells: &[Vec<u8>], space_for_cells: usize) -> usize {
+    let total = cells_size(cells);
+    let mut left = 0;
+    let mut best = (usize::MAX, 1);
+    for (i, cell) in cells[..cells.len() - 1].iter().enumerate() {
+        left += cell.len() + LEAF_NODE_CELL_POINTER_SIZE;
+        let right = total - left;
+        if left <= space_for_cells && right <= space_for_cells {
+            let difference = left.abs_diff(right);
+            if difference < best.0 {
+                best = (difference, i + 1);
+            }
+        }
+    }
+    best.1
+}
@@ -0,0 +1,135 @@
+//! # Cache
+//!
+//! The pool of pages the pager holds in memory. It holds at most as many
+//! pages as its capacity, unless more of them are pinned. When it is full,
+//! the page to make room for another one is picked with the clock algorithm,
+//! which approximates evicting the least recently used page: the frames are
+//! swept in a circle, a page used since the last sweep is given a second
+//! chance, and the first page which was not used is evicted.
+
+use std::collections::HashMap;
+
+/// A struct to hold a page in memory, along with its state
+pub struct Frame {
+    pub page_num: u32,
+    pub data: Vec<u8>,
+    /// The page was changed since it was read from or written to the file
+    pub dirty: bool,
+    /// The page was used since the clock hand last passed it
+    pub referenced: bool,
+    /// A pinned page is never evicted
+    pub pin_count: u32,
+}
+
+/// A struct to hold the frames of the cache and find them by page number
+pub struct PageCache {
+    pub frames: Vec<Frame>,
+    /// The index in `frames` of every page held in memory
+    pub page_table: HashMap<u32, usize>,
+    /// The number of pages held in memory before pages are evicted
+    pub capacity: usize,
+    /// The index of the next frame the clock considers for eviction
+    pub hand: usize,
+}
+
+impl PageCache {
+    /// Returns an empty cache
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The number of pages held in memory before pages are evicted
+    pub fn new(capacity: usize) -> Self {
+        PageCache {
+            frames: Vec::new(),
+            page_table: HashMap::new(),
+            capacity,
+            hand: 0,
+        }
+    }
+
+    /// Returns true if the page is held in memory
+    ///
+    /// # Arguments
+    ///
+    /// * `page_num` - The number of the page
+    pub fn contains(&self, page_num: u32) -> bool {
+        self.page_table.contains_key(&page_num)
+    }
+
+    /// Returns the frame holding the page, marking the page as used
+    ///
+    /// # Arguments
+    ///
+    /// * `page_num` - The number of the page
+    pub fn get(&mut self, page_num: u32) -> Option<&mut Frame> {
+        let index = *self.page_table.get(&page_num)?;
+        let frame = &mut self.frames[index];
+        frame.referenced = true;
+        Some(frame)
+    }
+
+    /// Adds a frame for a page which is not held in memory yet
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - The frame holding the page
+    pub fn insert(&mut self, frame: Frame) {
+        self.page_table.insert(frame.page_num, self.frames.len());
+        self.frames.push(frame);
+    }
+
+    /// Returns the index of the frame to evict, moving the clock hand past it.
+    /// Returns `None` if every page is pinned.
+    pub fn victim(&mut self) -> Option<usize> {
+        // The first sweep may only clear the referenced flags,
+        // the second one then finds an unpinned page if there is any
+        for _ in 0..2 * self.frames.len() {
+            let index = self.hand;
+            self.hand = (self.hand + 1) % self.frames.len();
+
+            let frame = &mut self.frames[index];
+            if frame.pin_count > 0 {
+                continue;
+            }
+            if frame.referenced {
+                frame.referenced = false;
+                continue;
+            }
+            return Some(index);
+        }
+        None
+    }
+
+    /// Removes the frame with the given index from the cache and returns it
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index of the frame
+    pub fn remove(&mut self, index: usize) -> Frame {
+        let frame = self.frames.swap_remove(index);
+        self.page_table.remove(&frame.page_num);
+
+        // The last frame took the place of the removed one
+        if let Some(moved) = self.frames.get(index) {
+            self.page_table.insert(moved.page_num, index);
+        }
+        if self.hand >= self.frames.len() {
+            self.hand = 0;
+        }
+        frame
+    }
+
+    /// Returns the numbers of the pages held in memory, in order
+    pub fn page_nums(&self) -> Vec<u32> {
+        let mut page_nums: Vec<u32> = self.page_table.keys().copied().collect();
+        page_nums.sort_unstable();
+        page_nums
+    }
+
+    /// Removes every page from the cache
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.page_table.clear();
+        self.hand = 0;
+    }
+}
@@ -0,0 +1,119 @@
+//! # Catalog
+//!
+//! The `rsqlite_schema` table lists the tables and indexes stored in the
+//! database file. It is an ordinary B+tree rooted at the page named in the
+//! file header, with one row per table or index holding its name, the root
+//! page of its B+tree and the `create` statement defining it.
+
+use super::cursor::Cursor;
+use super::pager::Pager;
+use super::schema::Schema;
+use super::{row_key, Row, Value};
+
+/// Name of the catalog table
+pub const CATALOG_TABLE_NAME: &str = "rsqlite_schema";
+
+/// The statement defining the catalog table, rows are keyed by root page.
+/// Names and statements may be as long as any text, the part of a long
+/// statement which does not fit into its leaf node goes to overflow pages.
+pub const CATALOG_TABLE_SQL: &str = "create table rsqlite_schema (type text(8), name text, \
+     rootpage integer primary key, sql text)";
+
+/// A struct to hold a single object listed in the catalog
+pub struct CatalogEntry {
+    /// The kind of object, `table` or `index`
+    pub entry_type: String,
+    pub name: String,
+    pub root_page_num: u32,
+    pub sql: String,
+}
+
+impl CatalogEntry {
+    /// Converts the entry into a row of the catalog table
+    pub fn to_row(&self) -> Row {
+        Row {
+            values: vec![
+                Value::Text(self.entry_type.clone()),
+                Value::Text(self.name.clone()),
+                Value::Integer(self.root_page_num as i64),
+                Value::Text(self.sql.clone()),
+            ],
+        }
+    }
+
+    /// Builds an entry from a row of the catalog table
+    pub fn from_row(row: Row) -> Self {
+        let mut values = row.values.into_iter().map(|value| match value {
+            Value::Text(value) => value,
+            value => value.to_string(),
+        });
+        let entry_type = values.next().unwrap_or_default();
+        let name = values.next().unwrap_or_default();
+        let root_page_num = values.next().and_then(|v| v.parse().ok()).unwrap_or(0);
+        let sql = values.next().unwrap_or_default();
+        CatalogEntry {
+            entry_type,
+            name,
+            root_page_num,
+            sql,
+        }
+    }
+}
+
+/// Returns the schema of the catalog table
+pub fn catalog_schema() -> Schema {
+    Schema::parse(CATALOG_TABLE_SQL).expect("the catalog schema is valid")
+}
+
+/// Retrieve all the entries stored in the catalog, ordered by root page
+///
+/// # Arguments
+///
+/// * `pager` - The pager holding the pages of the database
+pub fn read_catalog(pager: &mut Pager) -> Vec<CatalogEntry> {
+    let schema = catalog_schema();
+    let root_page_num = pager.header.root_page_num;
+    let mut cursor = Cursor::table_start(pager, root_page_num);
+    let mut entries = Vec::new();
+
+    while !cursor.end_of_table {
+        entries.push(CatalogEntry::from_row(Row::deserialize(
+            &schema,
+            &cursor.value(),
+        )));
+        cursor.advance();
+    }
+
+    entries
+}
+
+/// Add an entry to the catalog
+///
+/// # Arguments
+///
+/// * `pager` - The pager holding the pages of the database
+/// * `entry` - The object to list in the catalog
+pub fn insert_catalog_entry(pager: &mut Pager, entry: &CatalogEntry) {
+    let value = entry.to_row().serialize();
+
+    let root_page_num = pager.header.root_page_num;
+    let key = row_key(i64::from(entry.root_page_num));
+    let mut cursor = Cursor::find(pager, root_page_num, &key);
+    cursor.insert(&key, &value);
+}
+
+/// Remove the entry of the object whose B+tree is rooted at the given page
+///
+/// # Arguments
+///
+/// * `pager` - The pager holding the pages of the database
+/// * `root_page_num` - The root page of the object, which is the key of its entry
+pub fn delete_catalog_entry(pager: &mut Pager, root_page_num: u32) {
+    let catalog_root_page_num = pager.header.root_page_num;
+    Cursor::find(
+        pager,
+        catalog_root_page_num,
+        &row_key(i64::from(root_page_num)),
+    )
+    .delete();
+}
@@ -0,0 +1,195 @@
+//! # Cursor
+//!
+//! An object representing a position in a B+tree, used to walk
+//! through the cells of the tree or to find where a cell belongs.
+//! A tree, which holds a table or an index, is identified by its root page.
+//! The leaf the cursor points to stays pinned in the cache of the pager
+//! until the cursor moves past it or is dropped.
+
+use super::node::{
+    compare_keys, get_node_type, internal_node_child, internal_node_find_child,
+    internal_node_right_child, leaf_node_key, leaf_node_next_leaf, leaf_node_num_cells, NodeType,
+};
+use super::pager::Pager;
+use std::cmp::Ordering;
+
+/// A struct holding the position of a cell in a leaf node of the table
+pub struct Cursor<'a> {
+    pub pager: &'a mut Pager,
+    pub root_page_num: u32,
+    pub page_num: u32,
+    pub cell_num: u32,
+    /// Indicates a position one past the last row
+    pub end_of_table: bool,
+}
+
+impl<'a> Cursor<'a> {
+    /// Returns a cursor pointing to the first row of the table
+    ///
+    /// # Arguments
+    ///
+    /// * `pager` - The pager holding the pages of the table
+    /// * `root_page_num` - The root page of the table to walk through
+    pub fn table_start(pager: &'a mut Pager, root_page_num: u32) -> Self {
+        let mut page_num = root_page_num;
+        loop {
+            let node = pager.get_page(page_num);
+            match get_node_type(node) {
+                NodeType::Leaf => {
+                    let num_cells = leaf_node_num_cells(node);
+                    pager.pin(page_num);
+                    return Cursor {
+                        pager,
+                        root_page_num,
+                        page_num,
+                        cell_num: 0,
+                        end_of_table: num_cells == 0,
+                    };
+                }
+                NodeType::Internal => page_num = internal_node_child(node, 0),
+            }
+        }
+    }
+
+    /// Returns a cursor pointing one past the last row of the table
+    ///
+    /// # Arguments
+    ///
+    /// * `pager` - The pager holding the pages of the table
+    /// * `root_page_num` - The root page of the table to walk through
+    pub fn table_end(pager: &'a mut Pager, root_page_num: u32) -> Self {
+        let mut page_num = root_page_num;
+        loop {
+            let node = pager.get_page(page_num);
+            match get_node_type(node) {
+                NodeType::Leaf => {
+                    let cell_num = leaf_node_num_cells(node);
+                    pager.pin(page_num);
+                    return Cursor {
+                        pager,
+                        root_page_num,
+                        page_num,
+                        cell_num,
+                        end_of_table: true,
+                    };
+                }
+                NodeType::Internal => page_num = internal_node_right_child(node),
+            }
+        }
+    }
+
+    /// Returns a cursor pointing to the cell with the given key.
+    /// If the key is not present, the cursor points to the position
+    /// where it should be inserted, which holds the next larger key.
+    ///
+    /// # Arguments
+    ///
+    /// * `pager` - The pager holding the pages of the tree
+    /// * `root_page_num` - The root page of the tree to search in
+    /// * `key` - The key to search for, of the size of the keys of the tree
+    pub fn find(pager: &'a mut Pager, root_page_num: u32, key: &[u8]) -> Self {
+        let mut page_num = root_page_num;
+
+        // Descend from the root to the leaf which should contain the key
+        while get_node_type(pager.get_page(page_num)) == NodeType::Internal {
+            let node = pager.get_page(page_num);
+            let child_index = internal_node_find_child(node, key);
+            page_num = internal_node_child(node, child_index);
+        }
+
+        let node = pager.get_page(page_num);
+        let num_cells = leaf_node_num_cells(node);
+
+        // Binary search for the key in the leaf node
+        let mut min_index = 0;
+        let mut one_past_max_index = num_cells;
+        while one_past_max_index != min_index {
+            let index = (min_index + one_past_max_index) / 2;
+            match compare_keys(key, leaf_node_key(node, index)) {
+                Ordering::Equal => {
+                    min_index = index;
+                    break;
+                }
+                Ordering::Less => one_past_max_index = index,
+                Ordering::Greater => min_index = index + 1,
+            }
+        }
+
+        // Internal keys route a key past the end of a leaf only
+        // when it is larger than every key in the table
+        pager.pin(page_num);
+        Cursor {
+            pager,
+            root_page_num,
+            page_num,
+            cell_num: min_index,
+            end_of_table: min_index == num_cells,
+        }
+    }
+
+    /// Moves the cursor to the next row,
+    /// following the link to the next leaf at the end of a leaf node
+    pub fn advance(&mut self) {
+        let node = self.pager.get_page(self.page_num);
+        self.cell_num += 1;
+
+        if self.cell_num >= leaf_node_num_cells(node) {
+            let next_page_num = leaf_node_next_leaf(node);
+            if next_page_num == 0 {
+                // This was the rightmost leaf
+                self.end_of_table = true;
+            } else {
+                self.pager.unpin(self.page_num);
+                self.pager.pin(next_page_num);
+                self.page_num = next_page_num;
+                self.cell_num = 0;
+            }
+        }
+    }
+
+    /// Returns the key of the cell the cursor points to
+    pub fn key(&mut self) -> &[u8] {
+        leaf_node_key(self.pager.get_page(self.page_num), self.cell_num)
+    }
+
+    /// Returns the bytes of the row the cursor points to,
+    /// including those stored in overflow pages
+    pub fn value(&mut self) -> Vec<u8> {
+        self.pager
+            .leaf_node_read_value(self.page_num, self.cell_num)
+    }
+
+    /// Inserts a cell at the position of the cursor
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key of the cell
+    /// * `value` - The value to be stored, e.g. a serialized row
+    pub fn insert(&mut self, key: &[u8], value: &[u8]) {
+        self.pager
+            .leaf_node_insert(self.page_num, self.cell_num, key, value);
+    }
+
+    /// Replaces the row the cursor points to, keeping its key.
+    /// The tree may be split, so the cursor must not be used afterwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The serialized row to be stored
+    pub fn update(&mut self, value: &[u8]) {
+        self.pager
+            .leaf_node_update(self.page_num, self.cell_num, value);
+    }
+
+    /// Removes the row the cursor points to.
+    /// The tree may be rebalanced, so the cursor must not be used afterwards.
+    pub fn delete(&mut self) {
+        self.pager.leaf_node_delete(self.page_num, self.cell_num);
+    }
+}
+
+impl<'a> Drop for Cursor<'a> {
+    fn drop(&mut self) {
+        self.pager.unpin(self.page_num);
+    }
+}
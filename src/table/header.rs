@@ -0,0 +1,152 @@
+//! # Header
+//!
+//! The header stored at the start of page 0 of the database file
+
+use super::{read_u32, write_u32};
+use crate::constants::{
+    DEFAULT_PAGE_SIZE, FORMAT_VERSION, HEADER_FORMAT_VERSION_OFFSET, HEADER_FREELIST_COUNT_OFFSET,
+    HEADER_FREELIST_HEAD_OFFSET, HEADER_JOURNAL_MODE_OFFSET, HEADER_MAGIC, HEADER_MAGIC_OFFSET,
+    HEADER_MAGIC_SIZE, HEADER_PAGE_COUNT_OFFSET, HEADER_PAGE_SIZE_OFFSET, HEADER_ROOT_PAGE_OFFSET,
+    HEADER_SCHEMA_COOKIE_OFFSET,
+};
+
+/// Enum to show how changes to the database file are made atomic
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum JournalMode {
+    /// Original pages are saved in a rollback journal, which is deleted on commit
+    Delete,
+    /// Changed pages are appended to a write-ahead log
+    Wal,
+}
+
+impl JournalMode {
+    /// Returns the name of the mode, as used by `pragma journal_mode`
+    pub fn name(self) -> &'static str {
+        match self {
+            JournalMode::Delete => "delete",
+            JournalMode::Wal => "wal",
+        }
+    }
+
+    /// Returns the mode with the given name, compared case-insensitively
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the mode
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rsqlite::table::header::JournalMode;
+    /// assert_eq!(JournalMode::parse("WAL"), Some(JournalMode::Wal));
+    /// assert_eq!(JournalMode::parse("memory"), None);
+    /// ```
+    pub fn parse(name: &str) -> Option<Self> {
+        [JournalMode::Delete, JournalMode::Wal]
+            .iter()
+            .copied()
+            .find(|mode| mode.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// A struct to hold the metadata describing a database file
+#[derive(Clone, Copy)]
+pub struct Header {
+    pub format_version: u32,
+    pub page_size: u32,
+    pub page_count: u32,
+    pub root_page_num: u32,
+    /// Changed every time the schema of the database changes
+    pub schema_cookie: u32,
+    /// The first page of the list of free pages, 0 if no page is free
+    pub freelist_head: u32,
+    pub freelist_count: u32,
+    pub journal_mode: JournalMode,
+}
+
+impl Default for Header {
+    fn default() -> Self {
+        Self::new(DEFAULT_PAGE_SIZE)
+    }
+}
+
+impl Header {
+    /// Returns the header of a new, empty database file
+    ///
+    /// # Arguments
+    ///
+    /// * `page_size` - The page size of the file
+    pub fn new(page_size: u32) -> Self {
+        Header {
+            format_version: FORMAT_VERSION,
+            page_size,
+            page_count: 0,
+            root_page_num: 1,
+            schema_cookie: 0,
+            freelist_head: 0,
+            freelist_count: 0,
+            journal_mode: JournalMode::Delete,
+        }
+    }
+
+    /// Returns true if the page starts with the magic string of a database file
+    ///
+    /// # Arguments
+    ///
+    /// * `page` - The bytes of page 0
+    pub fn has_magic(page: &[u8]) -> bool {
+        &page[HEADER_MAGIC_OFFSET..HEADER_MAGIC_OFFSET + HEADER_MAGIC_SIZE] == HEADER_MAGIC
+    }
+
+    /// Store the header at the start of the given page
+    ///
+    /// # Arguments
+    ///
+    /// * `destination` - The bytes of page 0
+    pub fn serialize(&self, destination: &mut [u8]) {
+        destination[HEADER_MAGIC_OFFSET..HEADER_MAGIC_OFFSET + HEADER_MAGIC_SIZE]
+            .copy_from_slice(HEADER_MAGIC);
+        write_u32(
+            destination,
+            HEADER_FORMAT_VERSION_OFFSET,
+            self.format_version,
+        );
+        write_u32(destination, HEADER_PAGE_SIZE_OFFSET, self.page_size);
+        write_u32(destination, HEADER_PAGE_COUNT_OFFSET, self.page_count);
+        write_u32(destination, HEADER_ROOT_PAGE_OFFSET, self.root_page_num);
+        write_u32(destination, HEADER_SCHEMA_COOKIE_OFFSET, self.schema_cookie);
+        write_u32(destination, HEADER_FREELIST_HEAD_OFFSET, self.freelist_head);
+        write_u32(
+            destination,
+            HEADER_FREELIST_COUNT_OFFSET,
+            self.freelist_count,
+        );
+        let journal_mode = match self.journal_mode {
+            JournalMode::Delete => 0,
+            JournalMode::Wal => 1,
+        };
+        write_u32(destination, HEADER_JOURNAL_MODE_OFFSET, journal_mode);
+    }
+
+    /// Retrieve the header from the start of the given page
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The bytes of page 0
+    pub fn deserialize(source: &[u8]) -> Self {
+        Header {
+            format_version: read_u32(source, HEADER_FORMAT_VERSION_OFFSET),
+            page_size: read_u32(source, HEADER_PAGE_SIZE_OFFSET),
+            page_count: read_u32(source, HEADER_PAGE_COUNT_OFFSET),
+            root_page_num: read_u32(source, HEADER_ROOT_PAGE_OFFSET),
+            schema_cookie: read_u32(source, HEADER_SCHEMA_COOKIE_OFFSET),
+            freelist_head: read_u32(source, HEADER_FREELIST_HEAD_OFFSET),
+            freelist_count: read_u32(source, HEADER_FREELIST_COUNT_OFFSET),
+            // Files from before the journal mode was stored hold 0 there
+            journal_mode: match read_u32(source, HEADER_JOURNAL_MODE_OFFSET) {
+                1 => JournalMode::Wal,
+                _ => JournalMode::Delete,
+            },
+        }
+    }
+}
@@ -0,0 +1,322 @@
+//! # Index
+//!
+//! An index is a B+tree holding one cell per row of a table, so that the
+//! rows with given values in some columns are found without reading the
+//! whole table. The key of a cell is made of the values of the indexed
+//! columns followed by the key of the row, and the cell has no value.
+//! Values are encoded so that comparing their bytes compares the values:
+//! a tag orders the storage classes, numbers are stored big-endian with
+//! their sign bit flipped and text and blobs are padded with zeroes up to
+//! the size of their column, followed by their length.
+//!
+//! Keys have a fixed size, so text and blobs of large columns only keep their
+//! first `INDEX_MAX_VALUE_SIZE` bytes. Values which differ after those bytes
+//! then share the same key, so the rows found through an index are checked
+//! against the values they were looked up for.
+
+use super::cursor::Cursor;
+use super::node::node_key_size;
+use super::pager::Pager;
+use super::schema::{ColumnType, Schema};
+use super::{read_row_key, row_key, Row, Value};
+use crate::constants::{INDEX_MAX_VALUE_SIZE, ROW_KEY_SIZE, STORAGE_CLASS_SIZE, VALUE_LENGTH_SIZE};
+use crate::vm::parser::parse;
+use crate::vm::statement::{CreateIndex, Statement};
+use std::cmp::Ordering;
+
+/// A struct to hold the definition of an index and the root page of its B+tree
+#[derive(Clone, Debug)]
+pub struct Index {
+    pub name: String,
+    /// Positions of the indexed columns in the schema of the table
+    pub columns: Vec<usize>,
+    /// No two rows may have the same values in the indexed columns
+    pub unique: bool,
+    pub root_page_num: u32,
+    /// The `create index` statement the index was parsed from
+    pub sql: String,
+}
+
+impl Index {
+    /// Returns the index defined by a `create index` statement,
+    /// or `None` if one of its columns is not in the schema
+    ///
+    /// # Arguments
+    ///
+    /// * `create` - The `create index` statement
+    /// * `schema` - The schema of the indexed table
+    /// * `root_page_num` - The root page of the B+tree of the index
+    pub fn new(create: &CreateIndex, schema: &Schema, root_page_num: u32) -> Option<Self> {
+        let columns = create
+            .columns
+            .iter()
+            .map(|name| schema.column_index(name))
+            .collect::<Option<Vec<usize>>>()?;
+        Some(Index {
+            name: create.name.clone(),
+            columns,
+            unique: create.unique,
+            root_page_num,
+            sql: create.sql.clone(),
+        })
+    }
+
+    /// Parses a `create index` statement read from the catalog.
+    /// Returns `None` if it could not be parsed.
+    ///
+    /// # Arguments
+    ///
+    /// * `sql` - The `create index` statement
+    pub fn parse(sql: &str) -> Option<CreateIndex> {
+        match parse(sql) {
+            Ok(Statement::CreateIndex(create)) => Some(create),
+            _ => None,
+        }
+    }
+
+    /// Returns the size of the keys of the index
+    ///
+    /// # Arguments
+    ///
+    /// * `schema` - The schema of the indexed table
+    pub fn key_size(&self, schema: &Schema) -> usize {
+        let values_size: usize = self
+            .columns
+            .iter()
+            .map(|&column| encoded_size(schema.columns[column].col_type))
+            .sum();
+        values_size + ROW_KEY_SIZE
+    }
+
+    /// Returns the encoded values of the indexed columns of a row,
+    /// which start the key of its cell
+    ///
+    /// # Arguments
+    ///
+    /// * `schema` - The schema of the indexed table
+    /// * `row` - A row of the table
+    pub fn values_key(&self, schema: &Schema, row: &Row) -> Vec<u8> {
+        let mut key = Vec::with_capacity(self.key_size(schema));
+        for &column in &self.columns {
+            let col_type = schema.columns[column].col_type;
+            match encode_value(col_type, &row.values[column]) {
+                Some(bytes) => key.extend(bytes),
+                None => unreachable!("only checked rows are stored"),
+            }
+        }
+        key
+    }
+
+    /// Returns true if one of the indexed columns of a row holds `NULL`.
+    /// Like in SQL, such a row never conflicts with another one in a unique index.
+    ///
+    /// # Arguments
+    ///
+    /// * `row` - A row of the table
+    pub fn has_null(&self, row: &Row) -> bool {
+        self.columns
+            .iter()
+            .any(|&column| row.values[column] == Value::Null)
+    }
+
+    /// Compares the values of the indexed columns of two rows
+    ///
+    /// # Arguments
+    ///
+    /// * `a` - A row of the table
+    /// * `b` - Another row of the table
+    pub fn compare_values(&self, a: &Row, b: &Row) -> Ordering {
+        self.columns
+            .iter()
+            .map(|&column| a.values[column].compare(&b.values[column]))
+            .find(|ordering| *ordering != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    }
+
+    /// Returns the key of the cell of a row
+    ///
+    /// # Arguments
+    ///
+    /// * `schema` - The schema of the indexed table
+    /// * `row` - A row of the table
+    pub fn key(&self, schema: &Schema, row: &Row) -> Vec<u8> {
+        let mut key = self.values_key(schema, row);
+        key.extend_from_slice(&row_key(row.key(schema)));
+        key
+    }
+
+    /// Adds the cell of a row to the index
+    ///
+    /// # Arguments
+    ///
+    /// * `pager` - The pager holding the pages of the index
+    /// * `schema` - The schema of the indexed table
+    /// * `row` - The row added to the table
+    pub fn insert(&self, pager: &mut Pager, schema: &Schema, row: &Row) {
+        let key = self.key(schema, row);
+        Cursor::find(pager, self.root_page_num, &key).insert(&key, &[]);
+    }
+
+    /// Removes the cell of a row from the index
+    ///
+    /// # Arguments
+    ///
+    /// * `pager` - The pager holding the pages of the index
+    /// * `schema` - The schema of the indexed table
+    /// * `row` - The row removed from the table
+    pub fn delete(&self, pager: &mut Pager, schema: &Schema, row: &Row) {
+        let key = self.key(schema, row);
+        Cursor::find(pager, self.root_page_num, &key).delete();
+    }
+
+    /// Returns the keys of the rows whose cells lie in the given range, in index order.
+    /// Every key in the range starts with `prefix`, the bytes after the prefix
+    /// are at least `lower` and, compared over the length of `upper`, at most `upper`.
+    ///
+    /// # Arguments
+    ///
+    /// * `pager` - The pager holding the pages of the index
+    /// * `prefix` - The encoded values of the leading indexed columns
+    /// * `lower` - The smallest encoded value of the next column, if any
+    /// * `upper` - The largest encoded value of the next column, if any
+    pub fn row_keys(
+        &self,
+        pager: &mut Pager,
+        prefix: &[u8],
+        lower: Option<&[u8]>,
+        upper: Option<&[u8]>,
+    ) -> Vec<i64> {
+        // No key is smaller than one whose values are padded with zeroes
+        // and whose row has the smallest id
+        let key_size = node_key_size(pager.get_page(self.root_page_num));
+        let mut start = prefix.to_vec();
+        start.extend_from_slice(lower.unwrap_or_default());
+        start.resize(key_size - ROW_KEY_SIZE, 0);
+        start.extend_from_slice(&row_key(i64::MIN));
+
+        let mut keys = Vec::new();
+        let mut cursor = Cursor::find(pager, self.root_page_num, &start);
+        while !cursor.end_of_table {
+            let key = cursor.key();
+            if !key.starts_with(prefix) {
+                break;
+            }
+            if let Some(upper) = upper {
+                if &key[prefix.len()..prefix.len() + upper.len()] > upper {
+                    break;
+                }
+            }
+            keys.push(read_row_key(&key[key.len() - ROW_KEY_SIZE..]));
+            cursor.advance();
+        }
+        keys
+    }
+}
+
+/// Returns the number of bytes the values of a column take in the key of an index
+///
+/// # Arguments
+///
+/// * `col_type` - The type of the indexed column
+pub fn encoded_size(col_type: ColumnType) -> usize {
+    // Numbers take 8 bytes ordering them as reals and 8 more
+    // ordering integers too large to be exact reals
+    let number_size = 16;
+    STORAGE_CLASS_SIZE
+        + match col_type {
+            ColumnType::Integer | ColumnType::Real => number_size,
+            ColumnType::Text(_) => key_bytes_size(col_type) + VALUE_LENGTH_SIZE,
+            ColumnType::Blob(_) => (key_bytes_size(col_type) + VALUE_LENGTH_SIZE).max(number_size),
+        }
+}
+
+/// Returns the number of bytes of text or a blob kept in the key of an index
+///
+/// # Arguments
+///
+/// * `col_type` - The type of the indexed column
+fn key_bytes_size(col_type: ColumnType) -> usize {
+    match col_type {
+        ColumnType::Integer | ColumnType::Real => 0,
+        ColumnType::Text(size) | ColumnType::Blob(size) => {
+            size.map_or(INDEX_MAX_VALUE_SIZE, |size| size.min(INDEX_MAX_VALUE_SIZE))
+        }
+    }
+}
+
+/// Returns the bytes a value takes in the key of an index, or `None` if the
+/// value could not be stored in a column of the given type as it is
+///
+/// # Arguments
+///
+/// * `col_type` - The type of the indexed column
+/// * `value` - The value to encode
+///
+/// # Example
+///
+/// ```
+/// use rsqlite::table::index::encode_value;
+/// use rsqlite::table::schema::ColumnType;
+/// use rsqlite::table::Value;
+/// let negative = encode_value(ColumnType::Integer, &Value::Integer(-2)).unwrap();
+/// let small = encode_value(ColumnType::Integer, &Value::Real(1.5)).unwrap();
+/// let large = encode_value(ColumnType::Integer, &Value::Integer(256)).unwrap();
+/// assert!(negative < small && small < large);
+/// assert_eq!(encode_value(ColumnType::Text(Some(3)), &Value::Text("abcd".to_string())), None);
+/// let long = Value::Text("a".repeat(100));
+/// let longer = Value::Text("a".repeat(200));
+/// assert_eq!(encode_value(ColumnType::Text(None), &long), encode_value(ColumnType::Text(None), &longer));
+/// ```
+pub fn encode_value(col_type: ColumnType, value: &Value) -> Option<Vec<u8>> {
+    let mut bytes = vec![0; encoded_size(col_type)];
+    let (tag, payload) = bytes.split_at_mut(STORAGE_CLASS_SIZE);
+    let key_size = key_bytes_size(col_type);
+    tag[0] = match (col_type, value) {
+        (_, Value::Null) => 0,
+        (ColumnType::Text(_), Value::Integer(_)) | (ColumnType::Text(_), Value::Real(_)) => {
+            return None
+        }
+        (_, Value::Integer(_)) | (_, Value::Real(_)) => {
+            let (real, integer) = match value {
+                Value::Integer(value) => (*value as f64, *value),
+                Value::Real(value) => (*value, *value as i64),
+                _ => unreachable!("not a number"),
+            };
+            // Flipping the sign bit, and every bit of negative reals,
+            // makes the bytes of the numbers compare like the numbers
+            let bits = real.to_bits();
+            let bits = if real.is_sign_negative() {
+                !bits
+            } else {
+                bits ^ (1 << 63)
+            };
+            payload[..8].copy_from_slice(&bits.to_be_bytes());
+            payload[8..16].copy_from_slice(&((integer as u64) ^ (1 << 63)).to_be_bytes());
+            1
+        }
+        (ColumnType::Text(_), Value::Text(value)) | (ColumnType::Blob(_), Value::Text(value))
+            if !col_type.is_too_long(value.len()) =>
+        {
+            encode_bytes(payload, key_size, value.as_bytes());
+            2
+        }
+        (ColumnType::Text(_), Value::Blob(value)) | (ColumnType::Blob(_), Value::Blob(value))
+            if !col_type.is_too_long(value.len()) =>
+        {
+            encode_bytes(payload, key_size, value);
+            3
+        }
+        _ => return None,
+    };
+    Some(bytes)
+}
+
+/// Writes the bytes padded with zeroes to the given size, followed by their
+/// length, so that a shorter value sorts before a longer one it starts.
+/// Only the first bytes are written if there are more than the size.
+fn encode_bytes(destination: &mut [u8], size: usize, bytes: &[u8]) {
+    let bytes = &bytes[..bytes.len().min(size)];
+    destination[..bytes.len()].copy_from_slice(bytes);
+    destination[size..size + VALUE_LENGTH_SIZE]
+        .copy_from_slice(&(bytes.len() as u32).to_be_bytes());
+}
@@ -0,0 +1,247 @@
+//! # Journal
+//!
+//! The rollback journal makes changes to the database file atomic. Before a page
+//! of the file is changed for the first time in a transaction, its original image
+//! is appended to the journal, `<db>-journal`. The journal is synced to disk before
+//! any page of the database file is overwritten, and deleted once the transaction
+//! is committed. A journal left behind by a crash is replayed when the file is
+//! opened again, restoring every page it holds and the original length of the file.
+//!
+//! Each record holds the page number, the image of the page and a checksum of both,
+//! so that a record torn by a crash is recognized. A record is only incomplete if
+//! the journal was not synced yet, in which case the page was not overwritten.
+
+use super::{read_u32, write_u32};
+use crate::constants::{
+    JOURNAL_HEADER_SIZE, JOURNAL_MAGIC, JOURNAL_MAGIC_SIZE, JOURNAL_PAGE_COUNT_OFFSET,
+    JOURNAL_PAGE_SIZE_OFFSET, JOURNAL_RECORD_CHECKSUM_SIZE, JOURNAL_RECORD_PAGE_NUM_SIZE,
+};
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+/// A struct to hold the state of the rollback journal of a database file
+pub struct Journal {
+    /// The path of the journal file
+    pub path: String,
+    /// The journal file, open while the current transaction changed pages
+    pub file: Option<File>,
+    /// The page size of the database file
+    pub page_size: usize,
+    /// The number of pages of the database file when the transaction started.
+    /// Pages past those are new and need no original image.
+    pub original_num_pages: u32,
+    /// The pages whose original image is in the journal
+    pub journaled: HashSet<u32>,
+    /// Every record appended to the journal is on disk
+    pub synced: bool,
+}
+
+/// Returns the checksum of the given bytes, using the FNV-1a hash
+///
+/// # Arguments
+///
+/// * `bytes` - The bytes to be checked
+///
+/// # Example
+///
+/// ```
+/// use rsqlite::table::journal::checksum;
+/// assert_eq!(checksum(b""), 0x811c9dc5);
+/// assert_ne!(checksum(b"page"), checksum(b"pagf"));
+/// ```
+pub fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c9dc5, |hash: u32, &byte| {
+        (hash ^ byte as u32).wrapping_mul(0x01000193)
+    })
+}
+
+/// Returns the path of the journal of the given database file
+///
+/// # Arguments
+///
+/// * `filename` - The path of the database file
+pub fn journal_path(filename: &str) -> String {
+    format!("{}-journal", filename)
+}
+
+impl Journal {
+    /// Returns the journal of a database file, with no transaction in progress
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - The path of the database file
+    /// * `page_size` - The page size of the database file
+    /// * `num_pages` - The number of pages of the database file
+    pub fn new(filename: &str, page_size: usize, num_pages: u32) -> Self {
+        Journal {
+            path: journal_path(filename),
+            file: None,
+            page_size,
+            original_num_pages: num_pages,
+            journaled: HashSet::new(),
+            synced: true,
+        }
+    }
+
+    /// Returns true if the original image of the page
+    /// must be saved before the page is changed
+    ///
+    /// # Arguments
+    ///
+    /// * `page_num` - The index of the page
+    pub fn needs(&self, page_num: u32) -> bool {
+        page_num < self.original_num_pages && !self.journaled.contains(&page_num)
+    }
+
+    /// Appends the original image of a page to the journal,
+    /// creating the journal for the first page of a transaction
+    ///
+    /// # Arguments
+    ///
+    /// * `page_num` - The index of the page
+    /// * `page` - The bytes of the page before it is changed
+    pub fn append(&mut self, page_num: u32, page: &[u8]) {
+        if self.file.is_none() {
+            self.create();
+        }
+
+        let mut record = vec![0u8; JOURNAL_RECORD_PAGE_NUM_SIZE];
+        write_u32(&mut record, 0, page_num);
+        record.extend_from_slice(page);
+        record.extend_from_slice(&checksum(&record).to_le_bytes());
+
+        let file = self.file.as_mut().unwrap();
+        if file.write_all(&record).is_err() {
+            println!("Error writing journal.");
+            std::process::exit(1);
+        }
+        self.journaled.insert(page_num);
+        self.synced = false;
+    }
+
+    /// Creates the journal file and writes its header
+    fn create(&mut self) {
+        let mut file = match OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&self.path)
+        {
+            Ok(file) => file,
+            Err(_) => {
+                println!("Error creating journal.");
+                std::process::exit(1);
+            }
+        };
+
+        let mut header = [0u8; JOURNAL_HEADER_SIZE];
+        header[..JOURNAL_MAGIC_SIZE].copy_from_slice(JOURNAL_MAGIC);
+        write_u32(&mut header, JOURNAL_PAGE_SIZE_OFFSET, self.page_size as u32);
+        write_u32(
+            &mut header,
+            JOURNAL_PAGE_COUNT_OFFSET,
+            self.original_num_pages,
+        );
+        if file.write_all(&header).is_err() {
+            println!("Error writing journal.");
+            std::process::exit(1);
+        }
+        self.file = Some(file);
+    }
+
+    /// Syncs the records appended to the journal to disk, creating the journal
+    /// if the transaction has not changed any page yet. Must be called before
+    /// a page of the database file is written, as only the journal knows
+    /// the length of the file to roll back to.
+    pub fn sync(&mut self) {
+        if self.file.is_none() {
+            self.create();
+            self.synced = false;
+        }
+        if self.synced {
+            return;
+        }
+        if let Some(file) = &self.file {
+            if file.sync_data().is_err() {
+                println!("Error syncing journal.");
+                std::process::exit(1);
+            }
+        }
+        self.synced = true;
+    }
+
+    /// Ends the transaction by deleting the journal, which commits it.
+    /// The next transaction starts with the given number of pages.
+    ///
+    /// # Arguments
+    ///
+    /// * `num_pages` - The number of pages of the database file
+    pub fn commit(&mut self, num_pages: u32) {
+        if self.file.take().is_some() && std::fs::remove_file(&self.path).is_err() {
+            println!("Error deleting journal.");
+            std::process::exit(1);
+        }
+        self.original_num_pages = num_pages;
+        self.journaled.clear();
+        self.synced = true;
+    }
+}
+
+/// Restores a database file from the journal left behind by a transaction
+/// which was not committed, then deletes the journal.
+/// Does nothing if there is no journal.
+///
+/// # Arguments
+///
+/// * `filename` - The path of the database file
+/// * `file` - The database file
+pub fn replay(filename: &str, file: &mut File) {
+    let path = journal_path(filename);
+    let mut bytes = Vec::new();
+    match File::open(&path) {
+        Ok(mut journal) => {
+            if journal.read_to_end(&mut bytes).is_err() {
+                println!("Error reading journal.");
+                std::process::exit(1);
+            }
+        }
+        Err(_) => return,
+    }
+
+    // A journal without a complete header was created before
+    // anything was synced, the database file is untouched
+    if bytes.len() >= JOURNAL_HEADER_SIZE && &bytes[..JOURNAL_MAGIC_SIZE] == JOURNAL_MAGIC {
+        let page_size = read_u32(&bytes, JOURNAL_PAGE_SIZE_OFFSET) as usize;
+        let num_pages = read_u32(&bytes, JOURNAL_PAGE_COUNT_OFFSET) as u64;
+        let record_size = JOURNAL_RECORD_PAGE_NUM_SIZE + page_size + JOURNAL_RECORD_CHECKSUM_SIZE;
+
+        for record in bytes[JOURNAL_HEADER_SIZE..].chunks_exact(record_size) {
+            let (body, stored) = record.split_at(record_size - JOURNAL_RECORD_CHECKSUM_SIZE);
+            if checksum(body) != read_u32(stored, 0) {
+                break;
+            }
+            let page_num = read_u32(body, 0) as u64;
+            let page = &body[JOURNAL_RECORD_PAGE_NUM_SIZE..];
+            if file
+                .seek(SeekFrom::Start(page_num * page_size as u64))
+                .and_then(|_| file.write_all(page))
+                .is_err()
+            {
+                println!("Error restoring page {} from journal.", page_num);
+                std::process::exit(1);
+            }
+        }
+
+        // Pages added by the transaction are dropped
+        if file.set_len(num_pages * page_size as u64).is_err() || file.sync_all().is_err() {
+            println!("Error restoring database from journal.");
+            std::process::exit(1);
+        }
+    }
+
+    if std::fs::remove_file(&path).is_err() {
+        println!("Error deleting journal.");
+        std::process::exit(1);
+    }
+}
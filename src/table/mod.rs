@@ -2,162 +2,191 @@
 //!
 //! Interface to implement the structure of a table
 
-use crate::constants::{
-    EMAIL_OFFSET, EMAIL_SIZE, ID_OFFSET, ID_SIZE, ROWS_PER_PAGE, ROW_SIZE, USERNAME_OFFSET,
-    USERNAME_SIZE,
-};
+use std::cmp::Ordering;
+use std::fmt;
 
+pub mod btree;
+pub mod cache;
+pub mod catalog;
+pub mod cursor;
+pub mod header;
+pub mod index;
+pub mod journal;
+pub mod node;
+pub mod overflow;
 pub mod pager;
-use pager::Pager;
+pub mod record;
+pub mod schema;
+pub mod wal;
+use crate::constants::ROW_KEY_SIZE;
+use index::Index;
+use record::{decode_record, encode_record};
+use schema::Schema;
 
-/// Structure to store the data present in the table as
-/// well as the number of rows present currently
-pub struct Table {
-    pub num_rows: u32,
-    pub pager: Pager,
+// All integers in the database file are stored in little-endian byte order,
+// so that a file written on one machine can be read on any other.
+
+/// Reads a little-endian `u32` stored at the given offset of a page
+pub fn read_u32(page: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(&page[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
 }
 
-impl Table {
-    /// Opens a file to load the database from,
-    /// if the file is not present, a new file is created
-    ///
-    /// # Arguments
-    ///
-    /// * `filename` - A string slice holding the file name
-    ///
-    /// # Example
-    ///
-    /// ```
-    /// use crate table::Table;
-    /// let table = Table::db_open("test.db");
-    /// ```
-    ///
-    /// # Panics
-    ///
-    /// Function might panic if there is some problem in creating or opening a file
-    pub fn db_open(filename: &str) -> Self {
-        let pager = Pager::open(filename);
-        let num_rows = pager.file_length as u32 / ROW_SIZE;
-        Table { pager, num_rows }
-    }
+/// Writes a `u32` in little-endian byte order at the given offset of a page
+pub fn write_u32(page: &mut [u8], offset: usize, value: u32) {
+    page[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
 
-    /// Safely closes the database and writes all the data to the file on the disk
-    pub fn db_close(&mut self) {
-        let num_full_pages = self.num_rows / ROWS_PER_PAGE;
+/// Returns the key of the row with the given id in the B+tree of a table
+pub fn row_key(id: i64) -> [u8; ROW_KEY_SIZE] {
+    id.to_le_bytes()
+}
 
-        for i in 0..num_full_pages {
-            if self.pager.pages[i as usize].is_empty() {
-                continue;
-            }
-            self.pager.flush(i);
-        }
+/// Returns the id of the row with the given key in the B+tree of a table
+pub fn read_row_key(key: &[u8]) -> i64 {
+    let mut bytes = [0u8; ROW_KEY_SIZE];
+    bytes.copy_from_slice(&key[..ROW_KEY_SIZE]);
+    i64::from_le_bytes(bytes)
+}
 
-        // There may be a partial page to write to the end of the file
-        let num_add_rows = self.num_rows % ROWS_PER_PAGE;
-        if num_add_rows > 0 {
-            let page_num = num_full_pages;
-            if !self.pager.pages[page_num as usize].is_empty() {
-                self.pager.flush(page_num);
-            }
-        }
+/// Structure to store the schema of a table,
+/// the page number of the root of its B+tree and its indexes
+#[derive(Clone)]
+pub struct Table {
+    pub root_page_num: u32,
+    pub schema: Schema,
+    pub indexes: Vec<Index>,
+}
 
-        if self.pager.file.sync_data().is_err() {
-            println!("Error closing db file.");
-            std::process::exit(1);
-        }
-    }
+/// Enum to hold a single value, either stored in a column
+/// or produced while evaluating an expression.
+/// The variants are the storage classes of SQLite.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Value {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
 
-    /// Store all the data fields into a page
+impl Value {
+    /// Returns the number the text spells, ignoring surrounding whitespace,
+    /// or `None` if it is not a number
     ///
     /// # Arguments
     ///
-    /// * `row` - The `Row` struct containing data to be stored
-    /// * `page_num` - The corresponding page number where the data must be stored
-    pub fn serialize_row(&mut self, row: Row, page_num: u32) {
-        let id_bytes = row.id.to_ne_bytes();
-        let username_bytes = row.username;
-        let email_bytes = row.email;
-        self.pager.pages[page_num as usize].extend_from_slice(&id_bytes);
-        self.pager.pages[page_num as usize].extend_from_slice(&username_bytes);
-        self.pager.pages[page_num as usize].extend_from_slice(&email_bytes);
+    /// * `text` - The text to read
+    pub fn parse_number(text: &str) -> Option<Value> {
+        let text = text.trim();
+        if let Ok(value) = text.parse() {
+            Some(Value::Integer(value))
+        } else if text.contains(|c: char| c.is_ascii_digit()) {
+            // Rules out `inf` and `NaN`, which are not numbers in SQL
+            text.parse().ok().map(Value::Real)
+        } else {
+            None
+        }
     }
 
-    /// Retrieve a row from a given page and byte offset
+    /// Compares two values in the order `NULL` < numbers < text < blobs.
+    /// Integers and reals are compared by their numeric value.
     ///
     /// # Arguments
     ///
-    /// * `page_num` - The corresponding page number where the row data is present
-    /// * `byte_offset` - The offset in the page where the row data starts
-    pub fn deserialize_row(&self, page_num: u32, byte_offset: u32) -> Row {
-        let offset = byte_offset as usize;
-        let mut id_byte_arr = [0; 4];
-        let id_bytes_slice = &self.pager.pages[page_num as usize]
-            [(offset + ID_OFFSET)..(offset + ID_OFFSET + ID_SIZE)];
-        let username_bytes = &self.pager.pages[page_num as usize]
-            [(offset + USERNAME_OFFSET)..(offset + USERNAME_OFFSET + USERNAME_SIZE)];
-        let email_bytes = &self.pager.pages[page_num as usize]
-            [(offset + EMAIL_OFFSET)..(offset + EMAIL_OFFSET + EMAIL_SIZE)];
-        id_byte_arr.copy_from_slice(id_bytes_slice);
-        let id = u32::from_ne_bytes(id_byte_arr);
-        let mut username = [0u8; USERNAME_SIZE];
-        username.copy_from_slice(username_bytes);
-        let mut email = [0u8; EMAIL_SIZE];
-        email.copy_from_slice(email_bytes);
-        Row {
-            id,
-            username,
-            email,
+    /// * `other` - The value to compare with
+    pub fn compare(&self, other: &Value) -> Ordering {
+        match (self, other) {
+            (Value::Null, Value::Null) => Ordering::Equal,
+            (Value::Null, _) => Ordering::Less,
+            (_, Value::Null) => Ordering::Greater,
+            (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+            (Value::Integer(a), Value::Real(b)) => (*a as f64).total_cmp(b),
+            (Value::Real(a), Value::Integer(b)) => a.total_cmp(&(*b as f64)),
+            (Value::Real(a), Value::Real(b)) => a.total_cmp(b),
+            (Value::Text(a), Value::Text(b)) => a.cmp(b),
+            (Value::Blob(a), Value::Blob(b)) => a.cmp(b),
+            (Value::Blob(_), _) => Ordering::Greater,
+            (_, Value::Blob(_)) => Ordering::Less,
+            (Value::Text(_), _) => Ordering::Greater,
+            (_, Value::Text(_)) => Ordering::Less,
         }
     }
+}
 
-    /// Calculate the page and byte offset where a row must be present.
-    /// Also load the required page.
-    ///
-    /// # Arguments
-    ///
-    /// * `table` - A mutable reference to `Table` struct
-    /// * `row_num` - The index of the row in the table
-    pub fn row_slot(&mut self, row_num: u32) -> (u32, u32) {
-        let page_num = row_num / ROWS_PER_PAGE;
-        let row_offset = row_num % ROWS_PER_PAGE;
-        let byte_offset = row_offset * ROW_SIZE;
-        self.pager.get_page(page_num);
-        (page_num, byte_offset)
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "NULL"),
+            Value::Integer(value) => write!(f, "{}", value),
+            Value::Real(value) => write!(f, "{:?}", value),
+            Value::Text(value) => write!(f, "{}", value),
+            Value::Blob(bytes) => {
+                write!(f, "x'")?;
+                for byte in bytes {
+                    write!(f, "{:02x}", byte)?;
+                }
+                write!(f, "'")
+            }
+        }
     }
 }
 
-/// A struct to hold data present in a row
+/// A struct to hold data present in a row, one value per column
 pub struct Row {
-    pub id: u32,
-    pub username: [u8; USERNAME_SIZE],
-    pub email: [u8; EMAIL_SIZE],
+    pub values: Vec<Value>,
+}
+
+impl Default for Row {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Row {
     /// Returns an empty `Row`
     pub fn new() -> Self {
-        Row {
-            id: 0,
-            username: [0u8; USERNAME_SIZE],
-            email: [0u8; EMAIL_SIZE],
+        Row { values: Vec::new() }
+    }
+
+    /// Returns the key of the row, the value of the key column of the schema
+    ///
+    /// # Arguments
+    ///
+    /// * `schema` - The schema of the table the row belongs to
+    pub fn key(&self, schema: &Schema) -> i64 {
+        match self.values[schema.key_column] {
+            Value::Integer(key) => key,
+            _ => unreachable!("the key column is always an integer"),
         }
     }
 
+    /// Returns the record holding the values of the row
+    pub fn serialize(&self) -> Vec<u8> {
+        encode_record(&self.values)
+    }
+
+    /// Retrieve a row from its record. Columns missing
+    /// from the record are `NULL`.
+    ///
+    /// # Arguments
+    ///
+    /// * `schema` - The schema of the table the row belongs to
+    /// * `source` - The bytes of the record
+    pub fn deserialize(schema: &Schema, source: &[u8]) -> Self {
+        let mut values = decode_record(source);
+        values.resize(schema.columns.len(), Value::Null);
+        Row { values }
+    }
+
     /// Helper function to print a `Row`
     ///
     /// # Arguments
     ///
     /// * `row` - A non-mutable reference to a `Row` struct
     pub fn print_row(&self) {
-        println!(
-            "({}, {}, {})",
-            self.id,
-            std::str::from_utf8(&self.username)
-                .unwrap()
-                .trim_end_matches(char::from(0)),
-            std::str::from_utf8(&self.email)
-                .unwrap()
-                .trim_end_matches(char::from(0))
-        );
+        let values: Vec<String> = self.values.iter().map(|value| value.to_string()).collect();
+        println!("({})", values.join(", "));
     }
 }
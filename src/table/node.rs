@@ -0,0 +1,524 @@
+//! # Node
+//!
+//! Accessors for the on-page layout of the B+tree nodes.
+//! Every page of a tree is either a leaf node, which holds the cells
+//! sorted by their key, or an internal node, which holds keys and
+//! pointers to its children. All the keys of a tree have the same size,
+//! stored in every node, while the values held by leaf cells vary in size.
+
+use super::overflow::overflow_page_data_size;
+use super::{read_row_key, read_u32, write_u32};
+use crate::constants::{
+    INTERNAL_NODE_CHILD_SIZE, INTERNAL_NODE_HEADER_SIZE, INTERNAL_NODE_MIN_CAPACITY,
+    INTERNAL_NODE_NUM_KEYS_OFFSET, INTERNAL_NODE_RIGHT_CHILD_OFFSET, INVALID_PAGE_NUM,
+    IS_ROOT_OFFSET, KEY_SIZE_OFFSET, LEAF_NODE_CELL_CONTENT_OFFSET, LEAF_NODE_CELL_POINTER_SIZE,
+    LEAF_NODE_HEADER_SIZE, LEAF_NODE_NEXT_LEAF_OFFSET, LEAF_NODE_NUM_CELLS_OFFSET,
+    LEAF_NODE_OVERFLOW_POINTER_SIZE, LEAF_NODE_VALUE_LENGTH_SIZE, NODE_TYPE_OFFSET,
+    PARENT_POINTER_OFFSET, ROW_KEY_SIZE,
+};
+use std::cmp::Ordering;
+
+/// Enum to indicate the type of a node
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum NodeType {
+    Internal,
+    Leaf,
+}
+
+/// Compares two keys of the same tree. A key is made of values encoded
+/// so that their bytes compare like the values, followed by the id
+/// of a row, which is compared as a number. The key of a row in the
+/// B+tree of a table is just its id.
+pub fn compare_keys(a: &[u8], b: &[u8]) -> Ordering {
+    let (a_values, a_id) = a.split_at(a.len() - ROW_KEY_SIZE);
+    let (b_values, b_id) = b.split_at(b.len() - ROW_KEY_SIZE);
+    a_values
+        .cmp(b_values)
+        .then_with(|| read_row_key(a_id).cmp(&read_row_key(b_id)))
+}
+
+/// Returns the number of bytes a leaf node has for its cells and their offsets
+///
+/// # Arguments
+///
+/// * `page_size` - The page size of the database file
+pub fn leaf_node_space_for_cells(page_size: usize) -> usize {
+    page_size - LEAF_NODE_HEADER_SIZE
+}
+
+/// Returns the largest size of a cell, for which the cells of a full leaf
+/// node and a new cell can always be divided between two nodes
+///
+/// # Arguments
+///
+/// * `page_size` - The page size of the database file
+pub fn leaf_node_max_cell_size(page_size: usize) -> usize {
+    leaf_node_space_for_cells(page_size) / 2 - LEAF_NODE_CELL_POINTER_SIZE
+}
+
+/// Returns the least number of bytes of a value kept in its cell
+/// when the rest of the value goes into overflow pages
+///
+/// # Arguments
+///
+/// * `page_size` - The page size of the database file
+pub fn leaf_node_min_local_size(page_size: usize) -> usize {
+    leaf_node_space_for_cells(page_size) / 8
+}
+
+/// Returns the largest size of the keys of a tree, for which
+/// an internal node still holds `INTERNAL_NODE_MIN_CAPACITY` keys
+///
+/// # Arguments
+///
+/// * `page_size` - The page size of the database file
+pub fn max_key_size(page_size: usize) -> usize {
+    (page_size - INTERNAL_NODE_HEADER_SIZE) / INTERNAL_NODE_MIN_CAPACITY - INTERNAL_NODE_CHILD_SIZE
+}
+
+/// Returns the type of the node
+pub fn get_node_type(node: &[u8]) -> NodeType {
+    match node[NODE_TYPE_OFFSET] {
+        0 => NodeType::Internal,
+        _ => NodeType::Leaf,
+    }
+}
+
+/// Sets the type of the node
+pub fn set_node_type(node: &mut [u8], node_type: NodeType) {
+    node[NODE_TYPE_OFFSET] = match node_type {
+        NodeType::Internal => 0,
+        NodeType::Leaf => 1,
+    };
+}
+
+/// Returns true if the node is the root of the tree
+pub fn is_node_root(node: &[u8]) -> bool {
+    node[IS_ROOT_OFFSET] != 0
+}
+
+/// Marks the node as the root of the tree or not
+pub fn set_node_root(node: &mut [u8], is_root: bool) {
+    node[IS_ROOT_OFFSET] = is_root as u8;
+}
+
+/// Returns the page number of the parent of the node
+pub fn node_parent(node: &[u8]) -> u32 {
+    read_u32(node, PARENT_POINTER_OFFSET)
+}
+
+/// Sets the page number of the parent of the node
+pub fn set_node_parent(node: &mut [u8], parent: u32) {
+    write_u32(node, PARENT_POINTER_OFFSET, parent);
+}
+
+/// Returns the size of the keys stored in the node
+pub fn node_key_size(node: &[u8]) -> usize {
+    read_u32(node, KEY_SIZE_OFFSET) as usize
+}
+
+/// Sets the size of the keys stored in the node
+pub fn set_node_key_size(node: &mut [u8], key_size: usize) {
+    write_u32(node, KEY_SIZE_OFFSET, key_size as u32);
+}
+
+/// Returns the number of cells present in a leaf node
+pub fn leaf_node_num_cells(node: &[u8]) -> u32 {
+    read_u32(node, LEAF_NODE_NUM_CELLS_OFFSET)
+}
+
+/// Sets the number of cells present in a leaf node
+pub fn set_leaf_node_num_cells(node: &mut [u8], num_cells: u32) {
+    write_u32(node, LEAF_NODE_NUM_CELLS_OFFSET, num_cells);
+}
+
+/// Returns the page number of the leaf to the right of this one,
+/// 0 indicates that this is the rightmost leaf
+pub fn leaf_node_next_leaf(node: &[u8]) -> u32 {
+    read_u32(node, LEAF_NODE_NEXT_LEAF_OFFSET)
+}
+
+/// Sets the page number of the leaf to the right of this one
+pub fn set_leaf_node_next_leaf(node: &mut [u8], next_leaf: u32) {
+    write_u32(node, LEAF_NODE_NEXT_LEAF_OFFSET, next_leaf);
+}
+
+/// Returns the offset in the page where the cells of a leaf node start
+pub fn leaf_node_cell_content(node: &[u8]) -> usize {
+    read_u32(node, LEAF_NODE_CELL_CONTENT_OFFSET) as usize
+}
+
+/// Sets the offset in the page where the cells of a leaf node start
+pub fn set_leaf_node_cell_content(node: &mut [u8], offset: usize) {
+    write_u32(node, LEAF_NODE_CELL_CONTENT_OFFSET, offset as u32);
+}
+
+/// Returns the offset in the page where the offset of the cell with the given index is stored
+fn leaf_node_cell_pointer(cell_num: u32) -> usize {
+    LEAF_NODE_HEADER_SIZE + cell_num as usize * LEAF_NODE_CELL_POINTER_SIZE
+}
+
+/// Returns the offset in the page where the cell with the given index starts
+pub fn leaf_node_cell_offset(node: &[u8], cell_num: u32) -> usize {
+    read_u32(node, leaf_node_cell_pointer(cell_num)) as usize
+}
+
+/// Returns the number of bytes the cell with the given index takes,
+/// without its offset
+pub fn leaf_node_cell_size(node: &[u8], cell_num: u32) -> usize {
+    let key_size = node_key_size(node);
+    let value_size = leaf_node_value_size(node, cell_num);
+    let local_size = leaf_node_local_size(node.len(), key_size, value_size);
+    let mut size = key_size + LEAF_NODE_VALUE_LENGTH_SIZE + local_size;
+    if local_size < value_size {
+        size += LEAF_NODE_OVERFLOW_POINTER_SIZE;
+    }
+    size
+}
+
+/// Returns the number of bytes of a value kept in its cell. A value whose
+/// cell would be larger than `leaf_node_max_cell_size` keeps only its first
+/// bytes, the rest goes into overflow pages. Like in SQLite, the number of
+/// bytes kept is chosen so that the last overflow page is filled when possible.
+///
+/// # Arguments
+///
+/// * `page_size` - The page size of the database file
+/// * `key_size` - The size of the keys of the tree
+/// * `value_size` - The size of the whole value
+///
+/// # Example
+///
+/// ```
+/// use rsqlite::table::node::{leaf_node_local_size, leaf_node_min_local_size};
+/// use rsqlite::table::overflow::overflow_page_data_size;
+/// assert_eq!(leaf_node_local_size(4096, 8, 100), 100);
+/// let min_local_size = leaf_node_min_local_size(4096);
+/// let value_size = min_local_size + 2 * overflow_page_data_size(4096);
+/// assert_eq!(leaf_node_local_size(4096, 8, value_size), min_local_size);
+/// ```
+pub fn leaf_node_local_size(page_size: usize, key_size: usize, value_size: usize) -> usize {
+    let max_local_size =
+        leaf_node_max_cell_size(page_size) - key_size - LEAF_NODE_VALUE_LENGTH_SIZE;
+    if value_size <= max_local_size {
+        return value_size;
+    }
+
+    let min_local_size = leaf_node_min_local_size(page_size);
+    let local_size =
+        min_local_size + (value_size - min_local_size) % overflow_page_data_size(page_size);
+    if local_size <= max_local_size - LEAF_NODE_OVERFLOW_POINTER_SIZE {
+        local_size
+    } else {
+        min_local_size
+    }
+}
+
+/// Returns the number of bytes taken by the cells of a leaf node and their offsets
+pub fn leaf_node_used_space(node: &[u8]) -> usize {
+    leaf_node_num_cells(node) as usize * LEAF_NODE_CELL_POINTER_SIZE + node.len()
+        - leaf_node_cell_content(node)
+}
+
+/// Returns the number of bytes left between the offsets and the cells of a leaf node
+pub fn leaf_node_free_space(node: &[u8]) -> usize {
+    leaf_node_cell_content(node) - leaf_node_cell_pointer(leaf_node_num_cells(node))
+}
+
+/// Returns true if a leaf node other than the root holds too few bytes,
+/// in which case it is merged with or borrows from a sibling
+pub fn leaf_node_is_underfull(node: &[u8]) -> bool {
+    leaf_node_used_space(node) < leaf_node_space_for_cells(node.len()) / 2
+}
+
+/// Returns the bytes of a cell of a leaf node holding the given key and value.
+/// Only the bytes of the value kept in the cell are stored, followed by
+/// the page number of the first overflow page if they are not all of them.
+///
+/// # Arguments
+///
+/// * `page_size` - The page size of the database file
+/// * `key` - The key of the cell
+/// * `value` - The whole value
+/// * `overflow_page_num` - The first page of the overflow pages holding the rest of the value
+pub fn leaf_node_new_cell(
+    page_size: usize,
+    key: &[u8],
+    value: &[u8],
+    overflow_page_num: u32,
+) -> Vec<u8> {
+    let local_size = leaf_node_local_size(page_size, key.len(), value.len());
+    let mut cell = Vec::with_capacity(
+        key.len() + LEAF_NODE_VALUE_LENGTH_SIZE + local_size + LEAF_NODE_OVERFLOW_POINTER_SIZE,
+    );
+    cell.extend_from_slice(key);
+    cell.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    cell.extend_from_slice(&value[..local_size]);
+    if local_size < value.len() {
+        cell.extend_from_slice(&overflow_page_num.to_le_bytes());
+    }
+    cell
+}
+
+/// Returns the key of the cell with the given index
+pub fn leaf_node_key(node: &[u8], cell_num: u32) -> &[u8] {
+    let offset = leaf_node_cell_offset(node, cell_num);
+    &node[offset..offset + node_key_size(node)]
+}
+
+/// Returns the size of the whole value of the cell with the given index
+pub fn leaf_node_value_size(node: &[u8], cell_num: u32) -> usize {
+    let offset = leaf_node_cell_offset(node, cell_num) + node_key_size(node);
+    read_u32(node, offset) as usize
+}
+
+/// Returns the bytes of the value of the cell with the given index kept in the cell
+pub fn leaf_node_value(node: &[u8], cell_num: u32) -> &[u8] {
+    let key_size = node_key_size(node);
+    let value_size = leaf_node_value_size(node, cell_num);
+    let local_size = leaf_node_local_size(node.len(), key_size, value_size);
+    let offset = leaf_node_cell_offset(node, cell_num) + key_size + LEAF_NODE_VALUE_LENGTH_SIZE;
+    &node[offset..offset + local_size]
+}
+
+/// Returns the page number of the first overflow page holding the rest
+/// of the value of the cell with the given index, 0 if it has none
+pub fn leaf_node_overflow_page(node: &[u8], cell_num: u32) -> u32 {
+    let key_size = node_key_size(node);
+    let value_size = leaf_node_value_size(node, cell_num);
+    if leaf_node_local_size(node.len(), key_size, value_size) == value_size {
+        return 0;
+    }
+    let cell = leaf_node_cell(node, cell_num);
+    read_u32(cell, cell.len() - LEAF_NODE_OVERFLOW_POINTER_SIZE)
+}
+
+/// Returns the bytes of the whole cell with the given index
+pub fn leaf_node_cell(node: &[u8], cell_num: u32) -> &[u8] {
+    let offset = leaf_node_cell_offset(node, cell_num);
+    &node[offset..offset + leaf_node_cell_size(node, cell_num)]
+}
+
+/// Returns the bytes of all the cells of a leaf node, in order
+pub fn leaf_node_cells(node: &[u8]) -> Vec<Vec<u8>> {
+    (0..leaf_node_num_cells(node))
+        .map(|i| leaf_node_cell(node, i).to_vec())
+        .collect()
+}
+
+/// Inserts a cell at the given position of a leaf node, which must have room for it.
+/// The cell is stored in front of the other cells and its offset
+/// is inserted in the array of offsets.
+pub fn leaf_node_insert_cell(node: &mut [u8], cell_num: u32, cell: &[u8]) {
+    let num_cells = leaf_node_num_cells(node);
+    let offset = leaf_node_cell_content(node) - cell.len();
+    node[offset..offset + cell.len()].copy_from_slice(cell);
+    set_leaf_node_cell_content(node, offset);
+
+    node.copy_within(
+        leaf_node_cell_pointer(cell_num)..leaf_node_cell_pointer(num_cells),
+        leaf_node_cell_pointer(cell_num + 1),
+    );
+    write_u32(node, leaf_node_cell_pointer(cell_num), offset as u32);
+    set_leaf_node_num_cells(node, num_cells + 1);
+}
+
+/// Removes the cell with the given index from a leaf node. The cells
+/// stored in front of it are moved over it, so that no space is lost.
+pub fn leaf_node_remove_cell(node: &mut [u8], cell_num: u32) {
+    let num_cells = leaf_node_num_cells(node);
+    let offset = leaf_node_cell_offset(node, cell_num);
+    let size = leaf_node_cell_size(node, cell_num);
+    let content = leaf_node_cell_content(node);
+
+    node.copy_within(content..offset, content + size);
+    set_leaf_node_cell_content(node, content + size);
+    node.copy_within(
+        leaf_node_cell_pointer(cell_num + 1)..leaf_node_cell_pointer(num_cells),
+        leaf_node_cell_pointer(cell_num),
+    );
+    set_leaf_node_num_cells(node, num_cells - 1);
+
+    for i in 0..num_cells - 1 {
+        let cell_offset = leaf_node_cell_offset(node, i);
+        if cell_offset < offset {
+            write_u32(node, leaf_node_cell_pointer(i), (cell_offset + size) as u32);
+        }
+    }
+}
+
+/// Replaces the cells of a leaf node with the given ones, which must fit
+pub fn set_leaf_node_cells(node: &mut [u8], cells: &[Vec<u8>]) {
+    set_leaf_node_num_cells(node, 0);
+    set_leaf_node_cell_content(node, node.len());
+    for (i, cell) in cells.iter().enumerate() {
+        leaf_node_insert_cell(node, i as u32, cell);
+    }
+}
+
+/// Returns the number of keys present in an internal node
+pub fn internal_node_num_keys(node: &[u8]) -> u32 {
+    read_u32(node, INTERNAL_NODE_NUM_KEYS_OFFSET)
+}
+
+/// Sets the number of keys present in an internal node
+pub fn set_internal_node_num_keys(node: &mut [u8], num_keys: u32) {
+    write_u32(node, INTERNAL_NODE_NUM_KEYS_OFFSET, num_keys);
+}
+
+/// Returns the page number of the rightmost child of an internal node
+pub fn internal_node_right_child(node: &[u8]) -> u32 {
+    read_u32(node, INTERNAL_NODE_RIGHT_CHILD_OFFSET)
+}
+
+/// Sets the page number of the rightmost child of an internal node
+pub fn set_internal_node_right_child(node: &mut [u8], right_child: u32) {
+    write_u32(node, INTERNAL_NODE_RIGHT_CHILD_OFFSET, right_child);
+}
+
+/// Returns the size of a cell of an internal node
+pub fn internal_node_cell_size(node: &[u8]) -> usize {
+    INTERNAL_NODE_CHILD_SIZE + node_key_size(node)
+}
+
+/// Returns the maximum number of keys an internal node can hold
+pub fn internal_node_max_cells(node: &[u8]) -> u32 {
+    ((node.len() - INTERNAL_NODE_HEADER_SIZE) / internal_node_cell_size(node)) as u32
+}
+
+/// Returns the number of keys an internal node other than the root should hold at least,
+/// a node holding fewer is merged with or borrows from a sibling
+pub fn internal_node_min_cells(node: &[u8]) -> u32 {
+    internal_node_max_cells(node) / 2
+}
+
+/// Returns the offset in the page where the cell with the given index starts
+pub fn internal_node_cell_offset(node: &[u8], cell_num: u32) -> usize {
+    INTERNAL_NODE_HEADER_SIZE + cell_num as usize * internal_node_cell_size(node)
+}
+
+/// Returns the page number of the child with the given index.
+/// The child with index equal to the number of keys is the right child.
+pub fn internal_node_child(node: &[u8], child_num: u32) -> u32 {
+    let num_keys = internal_node_num_keys(node);
+    if child_num > num_keys {
+        println!(
+            "Tried to access child_num {} > num_keys {}",
+            child_num, num_keys
+        );
+        std::process::exit(1);
+    }
+
+    let page_num = if child_num == num_keys {
+        internal_node_right_child(node)
+    } else {
+        read_u32(node, internal_node_cell_offset(node, child_num))
+    };
+
+    if page_num == INVALID_PAGE_NUM {
+        println!(
+            "Tried to access child {} of node, but was invalid page",
+            child_num
+        );
+        std::process::exit(1);
+    }
+    page_num
+}
+
+/// Sets the page number of the child with the given index.
+/// The child with index equal to the number of keys is the right child.
+pub fn set_internal_node_child(node: &mut [u8], child_num: u32, page_num: u32) {
+    if child_num == internal_node_num_keys(node) {
+        set_internal_node_right_child(node, page_num);
+    } else {
+        let offset = internal_node_cell_offset(node, child_num);
+        write_u32(node, offset, page_num);
+    }
+}
+
+/// Returns the key of the cell with the given index
+pub fn internal_node_key(node: &[u8], key_num: u32) -> &[u8] {
+    let offset = internal_node_cell_offset(node, key_num) + INTERNAL_NODE_CHILD_SIZE;
+    &node[offset..offset + node_key_size(node)]
+}
+
+/// Sets the key of the cell with the given index
+pub fn set_internal_node_key(node: &mut [u8], key_num: u32, key: &[u8]) {
+    let offset = internal_node_cell_offset(node, key_num) + INTERNAL_NODE_CHILD_SIZE;
+    node[offset..offset + key.len()].copy_from_slice(key);
+}
+
+/// Returns the index of the child which should contain the given key
+pub fn internal_node_find_child(node: &[u8], key: &[u8]) -> u32 {
+    let num_keys = internal_node_num_keys(node);
+
+    // Binary search for the first key which is greater than or equal to the given key
+    let mut min_index = 0;
+    let mut max_index = num_keys; // there is one more child than key
+    while min_index != max_index {
+        let index = (min_index + max_index) / 2;
+        let key_to_right = internal_node_key(node, index);
+        if compare_keys(key_to_right, key) != Ordering::Less {
+            max_index = index;
+        } else {
+            min_index = index + 1;
+        }
+    }
+    min_index
+}
+
+/// Returns the index of the given child among the children of the node.
+/// The right child has an index equal to the number of keys.
+pub fn internal_node_child_index(node: &[u8], child_page_num: u32) -> u32 {
+    let num_keys = internal_node_num_keys(node);
+    match (0..=num_keys).find(|&i| internal_node_child(node, i) == child_page_num) {
+        Some(index) => index,
+        None => {
+            println!("Page {} is not a child of its parent.", child_page_num);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Removes the cell with the given index from an internal node,
+/// moving the following cells to the left
+pub fn internal_node_remove_cell(node: &mut [u8], cell_num: u32) {
+    let num_keys = internal_node_num_keys(node);
+    node.copy_within(
+        internal_node_cell_offset(node, cell_num + 1)..internal_node_cell_offset(node, num_keys),
+        internal_node_cell_offset(node, cell_num),
+    );
+    set_internal_node_num_keys(node, num_keys - 1);
+}
+
+/// Updates the key which pointed to the child holding `old_key` as its largest key
+pub fn update_internal_node_key(node: &mut [u8], old_key: &[u8], new_key: &[u8]) {
+    let old_child_index = internal_node_find_child(node, old_key);
+    // The right child has no key of its own
+    if old_child_index < internal_node_num_keys(node) {
+        set_internal_node_key(node, old_child_index, new_key);
+    }
+}
+
+/// Initializes the page as an empty leaf node
+/// whose cells hold keys of the given size
+pub fn initialize_leaf_node(node: &mut [u8], key_size: usize) {
+    set_node_type(node, NodeType::Leaf);
+    set_node_root(node, false);
+    set_node_key_size(node, key_size);
+    set_leaf_node_num_cells(node, 0);
+    set_leaf_node_next_leaf(node, 0); // 0 represents no sibling
+    set_leaf_node_cell_content(node, node.len());
+}
+
+/// Initializes the page as an empty internal node
+/// whose cells hold keys of the given size
+pub fn initialize_internal_node(node: &mut [u8], key_size: usize) {
+    set_node_type(node, NodeType::Internal);
+    set_node_root(node, false);
+    set_node_key_size(node, key_size);
+    set_internal_node_num_keys(node, 0);
+    // Page 0 holds the file header, so an empty internal node
+    // must point to an invalid page instead of defaulting to it
+    set_internal_node_right_child(node, INVALID_PAGE_NUM);
+}
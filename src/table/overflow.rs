@@ -0,0 +1,86 @@
+//! # Overflow
+//!
+//! Values too large to fit into a cell of a leaf node keep their first
+//! bytes in the cell, the rest is stored in a chain of overflow pages.
+//! Every overflow page starts with the page number of the next page of
+//! the chain, 0 on the last page, followed by the bytes of the value.
+
+use super::pager::Pager;
+use super::{read_u32, write_u32};
+use crate::constants::{OVERFLOW_PAGE_NEXT_OFFSET, OVERFLOW_PAGE_NEXT_SIZE};
+
+/// Returns the number of bytes of a value held by an overflow page
+///
+/// # Arguments
+///
+/// * `page_size` - The page size of the database file
+pub fn overflow_page_data_size(page_size: usize) -> usize {
+    page_size - OVERFLOW_PAGE_NEXT_SIZE
+}
+
+impl Pager {
+    /// Stores the bytes in a new chain of overflow pages
+    /// and returns the page number of its first page
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The bytes to be stored, at least one
+    pub fn write_overflow_chain(&mut self, bytes: &[u8]) -> u32 {
+        // Every page is loaded once it is picked, so that the next
+        // unused page is a different one
+        let data_size = overflow_page_data_size(self.page_size);
+        let mut page_nums = Vec::new();
+        for _ in bytes.chunks(data_size) {
+            let page_num = self.get_unused_page_num();
+            self.get_page(page_num);
+            page_nums.push(page_num);
+        }
+
+        for (i, chunk) in bytes.chunks(data_size).enumerate() {
+            let next_page_num = page_nums.get(i + 1).copied().unwrap_or(0);
+            let page = self.get_page_mut(page_nums[i]);
+            page.fill(0);
+            write_u32(page, OVERFLOW_PAGE_NEXT_OFFSET, next_page_num);
+            page[OVERFLOW_PAGE_NEXT_SIZE..OVERFLOW_PAGE_NEXT_SIZE + chunk.len()]
+                .copy_from_slice(chunk);
+        }
+        page_nums[0]
+    }
+
+    /// Appends the bytes held by a chain of overflow pages
+    ///
+    /// # Arguments
+    ///
+    /// * `page_num` - The page number of the first page of the chain
+    /// * `size` - The number of bytes held by the chain
+    /// * `bytes` - The bytes to append to
+    pub fn read_overflow_chain(&mut self, mut page_num: u32, size: usize, bytes: &mut Vec<u8>) {
+        let mut remaining = size;
+        while remaining > 0 {
+            if page_num == 0 {
+                println!("Overflow chain ends too early. Corrupt file.");
+                std::process::exit(1);
+            }
+            let page = self.get_page(page_num);
+            let chunk_size = remaining.min(overflow_page_data_size(page.len()));
+            bytes.extend_from_slice(
+                &page[OVERFLOW_PAGE_NEXT_SIZE..OVERFLOW_PAGE_NEXT_SIZE + chunk_size],
+            );
+            remaining -= chunk_size;
+            page_num = read_u32(page, OVERFLOW_PAGE_NEXT_OFFSET);
+        }
+    }
+
+    /// Adds every page of a chain of overflow pages to the free list
+    ///
+    /// # Arguments
+    ///
+    /// * `page_num` - The page number of the first page of the chain
+    pub fn free_overflow_chain(&mut self, mut page_num: u32) {
+        while page_num != 0 {
+            let next_page_num = read_u32(self.get_page(page_num), OVERFLOW_PAGE_NEXT_OFFSET);
+            self.free_page(page_num);
+            page_num = next_page_num;
+        }
+    }
+}
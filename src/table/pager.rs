@@ -1,16 +1,59 @@
 //! # Pager
-//! 
-//! Interface to load, hold and store pages into a file
+//!
+//! Interface to load, hold and store pages into a file.
+//! The pages in use are held in a cache of bounded size,
+//! changed pages are written back when they are evicted.
+//! The original image of every changed page is saved in the rollback
+//! journal first, so that a transaction is either committed as a whole
+//! or rolled back when the file is opened again. In WAL mode, changed
+//! pages are appended to the write-ahead log instead of the file.
 
-use crate::constants::{PAGE_SIZE, TABLE_MAX_PAGES};
+use super::cache::{Frame, PageCache};
+use super::header::{Header, JournalMode};
+use super::journal::{self, Journal};
+use super::wal::{wal_path, Wal};
+use super::{read_u32, write_u32};
+use crate::constants::{
+    DEFAULT_CACHE_SIZE, FORMAT_VERSION, FREE_PAGE_NEXT_OFFSET, HEADER_SIZE, MAX_PAGE_SIZE,
+    MIN_FORMAT_VERSION, MIN_PAGE_SIZE, WAL_AUTOCHECKPOINT,
+};
 use std::fs::{File, OpenOptions};
 use std::io::{Read, Seek, SeekFrom, Write};
 
 /// A struct to hold all the pages and file metadata
 pub struct Pager {
+    /// The path of the database file
+    pub filename: String,
     pub file: File,
     pub file_length: u64,
-    pub pages: Vec<Vec<u8>>,
+    /// The size of every page of the file, as stored in the header
+    pub page_size: usize,
+    pub num_pages: u32,
+    /// The pages held in memory
+    pub cache: PageCache,
+    pub header: Header,
+    pub journal: Journal,
+    /// The write-ahead log, present in WAL mode
+    pub wal: Option<Wal>,
+}
+
+/// Returns true if pages of the given size can be used,
+/// a power of two between `MIN_PAGE_SIZE` and `MAX_PAGE_SIZE`
+///
+/// # Arguments
+///
+/// * `page_size` - The page size to check
+///
+/// # Example
+///
+/// ```
+/// use rsqlite::table::pager::is_valid_page_size;
+/// assert!(is_valid_page_size(8192));
+/// assert!(!is_valid_page_size(1000));
+/// assert!(!is_valid_page_size(256));
+/// ```
+pub fn is_valid_page_size(page_size: u32) -> bool {
+    page_size.is_power_of_two() && (MIN_PAGE_SIZE..=MAX_PAGE_SIZE).contains(&page_size)
 }
 
 impl Pager {
@@ -21,108 +64,480 @@ impl Pager {
     /// # Arguments
     ///
     /// * `filename` - A string slice holding the file name
+    /// * `page_size` - The page size of a new file, an existing file
+    ///   keeps the page size stored in its header
     ///
     /// # Example
     ///
-    /// ```
-    /// use crate table::pager::Pager;
-    /// let pager = Pager::open("test.db");
+    /// ```no_run
+    /// use rsqlite::table::pager::Pager;
+    /// let pager = Pager::open("test.db", 4096);
     /// ```
     ///
     /// # Panics
     ///
-    /// Function might panic if there is some problem in creating or opening a file
-    pub fn open(filename: &str) -> Self {
+    /// Function might panic if there is some problem in creating or opening a file.
+    /// Exits if the file is not a database file this build can read.
+    pub fn open(filename: &str, page_size: u32) -> Self {
         let mut file = OpenOptions::new()
             .write(true)
             .read(true)
             .create(true)
+            .truncate(false)
             .open(filename)
             .unwrap();
+        // A transaction interrupted by a crash is rolled back before anything is read
+        journal::replay(filename, &mut file);
         let file_length = file.seek(SeekFrom::End(0)).unwrap();
 
+        let mut header = Header::new(page_size);
+        if file_length > 0 {
+            // The header tells the size of the pages, so it is read before any page
+            let mut bytes = [0u8; HEADER_SIZE];
+            let read = file
+                .seek(SeekFrom::Start(0))
+                .and_then(|_| file.read_exact(&mut bytes));
+            if read.is_err() || !Header::has_magic(&bytes) {
+                println!("Error: file is not a database.");
+                std::process::exit(1);
+            }
+
+            header = Header::deserialize(&bytes);
+            if header.format_version < MIN_FORMAT_VERSION || header.format_version > FORMAT_VERSION
+            {
+                println!(
+                    "Error: unsupported database format version {}.",
+                    header.format_version
+                );
+                std::process::exit(1);
+            }
+            if !is_valid_page_size(header.page_size) {
+                println!("Error: unsupported page size {}.", header.page_size);
+                std::process::exit(1);
+            }
+        }
+
+        if !file_length.is_multiple_of(header.page_size as u64) {
+            println!("Db file is not a whole number of pages. Corrupt file.");
+            std::process::exit(1);
+        }
+
+        // In WAL mode, the latest commit in the log holds the current
+        // number of pages and the current header
+        let mut num_pages = (file_length / header.page_size as u64) as u32;
+        let mut wal = None;
+        if header.journal_mode == JournalMode::Wal {
+            let mut log = Wal::open(filename, header.page_size as usize);
+            if log.db_size != 0 {
+                num_pages = log.db_size;
+            }
+            if let Some(offset) = log.find(0) {
+                let mut page = vec![0u8; header.page_size as usize];
+                log.read_page(offset, &mut page);
+                header = Header::deserialize(&page);
+            }
+            wal = Some(log);
+        }
+
+        if file_length > 0 && header.page_count != num_pages {
+            println!(
+                "Error: header page count {} does not match file size. Corrupt file.",
+                header.page_count
+            );
+            std::process::exit(1);
+        }
+
         Pager {
+            filename: filename.to_string(),
             file,
             file_length,
-            pages: vec![vec![]; TABLE_MAX_PAGES as usize],
+            page_size: header.page_size as usize,
+            num_pages,
+            // Pages are loaded lazily, when they are first used
+            cache: PageCache::new(DEFAULT_CACHE_SIZE),
+            header,
+            journal: Journal::new(filename, header.page_size as usize, num_pages),
+            wal,
         }
     }
 
-    /// Gets the page corresponding to the `page_num`
-    /// 
+    /// Discards every page and empties the file, which then holds pages of the given size
+    ///
     /// # Arguments
-    /// 
-    /// * `page_num` - The index of the page to be loaded
-    pub fn get_page(&mut self, page_num: u32) {
-        if page_num > TABLE_MAX_PAGES {
-            println!(
-                "Tried to fetch page number out of bounds. {} > {}",
-                page_num, TABLE_MAX_PAGES
-            );
+    ///
+    /// * `page_size` - The new page size, which must be valid
+    pub fn reset(&mut self, page_size: u32) {
+        // The whole file is discarded, so all of it must be in the journal
+        for page_num in 0..self.journal.original_num_pages {
+            if self.journal.needs(page_num) {
+                self.frame(page_num);
+                let frame = self.cache.get(page_num).unwrap();
+                self.journal.append(page_num, &frame.data);
+            }
+        }
+        self.journal.sync();
+
+        if self.file.set_len(0).is_err() {
+            println!("Error truncating file.");
             std::process::exit(1);
         }
+        self.file_length = 0;
+        self.page_size = page_size as usize;
+        self.num_pages = 0;
+        self.cache.clear();
+        self.header = Header::new(page_size);
+    }
+
+    /// Gets the page corresponding to the `page_num` for reading,
+    /// loading it from the file if it is not in memory yet
+    ///
+    /// # Arguments
+    ///
+    /// * `page_num` - The index of the page to be loaded
+    pub fn get_page(&mut self, page_num: u32) -> &[u8] {
+        self.frame(page_num).data.as_slice()
+    }
+
+    /// Gets the page corresponding to the `page_num` for writing,
+    /// loading it from the file if it is not in memory yet.
+    /// The page is marked as dirty, so that it is written to the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `page_num` - The index of the page to be loaded
+    pub fn get_page_mut(&mut self, page_num: u32) -> &mut [u8] {
+        self.frame(page_num);
+        let frame = self.cache.get(page_num).unwrap();
+        frame.dirty = true;
+        // The log keeps the original image of a page in WAL mode
+        if self.wal.is_none() && self.journal.needs(page_num) {
+            // The page is changed for the first time in this transaction
+            self.journal.append(page_num, &frame.data);
+        }
+        frame.data.as_mut_slice()
+    }
+
+    /// Returns the frame holding the page, loading the page if needed
+    ///
+    /// # Arguments
+    ///
+    /// * `page_num` - The index of the page
+    fn frame(&mut self, page_num: u32) -> &mut Frame {
+        if !self.cache.contains(page_num) {
+            self.load_page(page_num);
+        }
+        self.cache.get(page_num).unwrap()
+    }
 
-        if self.pages[page_num as usize].is_empty() {
-            // Cache miss. Load from file
-            let mut num_pages = self.file_length / PAGE_SIZE as u64;
+    /// Reads a page into the cache, evicting a page if the cache is full.
+    /// A page past the end of the file starts out filled with zeros.
+    ///
+    /// # Arguments
+    ///
+    /// * `page_num` - The index of the page to be loaded
+    fn load_page(&mut self, page_num: u32) {
+        self.evict_pages(self.cache.capacity - 1);
 
-            // We might save a partial page at the end of the file
-            if self.file_length % PAGE_SIZE as u64 > 0 {
-                num_pages += 1;
+        let mut page: Vec<u8> = vec![0; self.page_size];
+        let num_pages_on_disk = self.file_length / self.page_size as u64;
+        let mut on_disk = (page_num as u64) < num_pages_on_disk;
+
+        // The log holds a newer image of the page than the file
+        let wal_offset = self.wal.as_ref().and_then(|wal| wal.find(page_num));
+        if let Some(offset) = wal_offset {
+            self.wal.as_mut().unwrap().read_page(offset, &mut page);
+            on_disk = true;
+        } else if on_disk {
+            if self
+                .file
+                .seek(SeekFrom::Start(page_num as u64 * self.page_size as u64))
+                .is_err()
+            {
+                println!("Error seeking file.");
+                std::process::exit(1);
             }
 
-            if page_num as u64 <= num_pages {
-                if self
-                    .file
-                    .seek(SeekFrom::Start((page_num * PAGE_SIZE) as u64))
-                    .is_err()
-                {
-                    println!("Error seeking file.");
-                    std::process::exit(1);
-                }
-                let buf_size: usize = if ((page_num * PAGE_SIZE) as u64) <= self.file_length {
-                    (self.file_length - (page_num * PAGE_SIZE) as u64) as usize
-                } else {
-                    PAGE_SIZE as usize
-                };
+            if self.file.read_exact(page.as_mut_slice()).is_err() {
+                println!("Error reading file. {}", page.len());
+                std::process::exit(1);
+            }
+        }
 
-                let mut page: Vec<u8> = vec![0; buf_size];
+        self.cache.insert(Frame {
+            page_num,
+            data: page,
+            // A new page is dirty, the file must grow to hold it
+            dirty: !on_disk,
+            referenced: true,
+            pin_count: 0,
+        });
 
-                if self.file.read_exact(page.as_mut_slice()).is_err() {
-                    println!("Error reading file. {}", page.len());
-                    std::process::exit(1);
+        if page_num >= self.num_pages {
+            self.num_pages = page_num + 1;
+        }
+    }
+
+    /// Keeps the page in memory until it is unpinned as many times as it was pinned
+    ///
+    /// # Arguments
+    ///
+    /// * `page_num` - The index of the page
+    pub fn pin(&mut self, page_num: u32) {
+        self.frame(page_num).pin_count += 1;
+    }
+
+    /// Allows a pinned page to be evicted again once it is no longer pinned
+    ///
+    /// # Arguments
+    ///
+    /// * `page_num` - The index of the page
+    pub fn unpin(&mut self, page_num: u32) {
+        if let Some(frame) = self.cache.get(page_num) {
+            frame.pin_count = frame.pin_count.saturating_sub(1);
+        }
+    }
+
+    /// Changes the number of pages held in memory,
+    /// evicting pages until the cache holds no more than that
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The new number of pages, at least one
+    pub fn set_cache_size(&mut self, capacity: usize) {
+        self.cache.capacity = capacity;
+        self.evict_pages(capacity);
+    }
+
+    /// Evicts pages until the cache holds no more than the given number of pages,
+    /// writing changed pages to the file first
+    ///
+    /// # Arguments
+    ///
+    /// * `max_pages` - The number of pages left in the cache
+    fn evict_pages(&mut self, max_pages: usize) {
+        while self.cache.frames.len() > max_pages {
+            match self.cache.victim() {
+                Some(index) => {
+                    let frame = self.cache.remove(index);
+                    if frame.dirty {
+                        self.write_page(frame.page_num, &frame.data);
+                    }
                 }
-                self.pages[page_num as usize].extend_from_slice(page.as_slice());
+                // Every page is pinned, the cache grows past its capacity
+                None => break,
             }
         }
     }
 
+    /// Returns the page number of a page which is not in use yet.
+    /// Pages on the free list are reused first,
+    /// otherwise new pages go onto the end of the file.
+    pub fn get_unused_page_num(&mut self) -> u32 {
+        let page_num = self.header.freelist_head;
+        if page_num == 0 {
+            return self.num_pages;
+        }
+
+        self.header.freelist_head = read_u32(self.get_page(page_num), FREE_PAGE_NEXT_OFFSET);
+        self.header.freelist_count -= 1;
+        page_num
+    }
+
+    /// Adds a page which is no longer in use to the front of the free list
+    ///
+    /// # Arguments
+    ///
+    /// * `page_num` - The index of the page to be freed
+    pub fn free_page(&mut self, page_num: u32) {
+        let next_page_num = self.header.freelist_head;
+        let page = self.get_page_mut(page_num);
+        page.fill(0);
+        write_u32(page, FREE_PAGE_NEXT_OFFSET, next_page_num);
+
+        self.header.freelist_head = page_num;
+        self.header.freelist_count += 1;
+    }
+
+    /// Updates the page count in the header and stores the header in page 0.
+    /// Page 0 is only marked dirty if the header changed.
+    pub fn write_header(&mut self) {
+        self.header.page_count = self.num_pages;
+        let mut bytes = [0u8; HEADER_SIZE];
+        self.header.serialize(&mut bytes);
+        if self.get_page(0)[..HEADER_SIZE] != bytes {
+            self.get_page_mut(0)[..HEADER_SIZE].copy_from_slice(&bytes);
+        }
+    }
+
     /// Writes the page with given page number to the file on disk
-    /// 
+    /// if it changed since it was last written, then marks it clean
+    ///
     /// # Arguments
-    /// 
+    ///
     /// * `page_num` - The index of the page to be written to the disk
     pub fn flush(&mut self, page_num: u32) {
-        if self.pages[page_num as usize].is_empty() {
-            println!("Tried to flush null page");
+        let page = match self.cache.get(page_num) {
+            Some(frame) if !frame.dirty => return,
+            Some(frame) => {
+                frame.dirty = false;
+                std::mem::take(&mut frame.data)
+            }
+            None => {
+                println!("Tried to flush null page");
+                std::process::exit(1);
+            }
+        };
+
+        self.write_page(page_num, &page);
+        self.cache.get(page_num).unwrap().data = page;
+    }
+
+    /// Writes every dirty page held in memory to the file on disk
+    pub fn flush_all(&mut self) {
+        for page_num in self.cache.page_nums() {
+            self.flush(page_num);
+        }
+    }
+
+    /// Commits the transaction: writes the header and every dirty page,
+    /// syncs the file to disk, then deletes the journal.
+    /// In WAL mode, the pages are appended to the log instead.
+    pub fn commit(&mut self) {
+        self.write_header();
+        if self.wal.is_some() {
+            self.commit_wal();
+            return;
+        }
+        self.flush_all();
+
+        if self.file.sync_data().is_err() {
+            println!("Error syncing db file.");
             std::process::exit(1);
         }
+        self.journal.page_size = self.page_size;
+        self.journal.commit(self.num_pages);
+    }
+
+    /// Rolls back the transaction: the changed pages held in memory are discarded,
+    /// and the file is opened again, which replays the journal. In WAL mode,
+    /// the frames written by the transaction are discarded instead.
+    pub fn rollback(&mut self) {
+        // The journal is closed, but stays on disk to be replayed
+        self.journal.file = None;
+        let capacity = self.cache.capacity;
+        *self = Pager::open(&self.filename, self.journal.page_size as u32);
+        self.cache.capacity = capacity;
+    }
+
+    /// Appends every dirty page to the write-ahead log, the last one ending the commit.
+    /// Runs a checkpoint once the log has grown past `WAL_AUTOCHECKPOINT` frames.
+    fn commit_wal(&mut self) {
+        let mut page_nums: Vec<u32> = self
+            .cache
+            .page_nums()
+            .into_iter()
+            .filter(|&page_num| self.cache.get(page_num).unwrap().dirty)
+            .collect();
+        let wal = self.wal.as_mut().unwrap();
+        if page_nums.is_empty() {
+            if wal.pending.is_empty() {
+                return;
+            }
+            // Pages written to the log before the commit still need a frame ending it
+            page_nums.push(0);
+        }
+
+        let last = page_nums.len() - 1;
+        for (i, &page_num) in page_nums.iter().enumerate() {
+            let frame = self.cache.get(page_num).unwrap();
+            frame.dirty = false;
+            let commit_size = if i == last { self.num_pages } else { 0 };
+            wal.append(page_num, &frame.data, commit_size);
+        }
+        wal.commit(self.num_pages);
+
+        if wal.num_frames >= WAL_AUTOCHECKPOINT {
+            self.checkpoint();
+        }
+    }
+
+    /// Copies the committed pages of the write-ahead log into the database file.
+    /// Returns the number of frames in the log and the number of frames
+    /// checkpointed, or `None` if the database is not in WAL mode.
+    pub fn checkpoint(&mut self) -> Option<(u32, u32)> {
+        let wal = self.wal.as_mut()?;
+        let num_frames = wal.num_frames;
+        let checkpointed = wal.checkpoint(&mut self.file);
+        if checkpointed > 0 {
+            self.file_length = wal.db_size as u64 * self.page_size as u64;
+        }
+        Some((num_frames, checkpointed))
+    }
+
+    /// Switches to the given journal mode. The transaction in progress is committed
+    /// first, then the journal mode is stored in the header.
+    ///
+    /// # Arguments
+    ///
+    /// * `journal_mode` - The new journal mode
+    pub fn set_journal_mode(&mut self, journal_mode: JournalMode) {
+        if journal_mode == self.header.journal_mode {
+            return;
+        }
+        self.commit();
 
-        if self
-            .file
-            .seek(SeekFrom::Start((page_num * PAGE_SIZE) as u64))
-            .is_err()
-        {
+        match journal_mode {
+            JournalMode::Wal => {
+                // Older builds would ignore the log, so they must not open the file
+                self.header.format_version = FORMAT_VERSION;
+                self.header.journal_mode = journal_mode;
+                self.commit();
+                // A log left over from an earlier time in WAL mode is out of date
+                let _ = std::fs::remove_file(wal_path(&self.filename));
+                self.wal = Some(Wal::open(&self.filename, self.page_size));
+            }
+            JournalMode::Delete => {
+                // Nothing is pending after the commit, so the whole log is checkpointed
+                self.checkpoint();
+                self.wal = None;
+                if std::fs::remove_file(wal_path(&self.filename)).is_err() {
+                    println!("Error deleting write-ahead log.");
+                    std::process::exit(1);
+                }
+                self.journal.commit(self.num_pages);
+                self.header.journal_mode = journal_mode;
+                self.commit();
+            }
+        }
+    }
+
+    /// Writes the bytes of a page at its place in the file,
+    /// which grows if the page is past its end.
+    /// In WAL mode, the page is appended to the log instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `page_num` - The index of the page
+    /// * `page` - The bytes of the page
+    fn write_page(&mut self, page_num: u32, page: &[u8]) {
+        if let Some(wal) = &mut self.wal {
+            wal.append(page_num, page, 0);
+            return;
+        }
+
+        // The original image of the page must be on disk before it is overwritten
+        self.journal.sync();
+
+        let offset = page_num as u64 * self.page_size as u64;
+        if self.file.seek(SeekFrom::Start(offset)).is_err() {
             println!("Error seeking.");
             std::process::exit(1);
         }
 
-        let drained_vec: Vec<u8> = self.pages[page_num as usize].drain(..).collect();
-        self.pages[page_num as usize].shrink_to_fit();
-
-        if self.file.write_all(drained_vec.as_ref()).is_err() {
+        if self.file.write_all(page).is_err() {
             println!("Error writing.");
             std::process::exit(1);
         }
+        self.file_length = self.file_length.max(offset + page.len() as u64);
     }
 }
@@ -0,0 +1,205 @@
+//! # Record
+//!
+//! The record format rows are stored in, modelled on the one of SQLite.
+//! A record starts with a header: its size in bytes followed by one serial
+//! type per value, naming the storage class and size of the value. The
+//! values follow the header, packed without padding:
+//!
+//! * `NULL` and the integers 0 and 1 take no bytes
+//! * other integers take 1, 2, 3, 4, 6 or 8 bytes, the fewest which hold them
+//! * reals take 8 bytes
+//! * text and blobs take as many bytes as they hold
+//!
+//! Sizes and serial types are varints: 7 bits per byte, least significant
+//! first, the high bit set on every byte but the last. Like every integer
+//! in the database file, integer values are stored in little-endian order.
+
+use super::Value;
+use crate::constants::{
+    SERIAL_TYPE_BLOB, SERIAL_TYPE_NULL, SERIAL_TYPE_ONE, SERIAL_TYPE_REAL, SERIAL_TYPE_TEXT,
+    SERIAL_TYPE_ZERO,
+};
+
+/// Number of bytes taken by the integers of serial types 1 to 6
+const INTEGER_SIZES: [usize; 6] = [1, 2, 3, 4, 6, 8];
+
+/// Returns the bytes of the record holding the given values
+///
+/// # Arguments
+///
+/// * `values` - The values of the record, in order
+///
+/// # Example
+///
+/// ```
+/// use rsqlite::table::record::{decode_record, encode_record};
+/// use rsqlite::table::Value;
+/// let values = vec![Value::Integer(-300), Value::Null, Value::Text("a\0b".to_string())];
+/// let record = encode_record(&values);
+/// assert_eq!(record.len(), 9);
+/// assert_eq!(decode_record(&record), values);
+/// ```
+pub fn encode_record(values: &[Value]) -> Vec<u8> {
+    let serial_types: Vec<u64> = values.iter().map(serial_type).collect();
+    let types_size: usize = serial_types.iter().map(|&t| varint_size(t)).sum();
+
+    let mut record = Vec::new();
+    write_varint(&mut record, header_size(types_size) as u64);
+    for &serial_type in &serial_types {
+        write_varint(&mut record, serial_type);
+    }
+    for (value, &serial_type) in values.iter().zip(&serial_types) {
+        match value {
+            Value::Null => (),
+            Value::Integer(value) => {
+                let size = serial_type_size(serial_type);
+                record.extend_from_slice(&value.to_le_bytes()[..size]);
+            }
+            Value::Real(value) => record.extend_from_slice(&value.to_le_bytes()),
+            Value::Text(value) => record.extend_from_slice(value.as_bytes()),
+            Value::Blob(value) => record.extend_from_slice(value),
+        }
+    }
+    record
+}
+
+/// Returns the values held by a record
+///
+/// # Arguments
+///
+/// * `record` - The bytes of the record
+pub fn decode_record(record: &[u8]) -> Vec<Value> {
+    let mut header_offset = 0;
+    let header_size = read_varint(record, &mut header_offset) as usize;
+    let mut offset = header_size;
+
+    let mut values = Vec::new();
+    while header_offset < header_size {
+        let serial_type = read_varint(record, &mut header_offset);
+        let size = serial_type_size(serial_type);
+        let bytes = &record[offset..offset + size];
+        offset += size;
+
+        values.push(match serial_type {
+            SERIAL_TYPE_NULL => Value::Null,
+            SERIAL_TYPE_REAL => {
+                let mut real = [0u8; 8];
+                real.copy_from_slice(bytes);
+                Value::Real(f64::from_le_bytes(real))
+            }
+            SERIAL_TYPE_ZERO => Value::Integer(0),
+            SERIAL_TYPE_ONE => Value::Integer(1),
+            1..=6 => {
+                // Sign extend the integer to 8 bytes
+                let negative = bytes[size - 1] & 0x80 != 0;
+                let mut integer = [if negative { 0xff } else { 0 }; 8];
+                integer[..size].copy_from_slice(bytes);
+                Value::Integer(i64::from_le_bytes(integer))
+            }
+            serial_type if serial_type % 2 == 0 => Value::Blob(bytes.to_vec()),
+            _ => Value::Text(String::from_utf8_lossy(bytes).into_owned()),
+        });
+    }
+    values
+}
+
+/// Returns the serial type of a value
+///
+/// # Arguments
+///
+/// * `value` - The value to be stored in a record
+pub fn serial_type(value: &Value) -> u64 {
+    match value {
+        Value::Null => SERIAL_TYPE_NULL,
+        Value::Integer(0) => SERIAL_TYPE_ZERO,
+        Value::Integer(1) => SERIAL_TYPE_ONE,
+        Value::Integer(value) => {
+            let value = *value;
+            let fits = |size: usize| {
+                let bits = size as u32 * 8;
+                let min = -(1i128 << (bits - 1));
+                let max = (1i128 << (bits - 1)) - 1;
+                (min..=max).contains(&(value as i128))
+            };
+            let index = INTEGER_SIZES.iter().position(|&size| fits(size));
+            index.unwrap_or(INTEGER_SIZES.len() - 1) as u64 + 1
+        }
+        Value::Real(_) => SERIAL_TYPE_REAL,
+        Value::Text(value) => SERIAL_TYPE_TEXT + 2 * value.len() as u64,
+        Value::Blob(value) => SERIAL_TYPE_BLOB + 2 * value.len() as u64,
+    }
+}
+
+/// Returns the number of bytes taken by a value of the given serial type
+///
+/// # Arguments
+///
+/// * `serial_type` - The serial type of the value
+pub fn serial_type_size(serial_type: u64) -> usize {
+    match serial_type {
+        1..=6 => INTEGER_SIZES[serial_type as usize - 1],
+        SERIAL_TYPE_REAL => 8,
+        serial_type if serial_type >= SERIAL_TYPE_BLOB => {
+            ((serial_type - SERIAL_TYPE_BLOB) / 2) as usize
+        }
+        _ => 0,
+    }
+}
+
+/// Returns the size of the header of a record, given the number of bytes
+/// taken by the serial types. The size includes the varint holding it.
+///
+/// # Arguments
+///
+/// * `types_size` - The number of bytes taken by the serial types
+pub fn header_size(types_size: usize) -> usize {
+    let mut size = types_size + 1;
+    while types_size + varint_size(size as u64) != size {
+        size = types_size + varint_size(size as u64);
+    }
+    size
+}
+
+/// Returns the number of bytes the varint holding the value takes
+///
+/// # Arguments
+///
+/// * `value` - The value to be stored
+pub fn varint_size(value: u64) -> usize {
+    let bits = 64 - value.leading_zeros() as usize;
+    bits.div_ceil(7).max(1)
+}
+
+/// Appends the value to the bytes as a varint
+///
+/// # Arguments
+///
+/// * `bytes` - The bytes to append to
+/// * `value` - The value to be stored
+pub fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+/// Reads the varint at the given offset, moving the offset past it
+///
+/// # Arguments
+///
+/// * `bytes` - The bytes holding the varint
+/// * `offset` - The offset of the varint, moved to the byte following it
+pub fn read_varint(bytes: &[u8], offset: &mut usize) -> u64 {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = bytes[*offset];
+        *offset += 1;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 || shift >= 63 {
+            return value;
+        }
+        shift += 7;
+    }
+}
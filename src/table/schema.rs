@@ -0,0 +1,142 @@
+//! # Schema
+//!
+//! Description of the columns of a table, parsed from its
+//! `create table` statement
+
+use super::Value;
+use crate::vm::parser::parse;
+use crate::vm::statement::Statement;
+
+/// Enum to indicate the type of a column, which decides how values
+/// are converted before they are stored in it, like the type affinity of SQLite
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ColumnType {
+    /// A 64 bit signed integer, reals which are not whole numbers are kept as they are
+    Integer,
+    /// A 64 bit floating point number
+    Real,
+    /// A string of at most the given number of bytes, or of any length
+    Text(Option<usize>),
+    /// Any value, blobs and text of at most the given number of bytes, or of any length
+    Blob(Option<usize>),
+}
+
+impl ColumnType {
+    /// Returns whether text or a blob of the given number of bytes is longer
+    /// than the size of the column allows. Columns without a size take any length.
+    ///
+    /// # Arguments
+    ///
+    /// * `length` - The number of bytes of the value
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rsqlite::table::schema::ColumnType;
+    /// assert!(ColumnType::Text(Some(8)).is_too_long(9));
+    /// assert!(!ColumnType::Blob(None).is_too_long(2_000_000_000));
+    /// ```
+    pub fn is_too_long(&self, length: usize) -> bool {
+        match self {
+            ColumnType::Text(Some(size)) | ColumnType::Blob(Some(size)) => length > *size,
+            _ => false,
+        }
+    }
+
+    /// Converts a value to the storage class preferred by the column.
+    /// Text which spells a number becomes a number in `integer` and `real`
+    /// columns, reals which are whole numbers become integers in `integer`
+    /// columns, numbers become text in `text` columns and `blob` columns keep
+    /// values as they are.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value to be stored in the column
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rsqlite::table::schema::ColumnType;
+    /// use rsqlite::table::Value;
+    /// let text = Value::Text(" 42 ".to_string());
+    /// assert_eq!(ColumnType::Integer.apply_affinity(text), Value::Integer(42));
+    /// assert_eq!(ColumnType::Real.apply_affinity(Value::Integer(3)), Value::Real(3.0));
+    /// assert_eq!(ColumnType::Text(Some(8)).apply_affinity(Value::Real(2.5)), Value::Text("2.5".to_string()));
+    /// ```
+    pub fn apply_affinity(&self, value: Value) -> Value {
+        match (self, value) {
+            (ColumnType::Integer, Value::Text(text)) => match Value::parse_number(&text) {
+                Some(number) => self.apply_affinity(number),
+                None => Value::Text(text),
+            },
+            (ColumnType::Integer, Value::Real(value)) => {
+                let integer = value as i64;
+                // The bounds are exact, -2^63 and 2^63, as the cast saturates
+                if integer as f64 == value
+                    && (-9.223_372_036_854_776e18..9.223_372_036_854_776e18).contains(&value)
+                {
+                    Value::Integer(integer)
+                } else {
+                    Value::Real(value)
+                }
+            }
+            (ColumnType::Real, Value::Text(text)) => match Value::parse_number(&text) {
+                Some(number) => self.apply_affinity(number),
+                None => Value::Text(text),
+            },
+            (ColumnType::Real, Value::Integer(value)) => Value::Real(value as f64),
+            (ColumnType::Text(_), value @ Value::Integer(_))
+            | (ColumnType::Text(_), value @ Value::Real(_)) => Value::Text(value.to_string()),
+            (_, value) => value,
+        }
+    }
+}
+
+/// A struct to hold the name and type of a column
+#[derive(Clone, Debug)]
+pub struct Column {
+    pub name: String,
+    pub col_type: ColumnType,
+}
+
+/// A struct to hold the definition of a table
+#[derive(Clone, Debug)]
+pub struct Schema {
+    pub name: String,
+    pub columns: Vec<Column>,
+    /// Index of the integer column rows are keyed by
+    pub key_column: usize,
+    /// The `create table` statement the schema was parsed from
+    pub sql: String,
+}
+
+impl Schema {
+    /// Parses a `create table` statement of the form
+    /// `create table <name> (<column> <type> [primary key], ...)`.
+    /// Supported types are `integer`, `real`, `text[(<size>)]` and `blob[(<size>)]`.
+    /// The key of the table is the column marked as `primary key`,
+    /// or the first column if none is marked, and must be an integer.
+    ///
+    /// Returns `None` if the statement could not be parsed.
+    ///
+    /// # Arguments
+    ///
+    /// * `sql` - The `create table` statement
+    pub fn parse(sql: &str) -> Option<Self> {
+        match parse(sql) {
+            Ok(Statement::CreateTable(schema)) => Some(schema),
+            _ => None,
+        }
+    }
+
+    /// Returns the position of the named column, ignoring case
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the column
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns
+            .iter()
+            .position(|column| column.name.eq_ignore_ascii_case(name))
+    }
+}
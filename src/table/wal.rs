@@ -0,0 +1,334 @@
+//! # Wal
+//!
+//! In WAL mode, the database file is not changed by a commit. Instead, the
+//! changed pages are appended to the write-ahead log, `<db>-wal`, as frames.
+//! The last frame of a commit holds the number of pages of the database after
+//! the commit, and a commit is durable once the log is synced.
+//!
+//! Every frame holds a checksum covering the checksum of the previous frame, so
+//! that the frames are only valid in the order they were written. The salt of
+//! the log is stored in every frame, and changes whenever the log is reset, so
+//! that a frame left over from before the reset is never mistaken for a new one.
+//!
+//! Readers find the latest committed frame of a page through the WAL index,
+//! and read the page from the database file only if the log does not hold it.
+//! A checkpoint copies the committed pages back into the database file,
+//! after which the log starts over.
+
+use super::journal::checksum;
+use super::{read_u32, write_u32};
+use crate::constants::{
+    WAL_FRAME_CHECKSUM_OFFSET, WAL_FRAME_COMMIT_SIZE_OFFSET, WAL_FRAME_HEADER_SIZE,
+    WAL_FRAME_PAGE_NUM_OFFSET, WAL_FRAME_SALT_OFFSET, WAL_HEADER_SIZE, WAL_MAGIC, WAL_MAGIC_SIZE,
+    WAL_PAGE_SIZE_OFFSET, WAL_SALT_OFFSET,
+};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+/// A struct to hold the write-ahead log of a database file and its index
+pub struct Wal {
+    pub file: File,
+    /// The page size of the database file
+    pub page_size: usize,
+    /// Changed every time the log is reset
+    pub salt: u32,
+    /// The WAL index, the offset in the log of the latest committed image of every page
+    pub index: HashMap<u32, u64>,
+    /// The offset in the log of the images written by the transaction in progress
+    pub pending: HashMap<u32, u64>,
+    /// The number of frames in the log
+    pub num_frames: u32,
+    /// The number of frames up to the end of the last commit
+    pub committed_frames: u32,
+    /// The number of pages of the database after the last commit in the log,
+    /// 0 if the log holds no commit
+    pub db_size: u32,
+    /// The checksum of the last frame
+    pub last_checksum: u32,
+    /// The checksum of the last frame of the last commit
+    pub committed_checksum: u32,
+}
+
+/// Returns the path of the write-ahead log of the given database file
+///
+/// # Arguments
+///
+/// * `filename` - The path of the database file
+pub fn wal_path(filename: &str) -> String {
+    format!("{}-wal", filename)
+}
+
+/// Returns the checksum of a frame, which continues from the checksum of the previous frame
+///
+/// # Arguments
+///
+/// * `previous` - The checksum of the previous frame, or of the log header for the first frame
+/// * `frame_header` - The frame header, of which the checksum itself is not covered
+/// * `page` - The image of the page
+fn frame_checksum(previous: u32, frame_header: &[u8], page: &[u8]) -> u32 {
+    let mut bytes = Vec::with_capacity(4 + WAL_FRAME_CHECKSUM_OFFSET + page.len());
+    bytes.extend_from_slice(&previous.to_le_bytes());
+    bytes.extend_from_slice(&frame_header[..WAL_FRAME_CHECKSUM_OFFSET]);
+    bytes.extend_from_slice(page);
+    checksum(&bytes)
+}
+
+impl Wal {
+    /// Opens the write-ahead log of a database file, creating it if it is not present,
+    /// and builds the WAL index from the committed frames. Frames after the last
+    /// commit, written by a transaction which never committed, are discarded.
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - The path of the database file
+    /// * `page_size` - The page size of the database file
+    pub fn open(filename: &str, page_size: usize) -> Self {
+        let file = match OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(wal_path(filename))
+        {
+            Ok(file) => file,
+            Err(_) => {
+                println!("Error opening write-ahead log.");
+                std::process::exit(1);
+            }
+        };
+
+        let mut wal = Wal {
+            file,
+            page_size,
+            salt: 0,
+            index: HashMap::new(),
+            pending: HashMap::new(),
+            num_frames: 0,
+            committed_frames: 0,
+            db_size: 0,
+            last_checksum: 0,
+            committed_checksum: 0,
+        };
+
+        let mut header = [0u8; WAL_HEADER_SIZE];
+        let read = wal
+            .file
+            .seek(SeekFrom::Start(0))
+            .and_then(|_| wal.file.read_exact(&mut header));
+        if read.is_ok()
+            && &header[..WAL_MAGIC_SIZE] == WAL_MAGIC
+            && read_u32(&header, WAL_PAGE_SIZE_OFFSET) as usize == page_size
+        {
+            wal.salt = read_u32(&header, WAL_SALT_OFFSET);
+            wal.committed_checksum = checksum(&header);
+            wal.recover();
+        }
+        // A log without a valid header holds no commit
+        wal.reset();
+        wal
+    }
+
+    /// Reads the frames of the log, adding those of every complete commit to the index
+    fn recover(&mut self) {
+        let mut frame_header = [0u8; WAL_FRAME_HEADER_SIZE];
+        let mut page = vec![0u8; self.page_size];
+        let mut previous = self.committed_checksum;
+        let mut frames = HashMap::new();
+        let mut num_frames = 0;
+
+        loop {
+            let offset = self.frame_offset(num_frames);
+            let read = self
+                .file
+                .seek(SeekFrom::Start(offset))
+                .and_then(|_| self.file.read_exact(&mut frame_header))
+                .and_then(|_| self.file.read_exact(&mut page));
+            // A frame torn by a crash or left over from before a reset ends the log
+            if read.is_err()
+                || read_u32(&frame_header, WAL_FRAME_SALT_OFFSET) != self.salt
+                || read_u32(&frame_header, WAL_FRAME_CHECKSUM_OFFSET)
+                    != frame_checksum(previous, &frame_header, &page)
+            {
+                break;
+            }
+            previous = read_u32(&frame_header, WAL_FRAME_CHECKSUM_OFFSET);
+            num_frames += 1;
+
+            let page_num = read_u32(&frame_header, WAL_FRAME_PAGE_NUM_OFFSET);
+            frames.insert(page_num, offset + WAL_FRAME_HEADER_SIZE as u64);
+            let commit_size = read_u32(&frame_header, WAL_FRAME_COMMIT_SIZE_OFFSET);
+            if commit_size != 0 {
+                self.index.extend(frames.drain());
+                self.committed_frames = num_frames;
+                self.committed_checksum = previous;
+                self.db_size = commit_size;
+            }
+        }
+        self.num_frames = self.committed_frames;
+        self.last_checksum = self.committed_checksum;
+    }
+
+    /// Removes the frames after the last commit. A log without any
+    /// commit starts over with a new header and a new salt.
+    fn reset(&mut self) {
+        if self.committed_frames == 0 {
+            self.salt = self.salt.wrapping_add(1);
+            let mut header = [0u8; WAL_HEADER_SIZE];
+            header[..WAL_MAGIC_SIZE].copy_from_slice(WAL_MAGIC);
+            write_u32(&mut header, WAL_PAGE_SIZE_OFFSET, self.page_size as u32);
+            write_u32(&mut header, WAL_SALT_OFFSET, self.salt);
+            if self
+                .file
+                .seek(SeekFrom::Start(0))
+                .and_then(|_| self.file.write_all(&header))
+                .is_err()
+            {
+                println!("Error writing write-ahead log.");
+                std::process::exit(1);
+            }
+            self.index.clear();
+            self.committed_checksum = checksum(&header);
+        }
+
+        let length = self.frame_offset(self.committed_frames);
+        if self.file.set_len(length).is_err() || self.file.sync_data().is_err() {
+            println!("Error writing write-ahead log.");
+            std::process::exit(1);
+        }
+        self.pending.clear();
+        self.num_frames = self.committed_frames;
+        self.last_checksum = self.committed_checksum;
+    }
+
+    /// Returns the offset in the log of the frame with the given index
+    ///
+    /// # Arguments
+    ///
+    /// * `frame_num` - The index of the frame
+    fn frame_offset(&self, frame_num: u32) -> u64 {
+        WAL_HEADER_SIZE as u64 + frame_num as u64 * (WAL_FRAME_HEADER_SIZE + self.page_size) as u64
+    }
+
+    /// Returns the offset in the log of the latest image of the page,
+    /// if the log holds the page
+    ///
+    /// # Arguments
+    ///
+    /// * `page_num` - The index of the page
+    pub fn find(&self, page_num: u32) -> Option<u64> {
+        self.pending
+            .get(&page_num)
+            .or_else(|| self.index.get(&page_num))
+            .copied()
+    }
+
+    /// Reads the image of a page from the log
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset of the image in the log, as returned by `find`
+    /// * `page` - The bytes to read the page into
+    pub fn read_page(&mut self, offset: u64, page: &mut [u8]) {
+        if self
+            .file
+            .seek(SeekFrom::Start(offset))
+            .and_then(|_| self.file.read_exact(page))
+            .is_err()
+        {
+            println!("Error reading write-ahead log.");
+            std::process::exit(1);
+        }
+    }
+
+    /// Appends the image of a page to the log
+    ///
+    /// # Arguments
+    ///
+    /// * `page_num` - The index of the page
+    /// * `page` - The bytes of the page
+    /// * `commit_size` - The number of pages of the database
+    ///   if the frame ends a commit, otherwise 0
+    pub fn append(&mut self, page_num: u32, page: &[u8], commit_size: u32) {
+        let mut frame = vec![0u8; WAL_FRAME_HEADER_SIZE];
+        write_u32(&mut frame, WAL_FRAME_PAGE_NUM_OFFSET, page_num);
+        write_u32(&mut frame, WAL_FRAME_COMMIT_SIZE_OFFSET, commit_size);
+        write_u32(&mut frame, WAL_FRAME_SALT_OFFSET, self.salt);
+        let frame_checksum = frame_checksum(self.last_checksum, &frame, page);
+        write_u32(&mut frame, WAL_FRAME_CHECKSUM_OFFSET, frame_checksum);
+        frame.extend_from_slice(page);
+
+        let offset = self.frame_offset(self.num_frames);
+        if self
+            .file
+            .seek(SeekFrom::Start(offset))
+            .and_then(|_| self.file.write_all(&frame))
+            .is_err()
+        {
+            println!("Error writing write-ahead log.");
+            std::process::exit(1);
+        }
+        self.pending
+            .insert(page_num, offset + WAL_FRAME_HEADER_SIZE as u64);
+        self.num_frames += 1;
+        self.last_checksum = frame_checksum;
+    }
+
+    /// Makes the frames of the transaction durable and visible to readers.
+    /// The last frame appended must end the commit.
+    ///
+    /// # Arguments
+    ///
+    /// * `db_size` - The number of pages of the database after the commit
+    pub fn commit(&mut self, db_size: u32) {
+        if self.file.sync_data().is_err() {
+            println!("Error syncing write-ahead log.");
+            std::process::exit(1);
+        }
+        self.index.extend(self.pending.drain());
+        self.committed_frames = self.num_frames;
+        self.committed_checksum = self.last_checksum;
+        self.db_size = db_size;
+    }
+
+    /// Copies the latest committed image of every page in the log into the database file.
+    /// The log starts over, unless a transaction in progress has written frames to it.
+    /// Returns the number of frames which were checkpointed.
+    ///
+    /// # Arguments
+    ///
+    /// * `db_file` - The database file
+    pub fn checkpoint(&mut self, db_file: &mut File) -> u32 {
+        let checkpointed = self.committed_frames;
+        if checkpointed == 0 {
+            return 0;
+        }
+
+        let mut page_nums: Vec<u32> = self.index.keys().copied().collect();
+        page_nums.sort_unstable();
+        let mut page = vec![0u8; self.page_size];
+        for page_num in page_nums {
+            self.read_page(self.index[&page_num], &mut page);
+            if db_file
+                .seek(SeekFrom::Start(page_num as u64 * self.page_size as u64))
+                .and_then(|_| db_file.write_all(&page))
+                .is_err()
+            {
+                println!("Error writing checkpoint.");
+                std::process::exit(1);
+            }
+        }
+
+        // The log is only reset once the database file holds all of it
+        let length = self.db_size as u64 * self.page_size as u64;
+        if db_file.set_len(length).is_err() || db_file.sync_all().is_err() {
+            println!("Error writing checkpoint.");
+            std::process::exit(1);
+        }
+        if self.pending.is_empty() {
+            self.committed_frames = 0;
+            self.reset();
+        }
+        checkpointed
+    }
+}
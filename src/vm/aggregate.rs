@@ -0,0 +1,182 @@
+//! # Aggregate
+//!
+//! Aggregate functions compute a single value from the rows of a group.
+//! An accumulator is stepped with the arguments of every row of the group,
+//! then finished to get the value. `NULL` arguments are skipped.
+
+use super::expr::{arithmetic, to_number, to_real};
+use super::statement::{AggregateFunction, BinaryOperator, Expr, Literal};
+use super::ExecuteResult;
+use crate::table::Value;
+
+/// Enum to hold the state of an aggregate function while the rows of a group are read
+pub enum Accumulator {
+    Count(i64),
+    Sum {
+        /// `NULL` until the first value is added
+        sum: Value,
+        /// Adding integers went past the range of 64 bits
+        overflow: bool,
+    },
+    Avg {
+        sum: f64,
+        count: i64,
+    },
+    Min(Value),
+    Max(Value),
+    GroupConcat(Option<String>),
+}
+
+impl Accumulator {
+    /// Returns the state of the function before any row is read
+    ///
+    /// # Arguments
+    ///
+    /// * `function` - The aggregate function to compute
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rsqlite::table::Value;
+    /// use rsqlite::vm::aggregate::Accumulator;
+    /// use rsqlite::vm::statement::AggregateFunction;
+    /// let mut sum = Accumulator::new(AggregateFunction::Sum);
+    /// sum.step(&[Value::Integer(2)]);
+    /// sum.step(&[Value::Null]);
+    /// sum.step(&[Value::Real(0.5)]);
+    /// assert_eq!(sum.finish().ok(), Some(Value::Real(2.5)));
+    ///
+    /// let mut sum = Accumulator::new(AggregateFunction::Sum);
+    /// sum.step(&[Value::Integer(i64::MAX)]);
+    /// sum.step(&[Value::Integer(1)]);
+    /// assert!(sum.finish().is_err());
+    /// ```
+    pub fn new(function: AggregateFunction) -> Self {
+        match function {
+            AggregateFunction::Count => Accumulator::Count(0),
+            AggregateFunction::Sum => Accumulator::Sum {
+                sum: Value::Null,
+                overflow: false,
+            },
+            AggregateFunction::Avg => Accumulator::Avg { sum: 0.0, count: 0 },
+            AggregateFunction::Min => Accumulator::Min(Value::Null),
+            AggregateFunction::Max => Accumulator::Max(Value::Null),
+            AggregateFunction::GroupConcat => Accumulator::GroupConcat(None),
+        }
+    }
+
+    /// Adds a row to the group
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The arguments of the function evaluated for the row,
+    ///   none for `count(*)`
+    pub fn step(&mut self, args: &[Value]) {
+        let value = match args.first() {
+            Some(Value::Null) => return,
+            Some(value) => value.clone(),
+            // `count(*)` counts every row
+            None => Value::Null,
+        };
+
+        match self {
+            Accumulator::Count(count) => *count += 1,
+            Accumulator::Sum { sum, overflow } => {
+                let value = to_number(value);
+                *sum = match (&sum, value) {
+                    (Value::Null, value) => value,
+                    // Like SQLite, integers are never summed into a real
+                    (Value::Integer(a), Value::Integer(b)) => match a.checked_add(b) {
+                        Some(sum) => Value::Integer(sum),
+                        None => {
+                            *overflow = true;
+                            return;
+                        }
+                    },
+                    (_, value) => arithmetic(BinaryOperator::Add, sum.clone(), value),
+                };
+            }
+            Accumulator::Avg { sum, count } => {
+                *sum += to_real(&to_number(value));
+                *count += 1;
+            }
+            Accumulator::Min(min) => {
+                if *min == Value::Null || value.compare(min) == std::cmp::Ordering::Less {
+                    *min = value;
+                }
+            }
+            Accumulator::Max(max) => {
+                if *max == Value::Null || value.compare(max) == std::cmp::Ordering::Greater {
+                    *max = value;
+                }
+            }
+            Accumulator::GroupConcat(text) => {
+                let separator = match args.get(1) {
+                    Some(separator) => separator.to_string(),
+                    None => ",".to_string(),
+                };
+                match text {
+                    Some(text) => {
+                        text.push_str(&separator);
+                        text.push_str(&value.to_string());
+                    }
+                    None => *text = Some(value.to_string()),
+                }
+            }
+        }
+    }
+
+    /// Returns the value of the function over all the rows added,
+    /// or `IntegerOverflow` if the sum of integers is too large for 64 bits
+    pub fn finish(self) -> Result<Value, ExecuteResult> {
+        Ok(match self {
+            Accumulator::Count(count) => Value::Integer(count),
+            Accumulator::Sum { overflow: true, .. } => return Err(ExecuteResult::IntegerOverflow),
+            Accumulator::Sum { sum, .. } => sum,
+            Accumulator::Avg { count: 0, .. } => Value::Null,
+            Accumulator::Avg { sum, count } => Value::Real(sum / count as f64),
+            Accumulator::Min(value) | Accumulator::Max(value) => value,
+            Accumulator::GroupConcat(text) => text.map_or(Value::Null, Value::Text),
+        })
+    }
+}
+
+/// Returns the expression with every call of an aggregate function
+/// replaced by the value computed for it
+///
+/// # Arguments
+///
+/// * `expr` - The expression to rewrite
+/// * `aggregates` - Pairs of a call of an aggregate function and its value
+pub fn replace_aggregates(expr: &Expr, aggregates: &[(&Expr, Value)]) -> Expr {
+    let replace = |expr: &Expr| Box::new(replace_aggregates(expr, aggregates));
+    match expr {
+        Expr::Aggregate { .. } => {
+            let value = aggregates
+                .iter()
+                .find(|(aggregate, _)| *aggregate == expr)
+                .map_or(Value::Null, |(_, value)| value.clone());
+            Expr::Literal(match value {
+                Value::Null => Literal::Null,
+                Value::Integer(value) => Literal::Integer(value),
+                Value::Real(value) => Literal::Real(value),
+                Value::Text(value) => Literal::String(value),
+                Value::Blob(value) => Literal::Blob(value),
+            })
+        }
+        Expr::Unary { op, operand } => Expr::Unary {
+            op: *op,
+            operand: replace(operand),
+        },
+        Expr::Binary { left, op, right } => Expr::Binary {
+            left: replace(left),
+            op: *op,
+            right: replace(right),
+        },
+        Expr::IsNull { operand, negated } => Expr::IsNull {
+            operand: replace(operand),
+            negated: *negated,
+        },
+        Expr::Literal(_) | Expr::Column(_) => expr.clone(),
+    }
+}
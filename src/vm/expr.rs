@@ -0,0 +1,193 @@
+//! # Expr
+//!
+//! Evaluation of expressions, following the three-valued logic of SQL:
+//! a comparison involving `NULL` is neither true nor false but `NULL`
+
+use super::statement::{BinaryOperator, Expr, Literal, UnaryOperator};
+use crate::table::Value;
+use std::cmp::Ordering;
+
+/// Evaluates the expression, looking up the values of columns with the given function
+///
+/// # Arguments
+///
+/// * `expr` - The expression to evaluate
+/// * `column` - Returns the value of the named column in the current row
+///
+/// # Example
+///
+/// ```
+/// use rsqlite::table::Value;
+/// use rsqlite::vm::expr::evaluate;
+/// use rsqlite::vm::statement::{Expr, Literal};
+/// let expr = Expr::Literal(Literal::Integer(7));
+/// assert_eq!(evaluate(&expr, &|_| Value::Null), Value::Integer(7));
+/// ```
+pub fn evaluate(expr: &Expr, column: &dyn Fn(&str) -> Value) -> Value {
+    match expr {
+        Expr::Literal(literal) => match literal {
+            Literal::Null => Value::Null,
+            Literal::Integer(value) => Value::Integer(*value),
+            Literal::Real(value) => Value::Real(*value),
+            Literal::String(value) => Value::Text(value.clone()),
+            Literal::Blob(value) => Value::Blob(value.clone()),
+        },
+        Expr::Column(name) => column(name),
+        Expr::Unary { op, operand } => {
+            let value = evaluate(operand, column);
+            match op {
+                UnaryOperator::Negate => negate(value),
+                UnaryOperator::Not => match is_true(&value) {
+                    Some(truth) => boolean(!truth),
+                    None => Value::Null,
+                },
+            }
+        }
+        Expr::Binary { left, op, right } => {
+            let left = evaluate(left, column);
+            let right = evaluate(right, column);
+            match op {
+                BinaryOperator::And => match (is_true(&left), is_true(&right)) {
+                    (Some(false), _) | (_, Some(false)) => boolean(false),
+                    (Some(true), Some(true)) => boolean(true),
+                    _ => Value::Null,
+                },
+                BinaryOperator::Or => match (is_true(&left), is_true(&right)) {
+                    (Some(true), _) | (_, Some(true)) => boolean(true),
+                    (Some(false), Some(false)) => boolean(false),
+                    _ => Value::Null,
+                },
+                _ if left == Value::Null || right == Value::Null => Value::Null,
+                BinaryOperator::Concat => Value::Text(to_text(left) + &to_text(right)),
+                BinaryOperator::Add
+                | BinaryOperator::Subtract
+                | BinaryOperator::Multiply
+                | BinaryOperator::Divide
+                | BinaryOperator::Modulo => arithmetic(*op, to_number(left), to_number(right)),
+                op => {
+                    let ordering = left.compare(&right);
+                    boolean(match op {
+                        BinaryOperator::Equals => ordering == Ordering::Equal,
+                        BinaryOperator::NotEquals => ordering != Ordering::Equal,
+                        BinaryOperator::Less => ordering == Ordering::Less,
+                        BinaryOperator::LessEquals => ordering != Ordering::Greater,
+                        BinaryOperator::Greater => ordering == Ordering::Greater,
+                        BinaryOperator::GreaterEquals => ordering != Ordering::Less,
+                        _ => unreachable!("not a comparison"),
+                    })
+                }
+            }
+        }
+        Expr::IsNull { operand, negated } => {
+            let is_null = evaluate(operand, column) == Value::Null;
+            boolean(is_null != *negated)
+        }
+        // Aggregates are computed over a whole group and replaced by their
+        // values before the expression is evaluated, see `aggregate`
+        Expr::Aggregate { .. } => Value::Null,
+    }
+}
+
+/// Returns the truth of a value used as a condition, `None` for `NULL`.
+/// Numbers are true when they are not zero, text and blobs are converted to a number first.
+///
+/// # Arguments
+///
+/// * `value` - The value to test
+pub fn is_true(value: &Value) -> Option<bool> {
+    match value {
+        Value::Null => None,
+        Value::Integer(value) => Some(*value != 0),
+        Value::Real(value) => Some(*value != 0.0),
+        Value::Text(value) => is_true(&text_to_number(value)),
+        Value::Blob(value) => is_true(&text_to_number(&String::from_utf8_lossy(value))),
+    }
+}
+
+/// Returns the integer 1 for true and 0 for false
+fn boolean(truth: bool) -> Value {
+    Value::Integer(truth as i64)
+}
+
+/// Applies an arithmetic operator to two numbers. Integer results which
+/// overflow become reals, division by zero results in `NULL`.
+pub fn arithmetic(op: BinaryOperator, left: Value, right: Value) -> Value {
+    match (left, right) {
+        (Value::Integer(a), Value::Integer(b)) => {
+            let result = match op {
+                BinaryOperator::Add => a.checked_add(b),
+                BinaryOperator::Subtract => a.checked_sub(b),
+                BinaryOperator::Multiply => a.checked_mul(b),
+                BinaryOperator::Divide | BinaryOperator::Modulo if b == 0 => return Value::Null,
+                BinaryOperator::Divide => a.checked_div(b),
+                BinaryOperator::Modulo => Some(a.checked_rem(b).unwrap_or(0)),
+                _ => unreachable!("not an arithmetic operator"),
+            };
+            match result {
+                Some(result) => Value::Integer(result),
+                None => arithmetic(op, Value::Real(a as f64), Value::Real(b as f64)),
+            }
+        }
+        (left, right) => {
+            let (a, b) = (to_real(&left), to_real(&right));
+            match op {
+                BinaryOperator::Add => Value::Real(a + b),
+                BinaryOperator::Subtract => Value::Real(a - b),
+                BinaryOperator::Multiply => Value::Real(a * b),
+                BinaryOperator::Divide if b == 0.0 => Value::Null,
+                BinaryOperator::Divide => Value::Real(a / b),
+                // Like SQLite, the remainder is taken of the integer parts
+                BinaryOperator::Modulo => match (a as i64, b as i64) {
+                    (_, 0) => Value::Null,
+                    (a, b) => Value::Real(a.checked_rem(b).unwrap_or(0) as f64),
+                },
+                _ => unreachable!("not an arithmetic operator"),
+            }
+        }
+    }
+}
+
+/// Converts text and blobs to a number, other values are returned unchanged
+pub fn to_number(value: Value) -> Value {
+    match value {
+        Value::Text(text) => text_to_number(&text),
+        Value::Blob(bytes) => text_to_number(&String::from_utf8_lossy(&bytes)),
+        value => value,
+    }
+}
+
+/// Converts a value to text, the bytes of a blob are read as UTF-8
+pub fn to_text(value: Value) -> String {
+    match value {
+        Value::Text(text) => text,
+        Value::Blob(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+        value => value.to_string(),
+    }
+}
+
+/// Returns the numeric value of a number as a real
+pub fn to_real(value: &Value) -> f64 {
+    match value {
+        Value::Integer(value) => *value as f64,
+        Value::Real(value) => *value,
+        _ => 0.0,
+    }
+}
+
+/// Returns the number the text spells, or 0 if it is not a number
+fn text_to_number(text: &str) -> Value {
+    Value::parse_number(text).unwrap_or(Value::Integer(0))
+}
+
+/// Returns the value with its sign flipped
+fn negate(value: Value) -> Value {
+    match value {
+        Value::Null => Value::Null,
+        Value::Integer(value) => match value.checked_neg() {
+            Some(negated) => Value::Integer(negated),
+            None => Value::Real(-(value as f64)),
+        },
+        Value::Real(value) => Value::Real(-value),
+        value => negate(to_number(value)),
+    }
+}
@@ -0,0 +1,470 @@
+//! # Lexer
+//!
+//! Splits the text of a SQL statement into tokens,
+//! remembering where in the text each token starts
+
+use std::fmt;
+
+/// Enum to indicate a reserved word of the SQL dialect
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Keyword {
+    And,
+    As,
+    Asc,
+    Begin,
+    By,
+    Commit,
+    Create,
+    Delete,
+    Desc,
+    Drop,
+    From,
+    Group,
+    Having,
+    Index,
+    Insert,
+    Into,
+    Is,
+    Key,
+    Limit,
+    Not,
+    Null,
+    Offset,
+    On,
+    Or,
+    Order,
+    Pragma,
+    Primary,
+    Rollback,
+    Select,
+    Set,
+    Table,
+    Transaction,
+    Unique,
+    Update,
+    Values,
+    Where,
+}
+
+impl Keyword {
+    /// Returns the keyword spelled by the word, ignoring case
+    ///
+    /// # Arguments
+    ///
+    /// * `word` - A word of the statement
+    pub fn from_word(word: &str) -> Option<Self> {
+        let keyword = match word.to_ascii_lowercase().as_str() {
+            "and" => Keyword::And,
+            "as" => Keyword::As,
+            "asc" => Keyword::Asc,
+            "begin" => Keyword::Begin,
+            "by" => Keyword::By,
+            "commit" => Keyword::Commit,
+            "create" => Keyword::Create,
+            "delete" => Keyword::Delete,
+            "desc" => Keyword::Desc,
+            "drop" => Keyword::Drop,
+            "from" => Keyword::From,
+            "group" => Keyword::Group,
+            "having" => Keyword::Having,
+            "index" => Keyword::Index,
+            "insert" => Keyword::Insert,
+            "into" => Keyword::Into,
+            "is" => Keyword::Is,
+            "key" => Keyword::Key,
+            "limit" => Keyword::Limit,
+            "not" => Keyword::Not,
+            "null" => Keyword::Null,
+            "offset" => Keyword::Offset,
+            "on" => Keyword::On,
+            "or" => Keyword::Or,
+            "order" => Keyword::Order,
+            "pragma" => Keyword::Pragma,
+            "primary" => Keyword::Primary,
+            "rollback" => Keyword::Rollback,
+            "select" => Keyword::Select,
+            "set" => Keyword::Set,
+            "table" => Keyword::Table,
+            "transaction" => Keyword::Transaction,
+            "unique" => Keyword::Unique,
+            "update" => Keyword::Update,
+            "values" => Keyword::Values,
+            "where" => Keyword::Where,
+            _ => return None,
+        };
+        Some(keyword)
+    }
+}
+
+impl fmt::Display for Keyword {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let word = format!("{:?}", self).to_ascii_lowercase();
+        write!(f, "{}", word)
+    }
+}
+
+/// Enum to indicate the kind of a token, along with its value
+#[derive(Clone, PartialEq, Debug)]
+pub enum TokenKind {
+    Keyword(Keyword),
+    /// A name of a table or column, either bare or in double quotes
+    Identifier(String),
+    /// A string literal in single quotes
+    String(String),
+    /// A blob literal, hexadecimal digits in single quotes after `x`
+    Blob(Vec<u8>),
+    Integer(i64),
+    Real(f64),
+    LeftParen,
+    RightParen,
+    Comma,
+    Semicolon,
+    Dot,
+    Star,
+    Plus,
+    Minus,
+    Slash,
+    Percent,
+    Concat,
+    Equals,
+    NotEquals,
+    Less,
+    LessEquals,
+    Greater,
+    GreaterEquals,
+    /// Marks the end of the statement text
+    Eof,
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenKind::Keyword(keyword) => write!(f, "'{}'", keyword),
+            TokenKind::Identifier(name) => write!(f, "'{}'", name),
+            TokenKind::String(value) => write!(f, "'{}'", value.replace('\'', "''")),
+            TokenKind::Blob(value) => {
+                write!(f, "x'")?;
+                value
+                    .iter()
+                    .try_for_each(|byte| write!(f, "{:02x}", byte))?;
+                write!(f, "'")
+            }
+            TokenKind::Integer(value) => write!(f, "'{}'", value),
+            TokenKind::Real(value) => write!(f, "'{}'", value),
+            TokenKind::LeftParen => write!(f, "'('"),
+            TokenKind::RightParen => write!(f, "')'"),
+            TokenKind::Comma => write!(f, "','"),
+            TokenKind::Semicolon => write!(f, "';'"),
+            TokenKind::Dot => write!(f, "'.'"),
+            TokenKind::Star => write!(f, "'*'"),
+            TokenKind::Plus => write!(f, "'+'"),
+            TokenKind::Minus => write!(f, "'-'"),
+            TokenKind::Slash => write!(f, "'/'"),
+            TokenKind::Percent => write!(f, "'%'"),
+            TokenKind::Concat => write!(f, "'||'"),
+            TokenKind::Equals => write!(f, "'='"),
+            TokenKind::NotEquals => write!(f, "'!='"),
+            TokenKind::Less => write!(f, "'<'"),
+            TokenKind::LessEquals => write!(f, "'<='"),
+            TokenKind::Greater => write!(f, "'>'"),
+            TokenKind::GreaterEquals => write!(f, "'>='"),
+            TokenKind::Eof => write!(f, "end of input"),
+        }
+    }
+}
+
+/// A struct holding a token and the position in the text where it starts
+#[derive(Clone, Debug)]
+pub struct Token {
+    pub kind: TokenKind,
+    /// Line of the first character, starting at 1
+    pub line: usize,
+    /// Column of the first character, starting at 1
+    pub column: usize,
+    /// Byte offset of the first character in the text
+    pub start: usize,
+    /// Byte offset one past the last character in the text
+    pub end: usize,
+}
+
+/// A struct describing why a statement could not be parsed
+#[derive(Clone, PartialEq, Debug)]
+pub struct SyntaxError {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Syntax error at line {}, column {}: {}.",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+/// A struct walking through the characters of a statement
+pub struct Lexer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    column: usize,
+    offset: usize,
+}
+
+impl<'a> Lexer<'a> {
+    /// Returns a lexer positioned at the start of the text
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text of the statement
+    pub fn new(text: &'a str) -> Self {
+        Lexer {
+            chars: text.chars().peekable(),
+            line: 1,
+            column: 1,
+            offset: 0,
+        }
+    }
+
+    /// Splits the whole text into tokens, the last token is always `Eof`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rsqlite::vm::lexer::{Lexer, TokenKind};
+    /// let tokens = Lexer::new("select 1").tokenize().unwrap();
+    /// assert_eq!(tokens[1].kind, TokenKind::Integer(1));
+    /// ```
+    pub fn tokenize(mut self) -> Result<Vec<Token>, SyntaxError> {
+        let mut tokens = Vec::new();
+        loop {
+            let token = self.next_token()?;
+            let is_eof = token.kind == TokenKind::Eof;
+            tokens.push(token);
+            if is_eof {
+                return Ok(tokens);
+            }
+        }
+    }
+
+    /// Consumes the next character, keeping track of the position
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        self.offset += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    /// Consumes the next character if it is the expected one
+    fn bump_if(&mut self, expected: char) -> bool {
+        if self.chars.peek() == Some(&expected) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Skips whitespace, `-- line` comments and `/* block */` comments
+    fn skip_whitespace_and_comments(&mut self) -> Result<(), SyntaxError> {
+        loop {
+            match self.chars.peek().copied() {
+                Some(c) if c.is_whitespace() => {
+                    self.bump();
+                }
+                Some('-') if self.chars.clone().nth(1) == Some('-') => {
+                    while !matches!(self.chars.peek(), None | Some('\n')) {
+                        self.bump();
+                    }
+                }
+                Some('/') if self.chars.clone().nth(1) == Some('*') => {
+                    let (line, column) = (self.line, self.column);
+                    self.bump();
+                    self.bump();
+                    loop {
+                        match self.bump() {
+                            Some('*') if self.bump_if('/') => break,
+                            Some(_) => (),
+                            None => return Err(error("unterminated comment", line, column)),
+                        }
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Reads the next token from the text
+    fn next_token(&mut self) -> Result<Token, SyntaxError> {
+        self.skip_whitespace_and_comments()?;
+        let (line, column, start) = (self.line, self.column, self.offset);
+
+        let kind = match self.bump() {
+            Some(c) => self.token_kind(c, line, column)?,
+            None => TokenKind::Eof,
+        };
+
+        Ok(Token {
+            kind,
+            line,
+            column,
+            start,
+            end: self.offset,
+        })
+    }
+
+    /// Reads the rest of the token starting with the given character
+    fn token_kind(
+        &mut self,
+        c: char,
+        line: usize,
+        column: usize,
+    ) -> Result<TokenKind, SyntaxError> {
+        match c {
+            '(' => Ok(TokenKind::LeftParen),
+            ')' => Ok(TokenKind::RightParen),
+            ',' => Ok(TokenKind::Comma),
+            ';' => Ok(TokenKind::Semicolon),
+            '.' if !matches!(self.chars.peek(), Some(c) if c.is_ascii_digit()) => {
+                Ok(TokenKind::Dot)
+            }
+            '*' => Ok(TokenKind::Star),
+            '+' => Ok(TokenKind::Plus),
+            '-' => Ok(TokenKind::Minus),
+            '/' => Ok(TokenKind::Slash),
+            '%' => Ok(TokenKind::Percent),
+            '|' if self.bump_if('|') => Ok(TokenKind::Concat),
+            '=' => {
+                self.bump_if('=');
+                Ok(TokenKind::Equals)
+            }
+            '!' if self.bump_if('=') => Ok(TokenKind::NotEquals),
+            '<' if self.bump_if('=') => Ok(TokenKind::LessEquals),
+            '<' if self.bump_if('>') => Ok(TokenKind::NotEquals),
+            '<' => Ok(TokenKind::Less),
+            '>' if self.bump_if('=') => Ok(TokenKind::GreaterEquals),
+            '>' => Ok(TokenKind::Greater),
+            '\'' => Ok(TokenKind::String(self.quoted('\'', line, column)?)),
+            '"' => Ok(TokenKind::Identifier(self.quoted('"', line, column)?)),
+            'x' | 'X' if self.chars.peek() == Some(&'\'') => {
+                self.bump();
+                self.blob(line, column)
+            }
+            c if c.is_ascii_digit() || c == '.' => Ok(self.number(c, line, column)?),
+            c if c.is_alphabetic() || c == '_' => {
+                let mut word = String::from(c);
+                while let Some(&c) = self.chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    word.push(c);
+                    self.bump();
+                }
+                match Keyword::from_word(&word) {
+                    Some(keyword) => Ok(TokenKind::Keyword(keyword)),
+                    None => Ok(TokenKind::Identifier(word)),
+                }
+            }
+            c => Err(error(
+                &format!("unexpected character '{}'", c),
+                line,
+                column,
+            )),
+        }
+    }
+
+    /// Reads the rest of a quoted string or identifier, a doubled quote
+    /// character stands for the quote character itself
+    fn quoted(&mut self, quote: char, line: usize, column: usize) -> Result<String, SyntaxError> {
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                Some(c) if c == quote => {
+                    if !self.bump_if(quote) {
+                        return Ok(value);
+                    }
+                    value.push(quote);
+                }
+                Some(c) => value.push(c),
+                None => return Err(error("unterminated string", line, column)),
+            }
+        }
+    }
+
+    /// Reads the rest of a blob literal, two hexadecimal digits per byte
+    fn blob(&mut self, line: usize, column: usize) -> Result<TokenKind, SyntaxError> {
+        let digits = self.quoted('\'', line, column)?;
+        let malformed = || error(&format!("malformed blob x'{}'", digits), line, column);
+        if digits.len() % 2 != 0 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(malformed());
+        }
+        (0..digits.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).map_err(|_| malformed()))
+            .collect::<Result<Vec<u8>, SyntaxError>>()
+            .map(TokenKind::Blob)
+    }
+
+    /// Reads the rest of an integer or real number
+    fn number(
+        &mut self,
+        first: char,
+        line: usize,
+        column: usize,
+    ) -> Result<TokenKind, SyntaxError> {
+        let mut text = String::from(first);
+        let mut is_real = first == '.';
+
+        while let Some(&c) = self.chars.peek() {
+            if c.is_ascii_digit() || (c == '.' && !is_real) {
+                is_real |= c == '.';
+                text.push(c);
+                self.bump();
+            } else if c == 'e' || c == 'E' {
+                is_real = true;
+                text.push(c);
+                self.bump();
+                if let Some(&sign) = self.chars.peek() {
+                    if sign == '+' || sign == '-' {
+                        text.push(sign);
+                        self.bump();
+                    }
+                }
+            } else {
+                break;
+            }
+        }
+
+        if matches!(self.chars.peek(), Some(c) if c.is_alphanumeric() || *c == '_') {
+            return Err(error(&format!("malformed number '{}'", text), line, column));
+        }
+
+        // Like SQLite, an integer too large for 64 bits is read as a real,
+        // the parser reads `-9223372036854775808` as the smallest integer
+        let kind = if is_real {
+            text.parse().ok().map(TokenKind::Real)
+        } else {
+            text.parse()
+                .map(TokenKind::Integer)
+                .or_else(|_| text.parse().map(TokenKind::Real))
+                .ok()
+        };
+        kind.ok_or_else(|| error(&format!("malformed number '{}'", text), line, column))
+    }
+}
+
+/// Returns a `SyntaxError` at the given position
+fn error(message: &str, line: usize, column: usize) -> SyntaxError {
+    SyntaxError {
+        message: message.to_string(),
+        line,
+        column,
+    }
+}
@@ -3,32 +3,128 @@
 //! A very basic "vm" for SQL
 
 use crate::buffer::InputBuffer;
-use crate::constants::{EMAIL_SIZE, TABLE_MAX_ROWS, USERNAME_SIZE};
-use crate::table::{Row, Table};
-use std::str::FromStr;
+use crate::constants::ROW_KEY_SIZE;
+use crate::database::Database;
+use crate::table::catalog::{catalog_schema, CATALOG_TABLE_NAME};
+use crate::table::cursor::Cursor;
+use crate::table::header::JournalMode;
+use crate::table::index::{encode_value, Index};
+use crate::table::node::{self, max_key_size};
+use crate::table::pager::{is_valid_page_size, Pager};
+use crate::table::schema::{Column, ColumnType, Schema};
+use crate::table::{read_row_key, row_key, Row, Table, Value};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::convert::TryFrom;
 
+pub mod aggregate;
+pub mod expr;
+pub mod lexer;
+pub mod parser;
+pub mod sorter;
 pub mod statement;
 
-use statement::{Statement, StatementType};
+use aggregate::{replace_aggregates, Accumulator};
+use expr::{evaluate, is_true};
+use lexer::SyntaxError;
+use sorter::{compare_keys, SortRecord, Sorter};
+use statement::{
+    BinaryOperator, CreateIndex, Delete, Expr, Insert, OrderingTerm, Pragma, ResultColumn, Select,
+    Statement, Update,
+};
+
+/// Struct that holds the rows returned by a select, along with the column names
+pub struct ResultSet {
+    pub columns: Vec<String>,
+    pub rows: Vec<Row>,
+}
+
+impl ResultSet {
+    /// Prints the rows, preceded by the column names if `headers` is set
+    ///
+    /// # Arguments
+    ///
+    /// * `headers` - Whether to print a line with the column names first
+    pub fn print(&self, headers: bool) {
+        if headers {
+            println!("{}", self.columns.join(", "));
+        }
+        for row in &self.rows {
+            row.print_row();
+        }
+    }
+}
+
+/// Struct that holds the settings of the REPL, changed by meta commands
+#[derive(Default)]
+pub struct Settings {
+    /// Print the column names before the rows of a select, set with `.headers on|off`
+    pub headers: bool,
+}
 
 /// Enum to show the result of executing a statement
 pub enum ExecuteResult {
     Success,
-    TableFull,
+    Rows(ResultSet),
+    /// The number of rows changed by the statement
+    RowsAffected(usize),
+    DuplicateKey,
+    /// Two rows would have the same values in the columns of the named unique index
+    UniqueConstraint(String),
+    TableExists,
+    IndexExists,
+    StringTooLong,
+    TypeMismatch,
+    /// The page size can only be changed before any table is created
+    DatabaseNotEmpty,
+    /// The page size can not be changed in WAL mode
+    PageSizeInWalMode,
+    /// `begin` while a transaction is active
+    TransactionActive,
+    /// `commit` or `rollback` while no transaction is active
+    NoTransaction,
+    /// The journal mode can not be changed while a transaction is active
+    JournalModeInTransaction,
+    /// The sum of integers computed by `sum` is too large for 64 bits
+    IntegerOverflow,
+}
+
+/// Enum to show whether statements are committed one by one
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Transaction {
+    /// Every statement is committed once it succeeds, or rolled back if it fails
+    Autocommit,
+    /// Statements are committed together by `commit`, or undone by `rollback`
+    Active,
 }
 
 /// Enum to show the result of meta commands
 pub enum MetaCommandResult {
+    Success,
     UnrecognizedCommand,
 }
 
 /// Enum to show the result of processing/preparing an SQL statement
 pub enum PrepareResult {
     Success,
-    UnrecognizedStatement,
-    SyntaxError,
+    SyntaxError(SyntaxError),
     StringTooLong,
-    NegativeID,
+    NoSuchTable,
+    NoSuchIndex,
+    ReadOnlyTable,
+    /// The keys of an index are too large for its B+tree
+    KeyTooLarge,
+    ValueCountMismatch,
+    NoSuchColumn(String),
+    /// An aggregate function used where a single row is evaluated
+    MisusedAggregate(String),
+    UnknownPragma(String),
+    /// A page size which is not a power of two between 512 and 65536
+    InvalidPageSize,
+    /// A cache size which is not a positive number of pages
+    InvalidCacheSize,
+    /// A journal mode other than `delete` and `wal`
+    InvalidJournalMode,
 }
 
 /// Helper function to run a meta command
@@ -36,126 +132,1491 @@ pub enum PrepareResult {
 /// # Arguments
 ///
 /// * `input_buffer` - Buffer storing the user input from stdin
-/// * `table` - A `Table` struct holding current data
-pub fn do_meta_command(input_buffer: &InputBuffer, table: &mut Table) -> MetaCommandResult {
+/// * `database` - A `Database` struct holding current data
+/// * `settings` - The settings of the REPL
+pub fn do_meta_command(
+    input_buffer: &InputBuffer,
+    database: &mut Database,
+    settings: &mut Settings,
+) -> MetaCommandResult {
     if input_buffer.buffer == ".exit" {
-        table.db_close();
+        database.close();
         std::process::exit(0);
+    } else if input_buffer.buffer == ".btree" {
+        // Print the tree of every user table, in the order they were created
+        let mut root_page_nums: Vec<u32> = database
+            .tables
+            .values()
+            .map(|table| table.root_page_num)
+            .collect();
+        root_page_nums.sort_unstable();
+
+        println!("Tree:");
+        for root_page_num in root_page_nums {
+            database.pager.print_tree(root_page_num, 0);
+        }
+        MetaCommandResult::Success
+    } else if input_buffer.buffer == ".headers on" {
+        settings.headers = true;
+        MetaCommandResult::Success
+    } else if input_buffer.buffer == ".headers off" {
+        settings.headers = false;
+        MetaCommandResult::Success
     } else {
         MetaCommandResult::UnrecognizedCommand
     }
 }
 
-/// Helper function to transform data for insertion into the database
+/// Helper function to check the definition of a new table
+///
+/// # Arguments
+///
+/// * `schema` - The schema of the table to be created
+fn prepare_create(schema: &Schema) -> PrepareResult {
+    // The name and the statement itself must fit into their catalog columns
+    let catalog = catalog_schema();
+    if catalog.columns[1].col_type.is_too_long(schema.name.len())
+        || catalog.columns[3].col_type.is_too_long(schema.sql.len())
+    {
+        return PrepareResult::StringTooLong;
+    }
+
+    PrepareResult::Success
+}
+
+/// Helper function to check the definition of a new index
 ///
 /// # Arguments
 ///
-/// * `args` - Data corresponding to the fields in a row of the table
-/// * `statement` - A `Statement` struct holding the type of statement and data to be inserted
-/// in the case of an insert statement
-fn prepare_insert(args: &[&str], statement: &mut Statement) -> PrepareResult {
-    statement.row_to_insert.id = match FromStr::from_str(args[1]) {
-        Ok(uint) => uint,
-        Err(_) => return PrepareResult::NegativeID,
+/// * `create` - The create index statement
+/// * `database` - A `Database` struct holding the tables the statement may refer to
+fn prepare_create_index(create: &CreateIndex, database: &Database) -> PrepareResult {
+    if create.table_name.eq_ignore_ascii_case(CATALOG_TABLE_NAME) {
+        return PrepareResult::ReadOnlyTable;
+    }
+    let table = match database.table(&create.table_name) {
+        Some(table) => table,
+        None => return PrepareResult::NoSuchTable,
     };
 
-    let ubytes = args[2].as_bytes();
-    let ulen = ubytes.len();
-    if ulen > USERNAME_SIZE {
-        return PrepareResult::StringTooLong;
+    for name in &create.columns {
+        if table.schema.column_index(name).is_none() {
+            return PrepareResult::NoSuchColumn(name.clone());
+        }
+    }
+    // An internal node must hold a few keys to be split
+    let index = Index::new(create, &table.schema, 0).unwrap();
+    if index.key_size(&table.schema) > max_key_size(database.pager.page_size) {
+        return PrepareResult::KeyTooLarge;
     }
-    let mut username_bytes = [0u8; USERNAME_SIZE];
-    username_bytes[0..ulen].copy_from_slice(args[2].as_bytes());
-    statement.row_to_insert.username = username_bytes;
 
-    let ebytes = args[3].as_bytes();
-    let elen = ebytes.len();
-    if elen > EMAIL_SIZE {
+    let catalog = catalog_schema();
+    if catalog.columns[1].col_type.is_too_long(create.name.len())
+        || catalog.columns[3].col_type.is_too_long(create.sql.len())
+    {
         return PrepareResult::StringTooLong;
     }
-    let mut email_bytes = [0u8; EMAIL_SIZE];
-    email_bytes[0..elen].copy_from_slice(args[3].as_bytes());
-    statement.row_to_insert.email = email_bytes;
 
     PrepareResult::Success
 }
 
+/// Helper function to check that an insert statement refers to
+/// a table which exists and supplies a value for every column
+///
+/// # Arguments
+///
+/// * `insert` - The insert statement
+/// * `database` - A `Database` struct holding the tables the statement may refer to
+fn prepare_insert(insert: &Insert, database: &Database) -> PrepareResult {
+    if insert.table_name.eq_ignore_ascii_case(CATALOG_TABLE_NAME) {
+        return PrepareResult::ReadOnlyTable;
+    }
+    let table = match database.table(&insert.table_name) {
+        Some(table) => table,
+        None => return PrepareResult::NoSuchTable,
+    };
+
+    let num_columns = table.schema.columns.len();
+    if insert.rows.iter().any(|row| row.len() != num_columns) {
+        return PrepareResult::ValueCountMismatch;
+    }
+
+    let result = prepare_no_aggregates(insert.rows.iter().flatten());
+    if !matches!(result, PrepareResult::Success) {
+        return result;
+    }
+
+    // Values are computed before the row exists, so they cannot refer to columns
+    let mut column = None;
+    for expr in insert.rows.iter().flatten() {
+        expr.visit_columns(&mut |name| {
+            column.get_or_insert_with(|| name.to_string());
+        });
+    }
+    match column {
+        Some(name) => PrepareResult::NoSuchColumn(name),
+        None => PrepareResult::Success,
+    }
+}
+
+/// Helper function to check that every column the expression refers to exists,
+/// and that the arguments of aggregate functions do not call aggregate functions
+///
+/// # Arguments
+///
+/// * `expr` - The expression to check
+/// * `schema` - The schema of the table the expression is evaluated against
+fn prepare_expr(expr: &Expr, schema: &Schema) -> PrepareResult {
+    let mut missing = None;
+    expr.visit_columns(&mut |name| {
+        if missing.is_none() && schema.column_index(name).is_none() {
+            missing = Some(name.to_string());
+        }
+    });
+    if let Some(name) = missing {
+        return PrepareResult::NoSuchColumn(name);
+    }
+
+    // The arguments are evaluated for every row of a group
+    let mut args = Vec::new();
+    expr.visit_aggregates(&mut |aggregate| {
+        if let Expr::Aggregate {
+            args: arguments, ..
+        } = aggregate
+        {
+            args.extend(arguments);
+        }
+    });
+    prepare_no_aggregates(args)
+}
+
+/// Helper function to check that none of the expressions calls an aggregate function,
+/// for the parts of a statement which are evaluated for a single row
+///
+/// # Arguments
+///
+/// * `exprs` - The expressions to check
+fn prepare_no_aggregates<'a>(exprs: impl IntoIterator<Item = &'a Expr>) -> PrepareResult {
+    let mut function = None;
+    for expr in exprs {
+        expr.visit_aggregates(&mut |aggregate| {
+            if let Expr::Aggregate { function: name, .. } = aggregate {
+                function.get_or_insert(*name);
+            }
+        });
+    }
+    match function {
+        Some(function) => PrepareResult::MisusedAggregate(function.to_string()),
+        None => PrepareResult::Success,
+    }
+}
+
+/// Helper function to check that a select statement refers to a table which exists
+///
+/// # Arguments
+///
+/// * `select` - The select statement
+/// * `database` - A `Database` struct holding the tables the statement may refer to
+fn prepare_select(select: &Select, database: &Database) -> PrepareResult {
+    let table = match database.table(&select.table_name) {
+        Some(table) => table,
+        None => return PrepareResult::NoSuchTable,
+    };
+
+    let exprs = select
+        .columns
+        .iter()
+        .filter_map(|column| match column {
+            ResultColumn::Star => None,
+            ResultColumn::Expr { expr, .. } => Some(expr),
+        })
+        .chain(&select.where_clause)
+        .chain(&select.group_by)
+        .chain(&select.having)
+        .chain(
+            select
+                .order_by
+                .iter()
+                .map(|term| ordering_expr(term, &select.columns)),
+        );
+    for expr in exprs {
+        let result = prepare_expr(expr, &table.schema);
+        if !matches!(result, PrepareResult::Success) {
+            return result;
+        }
+    }
+
+    let per_row = select
+        .where_clause
+        .iter()
+        .chain(&select.group_by)
+        .chain(&select.limit)
+        .chain(&select.offset);
+    let result = prepare_no_aggregates(per_row);
+    if !matches!(result, PrepareResult::Success) {
+        return result;
+    }
+
+    // The number of rows is computed before any row is read
+    let mut column = None;
+    for expr in select.limit.iter().chain(&select.offset) {
+        expr.visit_columns(&mut |name| {
+            column.get_or_insert_with(|| name.to_string());
+        });
+    }
+    match column {
+        Some(name) => PrepareResult::NoSuchColumn(name),
+        None => PrepareResult::Success,
+    }
+}
+
+/// Returns the expression an ordering term sorts by, which is the expression
+/// of the result column it names if it is an alias, or the term itself otherwise
+///
+/// # Arguments
+///
+/// * `term` - An entry of the `order by` clause
+/// * `columns` - The result columns of the select
+fn ordering_expr<'a>(term: &'a OrderingTerm, columns: &'a [ResultColumn]) -> &'a Expr {
+    if let Expr::Column(name) = &term.expr {
+        for column in columns {
+            if let ResultColumn::Expr { expr, name: alias } = column {
+                if alias.eq_ignore_ascii_case(name) {
+                    return expr;
+                }
+            }
+        }
+    }
+    &term.expr
+}
+
+/// Helper function to check that an update statement refers to
+/// a table which may be modified and to columns which exist
+///
+/// # Arguments
+///
+/// * `update` - The update statement
+/// * `database` - A `Database` struct holding the tables the statement may refer to
+fn prepare_update(update: &Update, database: &Database) -> PrepareResult {
+    if update.table_name.eq_ignore_ascii_case(CATALOG_TABLE_NAME) {
+        return PrepareResult::ReadOnlyTable;
+    }
+    let table = match database.table(&update.table_name) {
+        Some(table) => table,
+        None => return PrepareResult::NoSuchTable,
+    };
+
+    for (name, _) in &update.assignments {
+        if table.schema.column_index(name).is_none() {
+            return PrepareResult::NoSuchColumn(name.clone());
+        }
+    }
+
+    let exprs = update
+        .assignments
+        .iter()
+        .map(|(_, expr)| expr)
+        .chain(&update.where_clause);
+    for expr in exprs {
+        let result = match prepare_no_aggregates(Some(expr)) {
+            PrepareResult::Success => prepare_expr(expr, &table.schema),
+            result => result,
+        };
+        if !matches!(result, PrepareResult::Success) {
+            return result;
+        }
+    }
+    PrepareResult::Success
+}
+
+/// Helper function to check that a delete statement refers to
+/// a table which may be modified and to columns which exist
+///
+/// # Arguments
+///
+/// * `delete` - The delete statement
+/// * `database` - A `Database` struct holding the tables the statement may refer to
+fn prepare_delete(delete: &Delete, database: &Database) -> PrepareResult {
+    if delete.table_name.eq_ignore_ascii_case(CATALOG_TABLE_NAME) {
+        return PrepareResult::ReadOnlyTable;
+    }
+    let table = match database.table(&delete.table_name) {
+        Some(table) => table,
+        None => return PrepareResult::NoSuchTable,
+    };
+
+    match &delete.where_clause {
+        Some(condition) => match prepare_no_aggregates(Some(condition)) {
+            PrepareResult::Success => prepare_expr(condition, &table.schema),
+            result => result,
+        },
+        None => PrepareResult::Success,
+    }
+}
+
 /// Helper function to process/prepare a SQL statement
 ///
 /// # Arguments
 ///
 /// * `input_buffer` - Buffer storing the user input from stdin
-/// * `statement` - A `Statement` struct holding the type of statement and relevant data based on the type
-pub fn prepare_statement(input_buffer: &InputBuffer, statement: &mut Statement) -> PrepareResult {
-    if &input_buffer.buffer[0..6] == "insert" {
-        statement.stmt_type = StatementType::Insert;
-
-        let args = input_buffer.buffer.split(' ').collect::<Vec<&str>>();
-        if args.len() < 4 {
-            return PrepareResult::SyntaxError;
-        } else {
-            return prepare_insert(&args, statement);
+/// * `statement` - Filled with the parsed statement
+/// * `database` - A `Database` struct holding the tables the statement may refer to
+pub fn prepare_statement(
+    input_buffer: &InputBuffer,
+    statement: &mut Statement,
+    database: &Database,
+) -> PrepareResult {
+    *statement = match parser::parse(&input_buffer.buffer) {
+        Ok(statement) => statement,
+        Err(error) => return PrepareResult::SyntaxError(error),
+    };
+
+    match statement {
+        Statement::CreateTable(schema) => prepare_create(schema),
+        Statement::CreateIndex(create) => prepare_create_index(create, database),
+        Statement::DropIndex(name) => {
+            if database.has_index(name) {
+                PrepareResult::Success
+            } else {
+                PrepareResult::NoSuchIndex
+            }
+        }
+        Statement::Insert(insert) => prepare_insert(insert, database),
+        Statement::Select(select) => prepare_select(select, database),
+        Statement::Update(update) => prepare_update(update, database),
+        Statement::Delete(delete) => prepare_delete(delete, database),
+        Statement::Pragma(pragma) => prepare_pragma(pragma),
+        Statement::Begin | Statement::Commit | Statement::Rollback | Statement::Empty => {
+            PrepareResult::Success
+        }
+    }
+}
+
+/// Helper function to execute a SQL statement based on its type
+///
+/// # Arguments
+///
+/// * `statement` - A parsed and prepared statement
+/// * `database` - A `Database` struct holding current data
+pub fn execute_statement(statement: &Statement, database: &mut Database) -> ExecuteResult {
+    match (statement, database.transaction) {
+        (Statement::Begin, Transaction::Autocommit) => {
+            database.transaction = Transaction::Active;
+            return ExecuteResult::Success;
+        }
+        (Statement::Begin, Transaction::Active) => return ExecuteResult::TransactionActive,
+        (Statement::Commit, Transaction::Active) => {
+            database.commit();
+            database.transaction = Transaction::Autocommit;
+            return ExecuteResult::Success;
         }
+        (Statement::Rollback, Transaction::Active) => {
+            database.rollback();
+            database.transaction = Transaction::Autocommit;
+            return ExecuteResult::Success;
+        }
+        (Statement::Commit, Transaction::Autocommit)
+        | (Statement::Rollback, Transaction::Autocommit) => return ExecuteResult::NoTransaction,
+        _ => (),
     }
 
-    if &input_buffer.buffer[0..6] == "select" {
-        statement.stmt_type = StatementType::Select;
-        return PrepareResult::Success;
+    let result = execute_single_statement(statement, database);
+    if database.transaction == Transaction::Autocommit {
+        match result {
+            ExecuteResult::Success | ExecuteResult::RowsAffected(_) | ExecuteResult::Rows(_) => {
+                database.commit()
+            }
+            // A statement which failed half way leaves no changes behind
+            _ => database.rollback(),
+        }
     }
-    PrepareResult::UnrecognizedStatement
+    result
 }
 
-/// Helper function to execute a SQL statement based on its type
+/// Helper function to execute a statement which is not
+/// a `begin`, `commit` or `rollback` statement
 ///
 /// # Arguments
 ///
-/// * `statement` - A `Statement` struct holding the type of statement and relevant data based on the type
-/// * `table` - A `Table` struct holding current data
-pub fn execute_statement(statement: &Statement, table: &mut Table) -> ExecuteResult {
-    match statement.stmt_type {
-        StatementType::Insert => execute_insert(statement, table),
-        StatementType::Select => execute_select(table),
-        StatementType::Empty => {
-            println!("Empty statement");
+/// * `statement` - The prepared statement
+/// * `database` - A `Database` struct holding current data
+fn execute_single_statement(statement: &Statement, database: &mut Database) -> ExecuteResult {
+    match statement {
+        Statement::CreateTable(schema) => execute_create(schema, database),
+        Statement::CreateIndex(create) => execute_create_index(create, database),
+        Statement::DropIndex(name) => {
+            database.drop_index(name);
+            ExecuteResult::Success
+        }
+        Statement::Insert(insert) => execute_insert(insert, database),
+        Statement::Select(select) => execute_select(select, database),
+        Statement::Update(update) => execute_update(update, database),
+        Statement::Delete(delete) => execute_delete(delete, database),
+        Statement::Pragma(pragma) => execute_pragma(pragma, database),
+        Statement::Begin | Statement::Commit | Statement::Rollback | Statement::Empty => {
             ExecuteResult::Success
         }
     }
 }
 
-/// Helper function to execute a SQL insert statement
+/// Helper function to execute a SQL create table statement
 ///
 /// # Arguments
 ///
-/// * `statement` - A `Statement` struct holding the type of statement and relevant data based on the type
-/// * `table` - A `Table` struct holding current data
-fn execute_insert(statement: &Statement, table: &mut Table) -> ExecuteResult {
-    if table.num_rows >= TABLE_MAX_ROWS {
-        return ExecuteResult::TableFull;
+/// * `schema` - The schema of the table to be created
+/// * `database` - A `Database` struct holding current data
+fn execute_create(schema: &Schema, database: &mut Database) -> ExecuteResult {
+    if database.has_table(&schema.name) {
+        return ExecuteResult::TableExists;
     }
+    if database.has_index(&schema.name) {
+        return ExecuteResult::IndexExists;
+    }
+    database.create_table(schema.clone());
+    ExecuteResult::Success
+}
 
-    let row = Row {
-        id: statement.row_to_insert.id,
-        username: statement.row_to_insert.username,
-        email: statement.row_to_insert.email,
-    };
+/// Helper function to execute a SQL create index statement.
+/// The index is filled with the rows already in the table,
+/// and dropped again if they break its uniqueness.
+///
+/// # Arguments
+///
+/// * `create` - The create index statement
+/// * `database` - A `Database` struct holding current data
+fn execute_create_index(create: &CreateIndex, database: &mut Database) -> ExecuteResult {
+    if database.has_table(&create.name) {
+        return ExecuteResult::TableExists;
+    }
+    if database.has_index(&create.name) {
+        return ExecuteResult::IndexExists;
+    }
+    let table = database.table(&create.table_name).unwrap().clone();
+    let index = database.create_index(create);
+
+    let mut keys = Vec::new();
+    let mut cursor = Cursor::table_start(&mut database.pager, table.root_page_num);
+    while !cursor.end_of_table {
+        let row = Row::deserialize(&table.schema, &cursor.value());
+        cursor.advance();
+        keys.push((index.key(&table.schema, &row), index.has_null(&row)));
+    }
+    drop(cursor);
+
+    // Sorted keys of rows with the same values are next to each other,
+    // rows with the same values either both hold a `NULL` or none does.
+    // Rows sharing the values in their keys are read to compare the whole values.
+    keys.sort_unstable_by(|a, b| node::compare_keys(&a.0, &b.0));
+    let values_size = index.key_size(&table.schema) - ROW_KEY_SIZE;
+    let mut start = 0;
+    while index.unique && start < keys.len() {
+        let values = &keys[start].0[..values_size];
+        let end = start
+            + keys[start..]
+                .iter()
+                .take_while(|(key, _)| &key[..values_size] == values)
+                .count();
+        if !keys[start].1 && end - start > 1 {
+            let rows: Vec<Row> = keys[start..end]
+                .iter()
+                .map(|(key, _)| {
+                    let key = read_row_key(&key[values_size..]);
+                    read_row(&table, &mut database.pager, key)
+                })
+                .collect();
+            if has_duplicate_values(&index, &mut rows.iter().collect::<Vec<_>>()) {
+                database.drop_index(&index.name);
+                return ExecuteResult::UniqueConstraint(index.name);
+            }
+        }
+        start = end;
+    }
+
+    for (key, _) in keys {
+        Cursor::find(&mut database.pager, index.root_page_num, &key).insert(&key, &[]);
+    }
+    ExecuteResult::Success
+}
+
+/// Helper function to read the row with the given key
+///
+/// # Arguments
+///
+/// * `table` - The table holding the row
+/// * `pager` - The pager holding the pages of the table
+/// * `key` - The key of the row
+fn read_row(table: &Table, pager: &mut Pager, key: i64) -> Row {
+    let mut cursor = Cursor::find(pager, table.root_page_num, &row_key(key));
+    Row::deserialize(&table.schema, &cursor.value())
+}
+
+/// Helper function to check if two of the rows have the same values
+/// in the columns of an index. The rows are sorted by those values.
+///
+/// # Arguments
+///
+/// * `index` - The index
+/// * `rows` - The rows to check, none of which may hold `NULL` in the indexed columns
+fn has_duplicate_values(index: &Index, rows: &mut [&Row]) -> bool {
+    rows.sort_by(|a, b| index.compare_values(a, b));
+    rows.windows(2)
+        .any(|pair| index.compare_values(pair[0], pair[1]) == Ordering::Equal)
+}
+
+/// Helper function to check if a row of the table other than the ignored ones
+/// already holds the values of a row in the columns of a unique index
+///
+/// # Arguments
+///
+/// * `table` - The table the row is added to
+/// * `index` - The unique index
+/// * `row` - The row to be added
+/// * `pager` - The pager holding the pages of the table and its indexes
+/// * `ignored` - The keys of rows which are not compared with the row
+fn unique_values_taken(
+    table: &Table,
+    index: &Index,
+    row: &Row,
+    pager: &mut Pager,
+    ignored: &HashSet<i64>,
+) -> bool {
+    // The key of the index may only hold the first bytes of long values
+    let values_key = index.values_key(&table.schema, row);
+    index
+        .row_keys(pager, &values_key, None, None)
+        .into_iter()
+        .filter(|key| !ignored.contains(key))
+        .any(|key| {
+            let other = read_row(table, pager, key);
+            index.compare_values(&other, row) == Ordering::Equal
+        })
+}
+
+/// Helper function to find the name of a unique index which another row
+/// of the table, or of the rows to be added, already holds the values of
+/// a row to be added in
+///
+/// # Arguments
+///
+/// * `table` - The table the rows are added to
+/// * `rows` - The rows to be added
+/// * `pager` - The pager holding the pages of the table and its indexes
+/// * `ignored` - The keys of rows of the table which are not compared with the rows
+fn unique_conflict(
+    table: &Table,
+    rows: &[&Row],
+    pager: &mut Pager,
+    ignored: &HashSet<i64>,
+) -> Option<String> {
+    for index in table.indexes.iter().filter(|index| index.unique) {
+        let mut checked: Vec<&Row> = rows
+            .iter()
+            .copied()
+            .filter(|row| !index.has_null(row))
+            .collect();
+        if has_duplicate_values(index, &mut checked)
+            || checked
+                .iter()
+                .any(|row| unique_values_taken(table, index, row, pager, ignored))
+        {
+            return Some(index.name.clone());
+        }
+    }
+    None
+}
+
+/// Helper function to convert a value to the type of the column it is stored in,
+/// following the affinity of the column, and check that it fits in the column.
+/// The key of a row must be an integer, other columns may hold `NULL`.
+///
+/// # Arguments
+///
+/// * `value` - The value to be stored
+/// * `column` - The column the value is stored in
+/// * `is_key` - Whether the column holds the key of the row
+fn column_value(value: Value, column: &Column, is_key: bool) -> Result<Value, ExecuteResult> {
+    let value = column.col_type.apply_affinity(value);
+    match (column.col_type, &value) {
+        (_, Value::Integer(_)) if is_key => Ok(value),
+        _ if is_key => Err(ExecuteResult::TypeMismatch),
+        (_, Value::Null) => Ok(value),
+        (ColumnType::Integer, Value::Integer(_))
+        | (ColumnType::Integer, Value::Real(_))
+        | (ColumnType::Real, Value::Real(_)) => Ok(value),
+        (ColumnType::Text(_), Value::Text(text)) | (ColumnType::Blob(_), Value::Text(text))
+            if column.col_type.is_too_long(text.len()) =>
+        {
+            Err(ExecuteResult::StringTooLong)
+        }
+        (ColumnType::Text(_), Value::Blob(bytes)) | (ColumnType::Blob(_), Value::Blob(bytes))
+            if column.col_type.is_too_long(bytes.len()) =>
+        {
+            Err(ExecuteResult::StringTooLong)
+        }
+        (ColumnType::Text(_), Value::Text(_))
+        | (ColumnType::Text(_), Value::Blob(_))
+        | (ColumnType::Blob(_), _) => Ok(value),
+        _ => Err(ExecuteResult::TypeMismatch),
+    }
+}
+
+/// Helper function to convert the values of an insert statement into a row,
+/// checking each value against the type of its column
+///
+/// # Arguments
+///
+/// * `exprs` - One expression per column of the table
+/// * `schema` - The schema of the table the row is inserted into
+fn build_row(exprs: &[Expr], schema: &Schema) -> Result<Row, ExecuteResult> {
+    let mut row = Row::new();
+
+    for (i, (column, expr)) in schema.columns.iter().zip(exprs).enumerate() {
+        let value = evaluate(expr, &|_| Value::Null);
+        row.values
+            .push(column_value(value, column, i == schema.key_column)?);
+    }
 
-    let (page_num, _) = table.row_slot(table.num_rows);
-    table.serialize_row(row, page_num);
-    table.num_rows += 1;
+    Ok(row)
+}
+
+/// Helper function to execute a SQL insert statement.
+/// All the rows are checked before the first one is inserted.
+///
+/// # Arguments
+///
+/// * `insert` - The insert statement
+/// * `database` - A `Database` struct holding current data
+fn execute_insert(insert: &Insert, database: &mut Database) -> ExecuteResult {
+    let table = database.table(&insert.table_name).unwrap().clone();
+    let schema = &table.schema;
+
+    let mut rows = Vec::with_capacity(insert.rows.len());
+    for exprs in &insert.rows {
+        match build_row(exprs, schema) {
+            Ok(row) => rows.push(row),
+            Err(result) => return result,
+        }
+    }
+
+    // A row may neither take a key or unique values already in the table,
+    // nor those of another row of the statement
+    let mut keys = HashSet::new();
+    for row in &rows {
+        let key = row_key(row.key(schema));
+        let mut cursor = Cursor::find(&mut database.pager, table.root_page_num, &key);
+        let duplicate = !cursor.end_of_table && cursor.key() == key;
+        drop(cursor);
+        if duplicate || !keys.insert(key) {
+            return ExecuteResult::DuplicateKey;
+        }
+    }
+    let new_rows: Vec<&Row> = rows.iter().collect();
+    if let Some(name) = unique_conflict(&table, &new_rows, &mut database.pager, &HashSet::new()) {
+        return ExecuteResult::UniqueConstraint(name);
+    }
+
+    for row in rows {
+        let key = row_key(row.key(schema));
+        let value = row.serialize();
+        Cursor::find(&mut database.pager, table.root_page_num, &key).insert(&key, &value);
+        for index in &table.indexes {
+            index.insert(&mut database.pager, schema, &row);
+        }
+    }
 
     ExecuteResult::Success
 }
 
-/// Helper function to execute a SQL select statement
+/// Struct that holds the range of an index listing the rows which may match a condition
+struct IndexScan<'a> {
+    index: &'a Index,
+    /// Encoded values of the leading columns of the index, compared for equality
+    prefix: Vec<u8>,
+    /// Encoded bounds of the next column, compared with `>`, `>=`, `<` or `<=`
+    lower: Option<Vec<u8>>,
+    upper: Option<Vec<u8>>,
+}
+
+/// Helper function to collect the comparisons of a column with a constant value
+/// in a condition, among the conditions joined by `and`, which every matching row meets
+///
+/// # Arguments
+///
+/// * `condition` - The condition of a statement
+/// * `schema` - The schema of the table the condition is evaluated against
+/// * `comparisons` - Filled with triples of the position of a column, the operator
+///   comparing the column with the value, and the value
+fn column_comparisons(
+    condition: &Expr,
+    schema: &Schema,
+    comparisons: &mut Vec<(usize, BinaryOperator, Value)>,
+) {
+    let (left, op, right) = match condition {
+        Expr::Binary { left, op, right } => (left.as_ref(), *op, right.as_ref()),
+        _ => return,
+    };
+    if op == BinaryOperator::And {
+        column_comparisons(left, schema, comparisons);
+        column_comparisons(right, schema, comparisons);
+        return;
+    }
+
+    // The column may be on either side of the operator
+    let (name, op, value) = match (left, right) {
+        (Expr::Column(name), value) => (name, op, value),
+        (value, Expr::Column(name)) => {
+            let op = match op {
+                BinaryOperator::Less => BinaryOperator::Greater,
+                BinaryOperator::LessEquals => BinaryOperator::GreaterEquals,
+                BinaryOperator::Greater => BinaryOperator::Less,
+                BinaryOperator::GreaterEquals => BinaryOperator::LessEquals,
+                op => op,
+            };
+            (name, op, value)
+        }
+        _ => return,
+    };
+
+    let mut constant = true;
+    value.visit_columns(&mut |_| constant = false);
+    let comparison = matches!(
+        op,
+        BinaryOperator::Equals
+            | BinaryOperator::Less
+            | BinaryOperator::LessEquals
+            | BinaryOperator::Greater
+            | BinaryOperator::GreaterEquals
+    );
+    if let (true, true, Some(column)) = (constant, comparison, schema.column_index(name)) {
+        comparisons.push((column, op, evaluate(value, &|_| Value::Null)));
+    }
+}
+
+/// Helper function to pick the index which narrows down the rows matching a condition
+/// the most. The leading columns of the index must be compared for equality, the next
+/// one may be compared with a range. Values which could not be stored in their column
+/// are not used, as they would not compare the same way once encoded.
+///
+/// # Arguments
+///
+/// * `condition` - The condition of a statement, if any
+/// * `table` - The table the condition is evaluated against
+fn plan_index_scan<'a>(condition: Option<&Expr>, table: &'a Table) -> Option<IndexScan<'a>> {
+    let mut comparisons = Vec::new();
+    column_comparisons(condition?, &table.schema, &mut comparisons);
+
+    let mut best: Option<(usize, IndexScan)> = None;
+    for index in &table.indexes {
+        let encoded = |column: usize, ops: &[BinaryOperator]| -> Vec<Vec<u8>> {
+            let col_type = table.schema.columns[column].col_type;
+            comparisons
+                .iter()
+                .filter(|(other, op, _)| *other == column && ops.contains(op))
+                .filter_map(|(_, _, value)| encode_value(col_type, value))
+                .collect()
+        };
+
+        let mut prefix = Vec::new();
+        let mut equal_columns = 0;
+        for &column in &index.columns {
+            match encoded(column, &[BinaryOperator::Equals]).pop() {
+                Some(value) => prefix.extend(value),
+                None => break,
+            }
+            equal_columns += 1;
+        }
+
+        let (lower, upper) = match index.columns.get(equal_columns) {
+            Some(&column) => (
+                encoded(
+                    column,
+                    &[BinaryOperator::Greater, BinaryOperator::GreaterEquals],
+                )
+                .into_iter()
+                .max(),
+                encoded(column, &[BinaryOperator::Less, BinaryOperator::LessEquals])
+                    .into_iter()
+                    .min(),
+            ),
+            None => (None, None),
+        };
+
+        // Each column compared for equality narrows down the rows more than a range
+        let score = 2 * equal_columns + (lower.is_some() || upper.is_some()) as usize;
+        if score > best.as_ref().map_or(0, |(best_score, _)| *best_score) {
+            let scan = IndexScan {
+                index,
+                prefix,
+                lower,
+                upper,
+            };
+            best = Some((score, scan));
+        }
+    }
+    best.map(|(_, scan)| scan)
+}
+
+/// Helper function to read the rows of a table which may match a condition,
+/// in the order of their keys, until `f` returns false. When an index covers
+/// columns compared with constant values, only the rows it lists in the
+/// matching range are read, otherwise the whole table is. Either way,
+/// `f` must still check the condition for every row.
+///
+/// # Arguments
+///
+/// * `table` - The table to read
+/// * `condition` - The condition of the statement, if any
+/// * `pager` - The pager holding the pages of the table and its indexes
+/// * `f` - Called with every row read
+fn scan_rows<F: FnMut(Row) -> bool>(
+    table: &Table,
+    condition: Option<&Expr>,
+    pager: &mut Pager,
+    mut f: F,
+) {
+    match plan_index_scan(condition, table) {
+        Some(scan) => {
+            let mut keys = scan.index.row_keys(
+                pager,
+                &scan.prefix,
+                scan.lower.as_deref(),
+                scan.upper.as_deref(),
+            );
+            keys.sort_unstable();
+            for key in keys {
+                let mut cursor = Cursor::find(pager, table.root_page_num, &row_key(key));
+                if !f(Row::deserialize(&table.schema, &cursor.value())) {
+                    return;
+                }
+            }
+        }
+        None => {
+            let mut cursor = Cursor::table_start(pager, table.root_page_num);
+            while !cursor.end_of_table {
+                let row = Row::deserialize(&table.schema, &cursor.value());
+                cursor.advance();
+                if !f(row) {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Helper function to evaluate the number of rows given to `limit` or `offset`,
+/// returning `None` for a negative number
+///
+/// # Arguments
+///
+/// * `expr` - The expression computing the number of rows
+fn row_count(expr: &Expr) -> Result<Option<usize>, ExecuteResult> {
+    match evaluate(expr, &|_| Value::Null) {
+        Value::Integer(value) => Ok(usize::try_from(value).ok()),
+        _ => Err(ExecuteResult::TypeMismatch),
+    }
+}
+
+/// Struct that evaluates the result columns of a select for every row or group,
+/// collecting the results in the requested order
+struct Projection<'a> {
+    select: &'a Select,
+    schema: &'a Schema,
+    /// The expressions the results are sorted by, with their direction
+    ordering: Vec<(&'a Expr, bool)>,
+    /// Sorts the results, unless they come in the requested order
+    sorter: Option<Sorter>,
+    rows: Vec<Vec<Value>>,
+    /// The results come in the reverse of the requested order
+    reverse: bool,
+    offset: usize,
+    limit: Option<usize>,
+}
+
+impl<'a> Projection<'a> {
+    /// Adds the result for a row, or for a group along with the values of its aggregates.
+    /// Returns false once no more results are needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `row` - The values of the row, or of the last row of the group
+    /// * `aggregates` - Pairs of a call of an aggregate function and its value for the group
+    fn push(&mut self, row: &[Value], aggregates: &[(&Expr, Value)]) -> bool {
+        let schema = self.schema;
+        let column = |name: &str| match schema.column_index(name) {
+            Some(index) => row.get(index).cloned().unwrap_or(Value::Null),
+            None => Value::Null,
+        };
+        let value = |expr: &Expr| {
+            if aggregates.is_empty() {
+                evaluate(expr, &column)
+            } else {
+                evaluate(&replace_aggregates(expr, aggregates), &column)
+            }
+        };
+
+        if let Some(condition) = &self.select.having {
+            if is_true(&value(condition)) != Some(true) {
+                return true;
+            }
+        }
+
+        let mut values = Vec::new();
+        for result_column in &self.select.columns {
+            match result_column {
+                ResultColumn::Star => values.extend(
+                    (0..schema.columns.len()).map(|i| row.get(i).cloned().unwrap_or(Value::Null)),
+                ),
+                ResultColumn::Expr { expr, .. } => values.push(value(expr)),
+            }
+        }
+
+        match &mut self.sorter {
+            Some(sorter) => {
+                let keys = self.ordering.iter().map(|(expr, _)| value(expr)).collect();
+                sorter.push(SortRecord { keys, values });
+                true
+            }
+            None => {
+                self.rows.push(values);
+                self.reverse || self.limit.map(|limit| limit + self.offset) != Some(self.rows.len())
+            }
+        }
+    }
+
+    /// Returns the results in the requested order, after applying the offset and limit
+    fn finish(self) -> Vec<Row> {
+        let ordered: Box<dyn Iterator<Item = Vec<Value>>> = match self.sorter {
+            Some(sorter) => Box::new(sorter.sorted()),
+            None if self.reverse => Box::new(self.rows.into_iter().rev()),
+            None => Box::new(self.rows.into_iter()),
+        };
+        ordered
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .map(|values| Row { values })
+            .collect()
+    }
+}
+
+/// Struct that holds the state of the aggregate functions while the rows of a group are read
+struct Group<'a> {
+    accumulators: Vec<(&'a Expr, Accumulator)>,
+    /// Columns outside of aggregate functions take their values from the last row
+    last_row: Vec<Value>,
+}
+
+impl<'a> Group<'a> {
+    /// Returns a group without rows
+    ///
+    /// # Arguments
+    ///
+    /// * `aggregates` - The calls of aggregate functions to compute
+    fn new(aggregates: &[&'a Expr]) -> Self {
+        let accumulators = aggregates
+            .iter()
+            .map(|&aggregate| match aggregate {
+                Expr::Aggregate { function, .. } => (aggregate, Accumulator::new(*function)),
+                _ => unreachable!("not an aggregate function"),
+            })
+            .collect();
+        Group {
+            accumulators,
+            last_row: Vec::new(),
+        }
+    }
+
+    /// Adds a row to the group
+    ///
+    /// # Arguments
+    ///
+    /// * `row` - The values of the row
+    /// * `schema` - The schema of the table the row belongs to
+    fn step(&mut self, row: Vec<Value>, schema: &Schema) {
+        let column = |name: &str| match schema.column_index(name) {
+            Some(index) => row[index].clone(),
+            None => Value::Null,
+        };
+        for (aggregate, accumulator) in &mut self.accumulators {
+            if let Expr::Aggregate { args, .. } = aggregate {
+                let args: Vec<Value> = args.iter().map(|arg| evaluate(arg, &column)).collect();
+                accumulator.step(&args);
+            }
+        }
+        self.last_row = row;
+    }
+
+    /// Adds the result for the group to the projection
+    fn finish(self, projection: &mut Projection) -> Result<(), ExecuteResult> {
+        let aggregates = self
+            .accumulators
+            .into_iter()
+            .map(|(aggregate, accumulator)| Ok((aggregate, accumulator.finish()?)))
+            .collect::<Result<Vec<(&Expr, Value)>, ExecuteResult>>()?;
+        projection.push(&self.last_row, &aggregates);
+        Ok(())
+    }
+}
+
+/// Helper function to execute a SQL select statement,
+/// evaluating the result columns for every row matching the condition.
+/// Rows are sorted unless they are ordered by the key, which is the order
+/// the table is read in, and scanning stops early once enough rows are found.
+/// A select using aggregate functions returns one row per group instead,
+/// the groups are formed by sorting the rows by the `group by` expressions.
+///
+/// # Arguments
+///
+/// * `select` - The select statement
+/// * `database` - A `Database` struct holding current data
+fn execute_select(select: &Select, database: &mut Database) -> ExecuteResult {
+    let table = database.table(&select.table_name).unwrap().clone();
+    let schema = &table.schema;
+
+    let limit = match select.limit.as_ref().map(row_count) {
+        Some(Ok(limit)) => limit,
+        Some(Err(result)) => return result,
+        None => None,
+    };
+    let offset = match select.offset.as_ref().map(row_count) {
+        Some(Ok(offset)) => offset.unwrap_or(0),
+        Some(Err(result)) => return result,
+        None => 0,
+    };
+
+    let ordering: Vec<(&Expr, bool)> = select
+        .order_by
+        .iter()
+        .map(|term| (ordering_expr(term, &select.columns), term.descending))
+        .collect();
+
+    let mut aggregates: Vec<&Expr> = Vec::new();
+    let exprs = select
+        .columns
+        .iter()
+        .filter_map(|column| match column {
+            ResultColumn::Star => None,
+            ResultColumn::Expr { expr, .. } => Some(expr),
+        })
+        .chain(&select.having)
+        .chain(ordering.iter().map(|(expr, _)| *expr));
+    for expr in exprs {
+        expr.visit_aggregates(&mut |aggregate| {
+            if !aggregates.contains(&aggregate) {
+                aggregates.push(aggregate);
+            }
+        });
+    }
+    let grouped = !aggregates.is_empty() || !select.group_by.is_empty() || select.having.is_some();
+
+    // The key is unique, so the terms after it never matter
+    let by_key = match ordering.first() {
+        Some((Expr::Column(name), _)) => {
+            !grouped && schema.column_index(name) == Some(schema.key_column)
+        }
+        _ => false,
+    };
+    let reverse = by_key && ordering[0].1;
+    let sorter = if ordering.is_empty() || by_key {
+        None
+    } else {
+        Some(Sorter::new(
+            ordering.iter().map(|(_, descending)| *descending).collect(),
+        ))
+    };
+
+    let columns = select
+        .columns
+        .iter()
+        .flat_map(|column| match column {
+            ResultColumn::Star => schema.columns.iter().map(|c| c.name.clone()).collect(),
+            ResultColumn::Expr { name, .. } => vec![name.clone()],
+        })
+        .collect();
+    let mut projection = Projection {
+        select,
+        schema,
+        ordering,
+        sorter,
+        rows: Vec::new(),
+        reverse,
+        offset,
+        limit,
+    };
+
+    // Without `group by` all the rows make up a single group
+    let mut group_sorter = if select.group_by.is_empty() {
+        None
+    } else {
+        Some(Sorter::new(vec![false; select.group_by.len()]))
+    };
+    let mut group = if grouped && group_sorter.is_none() {
+        Some(Group::new(&aggregates))
+    } else {
+        None
+    };
+
+    let condition = select.where_clause.as_ref();
+    scan_rows(&table, condition, &mut database.pager, |row| {
+        let column = |name: &str| match schema.column_index(name) {
+            Some(index) => row.values[index].clone(),
+            None => Value::Null,
+        };
+        if let Some(condition) = condition {
+            if is_true(&evaluate(condition, &column)) != Some(true) {
+                return true;
+            }
+        }
+
+        if let Some(group_sorter) = &mut group_sorter {
+            let keys = select
+                .group_by
+                .iter()
+                .map(|expr| evaluate(expr, &column))
+                .collect();
+            group_sorter.push(SortRecord {
+                keys,
+                values: row.values,
+            });
+            true
+        } else if let Some(group) = &mut group {
+            group.step(row.values, schema);
+            true
+        } else {
+            projection.push(&row.values, &[])
+        }
+    });
+
+    if let Some(group_sorter) = group_sorter {
+        // Rows with equal keys come one after another
+        let descending = vec![false; select.group_by.len()];
+        let mut current: Option<(Vec<Value>, Group)> = None;
+        for values in group_sorter.sorted() {
+            let column = |name: &str| match schema.column_index(name) {
+                Some(index) => values[index].clone(),
+                None => Value::Null,
+            };
+            let keys: Vec<Value> = select
+                .group_by
+                .iter()
+                .map(|expr| evaluate(expr, &column))
+                .collect();
+
+            let same_group = match &current {
+                Some((group_keys, _)) => {
+                    compare_keys(group_keys, &keys, &descending) == Ordering::Equal
+                }
+                None => false,
+            };
+            if !same_group {
+                if let Some((_, group)) = current.take() {
+                    if let Err(result) = group.finish(&mut projection) {
+                        return result;
+                    }
+                }
+                current = Some((keys, Group::new(&aggregates)));
+            }
+            if let Some((_, group)) = &mut current {
+                group.step(values, schema);
+            }
+        }
+        if let Some((_, group)) = current {
+            if let Err(result) = group.finish(&mut projection) {
+                return result;
+            }
+        }
+    } else if let Some(group) = group {
+        if let Err(result) = group.finish(&mut projection) {
+            return result;
+        }
+    }
+
+    ExecuteResult::Rows(ResultSet {
+        columns,
+        rows: projection.finish(),
+    })
+}
+
+/// Helper function to execute a SQL update statement.
+/// The new values of all the matching rows are computed and checked
+/// before the first row is changed. A row whose key changes is moved
+/// to its new place in the tree by deleting and inserting it again,
+/// and so is a row whose values change in the columns of an index.
 ///
 /// # Arguments
 ///
-/// * `statement` - A `Statement` struct holding the type of statement and relevant data based on the type
-/// * `table` - A `Table` struct holding current data
-fn execute_select(table: &mut Table) -> ExecuteResult {
-    for i in 0..table.num_rows {
-        let (page_num, byte_offset) = table.row_slot(i);
-        &table.deserialize_row(page_num, byte_offset).print_row();
+/// * `update` - The update statement
+/// * `database` - A `Database` struct holding current data
+fn execute_update(update: &Update, database: &mut Database) -> ExecuteResult {
+    let table = database.table(&update.table_name).unwrap().clone();
+    let schema = &table.schema;
+
+    let assignments: Vec<(usize, &Expr)> = update
+        .assignments
+        .iter()
+        .map(|(name, expr)| (schema.column_index(name).unwrap(), expr))
+        .collect();
+
+    // Pairs of the old and the new values of a row
+    let mut updates = Vec::new();
+    let mut result = None;
+    let condition = update.where_clause.as_ref();
+    scan_rows(&table, condition, &mut database.pager, |row| {
+        let column = |name: &str| match schema.column_index(name) {
+            Some(index) => row.values[index].clone(),
+            None => Value::Null,
+        };
+        if let Some(condition) = condition {
+            if is_true(&evaluate(condition, &column)) != Some(true) {
+                return true;
+            }
+        }
+
+        // Every expression sees the values the row had before the update
+        let mut values = row.values.clone();
+        for &(index, expr) in &assignments {
+            let value = evaluate(expr, &column);
+            match column_value(value, &schema.columns[index], index == schema.key_column) {
+                Ok(value) => values[index] = value,
+                Err(error) => {
+                    result = Some(error);
+                    return false;
+                }
+            }
+        }
+        updates.push((row, Row { values }));
+        true
+    });
+    if let Some(result) = result {
+        return result;
+    }
+
+    // A new key may only be taken by a row which is updated as well
+    let old_keys: HashSet<i64> = updates.iter().map(|(old, _)| old.key(schema)).collect();
+    let mut new_keys = HashSet::new();
+    for (old, row) in &updates {
+        let key = row.key(schema);
+        if !new_keys.insert(key) {
+            return ExecuteResult::DuplicateKey;
+        }
+        if key != old.key(schema) && !old_keys.contains(&key) {
+            let mut cursor = Cursor::find(&mut database.pager, table.root_page_num, &row_key(key));
+            if !cursor.end_of_table && cursor.key() == row_key(key) {
+                return ExecuteResult::DuplicateKey;
+            }
+        }
+    }
+
+    // The same goes for the values of the columns of a unique index
+    let new_rows: Vec<&Row> = updates.iter().map(|(_, row)| row).collect();
+    if let Some(name) = unique_conflict(&table, &new_rows, &mut database.pager, &old_keys) {
+        return ExecuteResult::UniqueConstraint(name);
+    }
+
+    // Moved rows and index cells are all removed before any is inserted again,
+    // so that they may swap keys with each other
+    let mut moved_cells = Vec::new();
+    for index in &table.indexes {
+        for (old, row) in &updates {
+            let (old_key, key) = (index.key(schema, old), index.key(schema, row));
+            if old_key != key {
+                Cursor::find(&mut database.pager, index.root_page_num, &old_key).delete();
+                moved_cells.push((index.root_page_num, key));
+            }
+        }
+    }
+
+    let mut moved = Vec::new();
+    for (old, row) in &updates {
+        let (old_key, key) = (row_key(old.key(schema)), row_key(row.key(schema)));
+        let value = row.serialize();
+
+        let mut cursor = Cursor::find(&mut database.pager, table.root_page_num, &old_key);
+        if key == old_key {
+            cursor.update(&value);
+        } else {
+            cursor.delete();
+            moved.push((key, value));
+        }
+    }
+    for (key, value) in moved {
+        Cursor::find(&mut database.pager, table.root_page_num, &key).insert(&key, &value);
+    }
+    for (root_page_num, key) in moved_cells {
+        Cursor::find(&mut database.pager, root_page_num, &key).insert(&key, &[]);
+    }
+
+    ExecuteResult::RowsAffected(updates.len())
+}
+
+/// Helper function to execute a SQL delete statement.
+/// The matching rows are collected first, as removing
+/// a row may rebalance the tree under the cursor.
+///
+/// # Arguments
+///
+/// * `delete` - The delete statement
+/// * `database` - A `Database` struct holding current data
+fn execute_delete(delete: &Delete, database: &mut Database) -> ExecuteResult {
+    let table = database.table(&delete.table_name).unwrap().clone();
+    let schema = &table.schema;
+
+    let mut rows = Vec::new();
+    let condition = delete.where_clause.as_ref();
+    scan_rows(&table, condition, &mut database.pager, |row| {
+        if let Some(condition) = condition {
+            let column = |name: &str| match schema.column_index(name) {
+                Some(index) => row.values[index].clone(),
+                None => Value::Null,
+            };
+            if is_true(&evaluate(condition, &column)) != Some(true) {
+                return true;
+            }
+        }
+        rows.push(row);
+        true
+    });
+
+    for row in &rows {
+        for index in &table.indexes {
+            index.delete(&mut database.pager, schema, row);
+        }
+        let key = row_key(row.key(schema));
+        Cursor::find(&mut database.pager, table.root_page_num, &key).delete();
+    }
+
+    ExecuteResult::RowsAffected(rows.len())
+}
+
+/// Returns the value a pragma sets, which is a constant expression.
+/// A bare name, e.g. `wal`, stands for itself.
+fn pragma_value(pragma: &Pragma) -> Option<Value> {
+    pragma
+        .value
+        .as_ref()
+        .map(|value| evaluate(value, &|name| Value::Text(name.to_string())))
+}
+
+/// Returns the value of a setting read by a pragma as a single row
+///
+/// # Arguments
+///
+/// * `name` - The name of the setting
+/// * `value` - The value of the setting
+fn pragma_setting(name: &str, value: Value) -> ExecuteResult {
+    ExecuteResult::Rows(ResultSet {
+        columns: vec![name.to_string()],
+        rows: vec![Row {
+            values: vec![value],
+        }],
+    })
+}
+
+/// Helper function to check a pragma statement
+///
+/// # Arguments
+///
+/// * `pragma` - The pragma statement
+fn prepare_pragma(pragma: &Pragma) -> PrepareResult {
+    match pragma.name.to_ascii_lowercase().as_str() {
+        "page_size" => match pragma_value(pragma) {
+            None => PrepareResult::Success,
+            Some(Value::Integer(size)) if u32::try_from(size).is_ok_and(is_valid_page_size) => {
+                PrepareResult::Success
+            }
+            Some(_) => PrepareResult::InvalidPageSize,
+        },
+        "cache_size" => match pragma_value(pragma) {
+            None | Some(Value::Integer(1..)) => PrepareResult::Success,
+            Some(_) => PrepareResult::InvalidCacheSize,
+        },
+        "journal_mode" => match pragma_value(pragma) {
+            None => PrepareResult::Success,
+            Some(Value::Text(name)) if JournalMode::parse(&name).is_some() => {
+                PrepareResult::Success
+            }
+            Some(_) => PrepareResult::InvalidJournalMode,
+        },
+        // All checkpoint modes are the same, as there are no other connections
+        "wal_checkpoint" => PrepareResult::Success,
+        _ => PrepareResult::UnknownPragma(pragma.name.clone()),
+    }
+}
+
+/// Helper function to execute a pragma statement, returning the value
+/// of the setting when it is read
+///
+/// # Arguments
+///
+/// * `pragma` - The prepared pragma statement
+/// * `database` - A `Database` struct holding current data
+fn execute_pragma(pragma: &Pragma, database: &mut Database) -> ExecuteResult {
+    if pragma.name.eq_ignore_ascii_case("cache_size") {
+        return match pragma_value(pragma) {
+            Some(Value::Integer(size)) => {
+                database.pager.set_cache_size(size as usize);
+                ExecuteResult::Success
+            }
+            _ => pragma_setting(
+                "cache_size",
+                Value::Integer(database.pager.cache.capacity as i64),
+            ),
+        };
+    }
+    if pragma.name.eq_ignore_ascii_case("journal_mode") {
+        return match pragma_value(pragma) {
+            // Changing the mode commits, which would end the transaction
+            Some(Value::Text(_)) if database.transaction == Transaction::Active => {
+                ExecuteResult::JournalModeInTransaction
+            }
+            Some(Value::Text(name)) => {
+                database
+                    .pager
+                    .set_journal_mode(JournalMode::parse(&name).unwrap());
+                ExecuteResult::Success
+            }
+            _ => pragma_setting(
+                "journal_mode",
+                Value::Text(database.pager.header.journal_mode.name().to_string()),
+            ),
+        };
+    }
+    if pragma.name.eq_ignore_ascii_case("wal_checkpoint") {
+        // Outside of WAL mode, the log and the checkpointed frames are reported as -1
+        let (log, checkpointed) = match database.pager.checkpoint() {
+            Some((log, checkpointed)) => (log as i64, checkpointed as i64),
+            None => (-1, -1),
+        };
+        return ExecuteResult::Rows(ResultSet {
+            columns: vec![
+                "busy".to_string(),
+                "log".to_string(),
+                "checkpointed".to_string(),
+            ],
+            rows: vec![Row {
+                values: vec![
+                    Value::Integer(0),
+                    Value::Integer(log),
+                    Value::Integer(checkpointed),
+                ],
+            }],
+        });
+    }
+
+    let page_size = match pragma_value(pragma) {
+        Some(Value::Integer(size)) => size as u32,
+        _ => return pragma_setting("page_size", Value::Integer(database.pager.page_size as i64)),
+    };
+
+    if page_size as usize != database.pager.page_size {
+        // The log holds pages of the current size
+        if database.pager.wal.is_some() {
+            return ExecuteResult::PageSizeInWalMode;
+        }
+        // Pages of the new size could not hold the existing B+trees
+        if !database.tables.is_empty() {
+            return ExecuteResult::DatabaseNotEmpty;
+        }
+        database.set_page_size(page_size);
     }
     ExecuteResult::Success
 }
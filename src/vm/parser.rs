@@ -0,0 +1,675 @@
+//! # Parser
+//!
+//! A recursive-descent parser turning the tokens of a SQL statement
+//! into the abstract syntax tree defined in `statement`
+
+use super::lexer::{Keyword, Lexer, SyntaxError, Token, TokenKind};
+use super::statement::{
+    AggregateFunction, BinaryOperator, CreateIndex, Delete, Expr, Insert, Literal, OrderingTerm,
+    Pragma, ResultColumn, Select, Statement, UnaryOperator, Update,
+};
+use crate::table::schema::{Column, ColumnType, Schema};
+
+/// Parses the text of a single statement, optionally ending with `;`
+///
+/// # Arguments
+///
+/// * `sql` - The text of the statement
+///
+/// # Example
+///
+/// ```
+/// use rsqlite::vm::parser::parse;
+/// use rsqlite::vm::statement::Statement;
+/// let statement = parse("select * from users;").unwrap();
+/// assert!(matches!(statement, Statement::Select(_)));
+/// ```
+pub fn parse(sql: &str) -> Result<Statement, SyntaxError> {
+    let tokens = Lexer::new(sql).tokenize()?;
+    let mut parser = Parser {
+        sql,
+        tokens,
+        position: 0,
+    };
+
+    let statement = parser.statement()?;
+    parser.consume(&TokenKind::Semicolon);
+    if parser.peek().kind != TokenKind::Eof {
+        return Err(parser.unexpected("end of statement"));
+    }
+    Ok(statement)
+}
+
+/// A struct holding the tokens of a statement and the position of the next one
+struct Parser<'a> {
+    sql: &'a str,
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser<'_> {
+    /// Returns the next token without consuming it
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position]
+    }
+
+    /// Consumes and returns the next token, `Eof` is never consumed
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.position].clone();
+        if token.kind != TokenKind::Eof {
+            self.position += 1;
+        }
+        token
+    }
+
+    /// Consumes the next token if it is of the given kind
+    fn consume(&mut self, kind: &TokenKind) -> bool {
+        if &self.peek().kind == kind {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Consumes the next token if it is the given keyword
+    fn consume_keyword(&mut self, keyword: Keyword) -> bool {
+        self.consume(&TokenKind::Keyword(keyword))
+    }
+
+    /// Consumes the next token, failing if it is not of the given kind
+    fn expect(&mut self, kind: TokenKind) -> Result<(), SyntaxError> {
+        if self.consume(&kind) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&kind.to_string()))
+        }
+    }
+
+    /// Consumes the next token, failing if it is not the given keyword
+    fn expect_keyword(&mut self, keyword: Keyword) -> Result<(), SyntaxError> {
+        self.expect(TokenKind::Keyword(keyword))
+    }
+
+    /// Consumes a name of a table or column
+    fn identifier(&mut self) -> Result<String, SyntaxError> {
+        match &self.peek().kind {
+            TokenKind::Identifier(name) => {
+                let name = name.clone();
+                self.advance();
+                Ok(name)
+            }
+            _ => Err(self.unexpected("a name")),
+        }
+    }
+
+    /// Returns an error at the next token, describing what was expected instead
+    fn unexpected(&self, expected: &str) -> SyntaxError {
+        let token = self.peek();
+        self.error_at(
+            token,
+            &format!("expected {} but found {}", expected, token.kind),
+        )
+    }
+
+    /// Returns an error at the position of the given token
+    fn error_at(&self, token: &Token, message: &str) -> SyntaxError {
+        SyntaxError {
+            message: message.to_string(),
+            line: token.line,
+            column: token.column,
+        }
+    }
+
+    /// statement := create_table | create_index | drop_index | insert | select | update
+    ///            | delete | pragma | transaction | <nothing>
+    fn statement(&mut self) -> Result<Statement, SyntaxError> {
+        match self.peek().kind {
+            TokenKind::Keyword(Keyword::Create) => match self.tokens[self.position + 1].kind {
+                TokenKind::Keyword(Keyword::Index) | TokenKind::Keyword(Keyword::Unique) => {
+                    self.create_index()
+                }
+                _ => self.create_table(),
+            },
+            TokenKind::Keyword(Keyword::Drop) => self.drop_index(),
+            TokenKind::Keyword(Keyword::Insert) => self.insert(),
+            TokenKind::Keyword(Keyword::Select) => self.select(),
+            TokenKind::Keyword(Keyword::Update) => self.update(),
+            TokenKind::Keyword(Keyword::Delete) => self.delete(),
+            TokenKind::Keyword(Keyword::Pragma) => self.pragma(),
+            TokenKind::Keyword(Keyword::Begin)
+            | TokenKind::Keyword(Keyword::Commit)
+            | TokenKind::Keyword(Keyword::Rollback) => self.transaction(),
+            TokenKind::Semicolon | TokenKind::Eof => Ok(Statement::Empty),
+            _ => Err(self.unexpected("a statement")),
+        }
+    }
+
+    /// create_table := CREATE TABLE name ( column_definition, ... )
+    ///
+    /// The schema is checked here, so that errors point at the offending column
+    fn create_table(&mut self) -> Result<Statement, SyntaxError> {
+        self.expect_keyword(Keyword::Create)?;
+        self.expect_keyword(Keyword::Table)?;
+        let name = self.identifier()?;
+        self.expect(TokenKind::LeftParen)?;
+
+        let first_column = self.peek().clone();
+        let mut columns: Vec<Column> = Vec::new();
+        let mut key_column = None;
+        loop {
+            let start = self.peek().clone();
+            let (column, is_key) = self.column_definition()?;
+
+            if columns
+                .iter()
+                .any(|other| other.name.eq_ignore_ascii_case(&column.name))
+            {
+                let message = format!("duplicate column name '{}'", column.name);
+                return Err(self.error_at(&start, &message));
+            }
+            if is_key {
+                if key_column.is_some() {
+                    return Err(self.error_at(&start, "table has more than one primary key"));
+                }
+                if column.col_type != ColumnType::Integer {
+                    return Err(self.error_at(&start, "primary key must be an integer"));
+                }
+                key_column = Some(columns.len());
+            }
+            columns.push(column);
+
+            if !self.consume(&TokenKind::Comma) {
+                break;
+            }
+        }
+        self.expect(TokenKind::RightParen)?;
+
+        // Without a primary key, rows are keyed by the first column
+        let key_column = match key_column {
+            Some(key_column) => key_column,
+            None if columns[0].col_type == ColumnType::Integer => 0,
+            None => return Err(self.error_at(&first_column, "first column must be an integer")),
+        };
+
+        Ok(Statement::CreateTable(Schema {
+            name,
+            columns,
+            key_column,
+            sql: self.sql.trim().trim_end_matches(';').trim_end().to_string(),
+        }))
+    }
+
+    /// create_index := CREATE [UNIQUE] INDEX name ON name ( column, ... )
+    fn create_index(&mut self) -> Result<Statement, SyntaxError> {
+        self.expect_keyword(Keyword::Create)?;
+        let unique = self.consume_keyword(Keyword::Unique);
+        self.expect_keyword(Keyword::Index)?;
+        let name = self.identifier()?;
+        self.expect_keyword(Keyword::On)?;
+        let table_name = self.identifier()?;
+        self.expect(TokenKind::LeftParen)?;
+
+        let mut columns = vec![self.identifier()?];
+        while self.consume(&TokenKind::Comma) {
+            columns.push(self.identifier()?);
+        }
+        self.expect(TokenKind::RightParen)?;
+
+        Ok(Statement::CreateIndex(CreateIndex {
+            name,
+            table_name,
+            columns,
+            unique,
+            sql: self.sql.trim().trim_end_matches(';').trim_end().to_string(),
+        }))
+    }
+
+    /// drop_index := DROP INDEX name
+    fn drop_index(&mut self) -> Result<Statement, SyntaxError> {
+        self.expect_keyword(Keyword::Drop)?;
+        self.expect_keyword(Keyword::Index)?;
+        Ok(Statement::DropIndex(self.identifier()?))
+    }
+
+    /// pragma := PRAGMA name [= expr | = keyword]
+    fn pragma(&mut self) -> Result<Statement, SyntaxError> {
+        self.expect_keyword(Keyword::Pragma)?;
+        let name = self.identifier()?;
+        let value = if self.consume(&TokenKind::Equals) {
+            // A keyword is a bare name, e.g. `pragma journal_mode = delete`
+            if let TokenKind::Keyword(keyword) = self.peek().kind {
+                self.advance();
+                Some(Expr::Column(keyword.to_string()))
+            } else {
+                Some(self.expr()?)
+            }
+        } else {
+            None
+        };
+        Ok(Statement::Pragma(Pragma { name, value }))
+    }
+
+    /// transaction := (BEGIN | COMMIT | ROLLBACK) [TRANSACTION]
+    fn transaction(&mut self) -> Result<Statement, SyntaxError> {
+        let statement = match self.advance().kind {
+            TokenKind::Keyword(Keyword::Begin) => Statement::Begin,
+            TokenKind::Keyword(Keyword::Commit) => Statement::Commit,
+            _ => Statement::Rollback,
+        };
+        self.consume_keyword(Keyword::Transaction);
+        Ok(statement)
+    }
+
+    /// column_definition := name type [PRIMARY KEY]
+    fn column_definition(&mut self) -> Result<(Column, bool), SyntaxError> {
+        let name = self.identifier()?;
+        let col_type = self.column_type()?;
+        let is_key = self.consume_keyword(Keyword::Primary);
+        if is_key {
+            self.expect_keyword(Keyword::Key)?;
+        }
+        Ok((Column { name, col_type }, is_key))
+    }
+
+    /// type := INT | INTEGER | REAL | DOUBLE | FLOAT
+    ///       | TEXT size | VARCHAR size | BLOB size
+    fn column_type(&mut self) -> Result<ColumnType, SyntaxError> {
+        let start = self.peek().clone();
+        let type_name = self.identifier()?.to_ascii_lowercase();
+
+        match type_name.as_str() {
+            "int" | "integer" => Ok(ColumnType::Integer),
+            "real" | "double" | "float" => Ok(ColumnType::Real),
+            "text" | "varchar" => Ok(ColumnType::Text(self.type_size(&start, "text")?)),
+            "blob" => Ok(ColumnType::Blob(self.type_size(&start, "blob")?)),
+            _ => Err(self.error_at(&start, &format!("unknown type '{}'", type_name))),
+        }
+    }
+
+    /// size := [ ( integer ) ]
+    fn type_size(&mut self, start: &Token, type_name: &str) -> Result<Option<usize>, SyntaxError> {
+        // Without a size, values may be of any length
+        if !self.consume(&TokenKind::LeftParen) {
+            return Ok(None);
+        }
+        let size = match self.advance().kind {
+            TokenKind::Integer(size) if size > 0 => size as usize,
+            _ => {
+                let message = format!("{} size must be a positive integer", type_name);
+                return Err(self.error_at(start, &message));
+            }
+        };
+        self.expect(TokenKind::RightParen)?;
+        Ok(Some(size))
+    }
+
+    /// insert := INSERT INTO name VALUES ( expr, ... ), ...
+    fn insert(&mut self) -> Result<Statement, SyntaxError> {
+        self.expect_keyword(Keyword::Insert)?;
+        self.expect_keyword(Keyword::Into)?;
+        let table_name = self.identifier()?;
+        self.expect_keyword(Keyword::Values)?;
+
+        let mut rows = Vec::new();
+        loop {
+            self.expect(TokenKind::LeftParen)?;
+            let mut row = vec![self.expr()?];
+            while self.consume(&TokenKind::Comma) {
+                row.push(self.expr()?);
+            }
+            self.expect(TokenKind::RightParen)?;
+            rows.push(row);
+
+            if !self.consume(&TokenKind::Comma) {
+                break;
+            }
+        }
+
+        Ok(Statement::Insert(Insert { table_name, rows }))
+    }
+
+    /// select := SELECT result_column, ... FROM name [WHERE expr]
+    ///           [GROUP BY expr, ...] [HAVING expr]
+    ///           [ORDER BY ordering_term, ...] [LIMIT expr [OFFSET expr]]
+    fn select(&mut self) -> Result<Statement, SyntaxError> {
+        self.expect_keyword(Keyword::Select)?;
+        let mut columns = vec![self.result_column()?];
+        while self.consume(&TokenKind::Comma) {
+            columns.push(self.result_column()?);
+        }
+        self.expect_keyword(Keyword::From)?;
+        let table_name = self.identifier()?;
+
+        let where_clause = if self.consume_keyword(Keyword::Where) {
+            Some(self.expr()?)
+        } else {
+            None
+        };
+
+        let mut group_by = Vec::new();
+        if self.consume_keyword(Keyword::Group) {
+            self.expect_keyword(Keyword::By)?;
+            loop {
+                group_by.push(self.expr()?);
+                if !self.consume(&TokenKind::Comma) {
+                    break;
+                }
+            }
+        }
+        let having = if self.consume_keyword(Keyword::Having) {
+            Some(self.expr()?)
+        } else {
+            None
+        };
+
+        let mut order_by = Vec::new();
+        if self.consume_keyword(Keyword::Order) {
+            self.expect_keyword(Keyword::By)?;
+            loop {
+                order_by.push(self.ordering_term()?);
+                if !self.consume(&TokenKind::Comma) {
+                    break;
+                }
+            }
+        }
+
+        let (mut limit, mut offset) = (None, None);
+        if self.consume_keyword(Keyword::Limit) {
+            limit = Some(self.expr()?);
+            if self.consume_keyword(Keyword::Offset) {
+                offset = Some(self.expr()?);
+            }
+        }
+
+        Ok(Statement::Select(Select {
+            columns,
+            table_name,
+            where_clause,
+            group_by,
+            having,
+            order_by,
+            limit,
+            offset,
+        }))
+    }
+
+    /// result_column := * | expr [[AS] alias]
+    fn result_column(&mut self) -> Result<ResultColumn, SyntaxError> {
+        if self.consume(&TokenKind::Star) {
+            return Ok(ResultColumn::Star);
+        }
+
+        let start = self.peek().start;
+        let expr = self.expr()?;
+        let end = self.tokens[self.position - 1].end;
+
+        let name = if self.consume_keyword(Keyword::As) {
+            self.identifier()?
+        } else if let TokenKind::Identifier(alias) = &self.peek().kind {
+            let alias = alias.clone();
+            self.advance();
+            alias
+        } else if let Expr::Column(name) = &expr {
+            name.clone()
+        } else {
+            self.sql[start..end].to_string()
+        };
+
+        Ok(ResultColumn::Expr { expr, name })
+    }
+
+    /// ordering_term := expr [ASC | DESC]
+    fn ordering_term(&mut self) -> Result<OrderingTerm, SyntaxError> {
+        let expr = self.expr()?;
+        let descending = if self.consume_keyword(Keyword::Desc) {
+            true
+        } else {
+            self.consume_keyword(Keyword::Asc);
+            false
+        };
+        Ok(OrderingTerm { expr, descending })
+    }
+
+    /// update := UPDATE name SET column = expr, ... [WHERE expr]
+    fn update(&mut self) -> Result<Statement, SyntaxError> {
+        self.expect_keyword(Keyword::Update)?;
+        let table_name = self.identifier()?;
+        self.expect_keyword(Keyword::Set)?;
+
+        let mut assignments = Vec::new();
+        loop {
+            let column = self.identifier()?;
+            self.expect(TokenKind::Equals)?;
+            assignments.push((column, self.expr()?));
+
+            if !self.consume(&TokenKind::Comma) {
+                break;
+            }
+        }
+
+        let where_clause = if self.consume_keyword(Keyword::Where) {
+            Some(self.expr()?)
+        } else {
+            None
+        };
+
+        Ok(Statement::Update(Update {
+            table_name,
+            assignments,
+            where_clause,
+        }))
+    }
+
+    /// delete := DELETE FROM name [WHERE expr]
+    fn delete(&mut self) -> Result<Statement, SyntaxError> {
+        self.expect_keyword(Keyword::Delete)?;
+        self.expect_keyword(Keyword::From)?;
+        let table_name = self.identifier()?;
+
+        let where_clause = if self.consume_keyword(Keyword::Where) {
+            Some(self.expr()?)
+        } else {
+            None
+        };
+
+        Ok(Statement::Delete(Delete {
+            table_name,
+            where_clause,
+        }))
+    }
+
+    /// expr := or_expr
+    fn expr(&mut self) -> Result<Expr, SyntaxError> {
+        self.or_expr()
+    }
+
+    /// or_expr := and_expr (OR and_expr)*
+    fn or_expr(&mut self) -> Result<Expr, SyntaxError> {
+        let mut left = self.and_expr()?;
+        while self.consume_keyword(Keyword::Or) {
+            let right = self.and_expr()?;
+            left = binary(left, BinaryOperator::Or, right);
+        }
+        Ok(left)
+    }
+
+    /// and_expr := not_expr (AND not_expr)*
+    fn and_expr(&mut self) -> Result<Expr, SyntaxError> {
+        let mut left = self.not_expr()?;
+        while self.consume_keyword(Keyword::And) {
+            let right = self.not_expr()?;
+            left = binary(left, BinaryOperator::And, right);
+        }
+        Ok(left)
+    }
+
+    /// not_expr := NOT not_expr | comparison
+    fn not_expr(&mut self) -> Result<Expr, SyntaxError> {
+        if self.consume_keyword(Keyword::Not) {
+            let operand = self.not_expr()?;
+            return Ok(Expr::Unary {
+                op: UnaryOperator::Not,
+                operand: Box::new(operand),
+            });
+        }
+        self.comparison()
+    }
+
+    /// comparison := additive ((= | != | < | <= | > | >=) additive | IS [NOT] NULL)*
+    fn comparison(&mut self) -> Result<Expr, SyntaxError> {
+        let mut left = self.additive()?;
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Equals => BinaryOperator::Equals,
+                TokenKind::NotEquals => BinaryOperator::NotEquals,
+                TokenKind::Less => BinaryOperator::Less,
+                TokenKind::LessEquals => BinaryOperator::LessEquals,
+                TokenKind::Greater => BinaryOperator::Greater,
+                TokenKind::GreaterEquals => BinaryOperator::GreaterEquals,
+                TokenKind::Keyword(Keyword::Is) => {
+                    self.advance();
+                    let negated = self.consume_keyword(Keyword::Not);
+                    self.expect_keyword(Keyword::Null)?;
+                    left = Expr::IsNull {
+                        operand: Box::new(left),
+                        negated,
+                    };
+                    continue;
+                }
+                _ => return Ok(left),
+            };
+            self.advance();
+            let right = self.additive()?;
+            left = binary(left, op, right);
+        }
+    }
+
+    /// additive := multiplicative ((+ | -) multiplicative)*
+    fn additive(&mut self) -> Result<Expr, SyntaxError> {
+        let mut left = self.multiplicative()?;
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Plus => BinaryOperator::Add,
+                TokenKind::Minus => BinaryOperator::Subtract,
+                _ => return Ok(left),
+            };
+            self.advance();
+            let right = self.multiplicative()?;
+            left = binary(left, op, right);
+        }
+    }
+
+    /// multiplicative := concat ((* | / | %) concat)*
+    fn multiplicative(&mut self) -> Result<Expr, SyntaxError> {
+        let mut left = self.concat()?;
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Star => BinaryOperator::Multiply,
+                TokenKind::Slash => BinaryOperator::Divide,
+                TokenKind::Percent => BinaryOperator::Modulo,
+                _ => return Ok(left),
+            };
+            self.advance();
+            let right = self.concat()?;
+            left = binary(left, op, right);
+        }
+    }
+
+    /// concat := unary (|| unary)*
+    fn concat(&mut self) -> Result<Expr, SyntaxError> {
+        let mut left = self.unary()?;
+        while self.consume(&TokenKind::Concat) {
+            let right = self.unary()?;
+            left = binary(left, BinaryOperator::Concat, right);
+        }
+        Ok(left)
+    }
+
+    /// unary := - unary | primary
+    fn unary(&mut self) -> Result<Expr, SyntaxError> {
+        if self.consume(&TokenKind::Minus) {
+            // The smallest integer is the only one whose digits alone are too
+            // large for 64 bits, so it is read without going through a real
+            let token = self.peek();
+            if &self.sql[token.start..token.end] == "9223372036854775808" {
+                self.advance();
+                return Ok(Expr::Literal(Literal::Integer(i64::MIN)));
+            }
+            let operand = self.unary()?;
+            return Ok(Expr::Unary {
+                op: UnaryOperator::Negate,
+                operand: Box::new(operand),
+            });
+        }
+        self.primary()
+    }
+
+    /// primary := number | string | NULL | column | ( expr )
+    fn primary(&mut self) -> Result<Expr, SyntaxError> {
+        let expr = match &self.peek().kind {
+            TokenKind::Integer(value) => Expr::Literal(Literal::Integer(*value)),
+            TokenKind::Real(value) => Expr::Literal(Literal::Real(*value)),
+            TokenKind::String(value) => Expr::Literal(Literal::String(value.clone())),
+            TokenKind::Blob(value) => Expr::Literal(Literal::Blob(value.clone())),
+            TokenKind::Keyword(Keyword::Null) => Expr::Literal(Literal::Null),
+            TokenKind::Identifier(_)
+                if self.tokens[self.position + 1].kind == TokenKind::LeftParen =>
+            {
+                return self.function_call();
+            }
+            TokenKind::Identifier(name) => Expr::Column(name.clone()),
+            TokenKind::LeftParen => {
+                self.advance();
+                let expr = self.expr()?;
+                self.expect(TokenKind::RightParen)?;
+                return Ok(expr);
+            }
+            _ => return Err(self.unexpected("an expression")),
+        };
+        self.advance();
+        Ok(expr)
+    }
+
+    /// function_call := name ( [* | expr, ...] )
+    fn function_call(&mut self) -> Result<Expr, SyntaxError> {
+        let token = self.advance();
+        let name = match &token.kind {
+            TokenKind::Identifier(name) => name.clone(),
+            _ => unreachable!("a function name is an identifier"),
+        };
+        let function = match AggregateFunction::from_name(&name) {
+            Some(function) => function,
+            None => return Err(self.error_at(&token, &format!("no such function '{}'", name))),
+        };
+
+        self.expect(TokenKind::LeftParen)?;
+        let mut args = Vec::new();
+        let star = function == AggregateFunction::Count && self.consume(&TokenKind::Star);
+        if !star && self.peek().kind != TokenKind::RightParen {
+            args.push(self.expr()?);
+            while self.consume(&TokenKind::Comma) {
+                args.push(self.expr()?);
+            }
+        }
+        self.expect(TokenKind::RightParen)?;
+
+        let (min_args, max_args) = function.num_args();
+        if args.len() < min_args || args.len() > max_args {
+            return Err(self.error_at(
+                &token,
+                &format!("wrong number of arguments to function '{}'", name),
+            ));
+        }
+        Ok(Expr::Aggregate { function, args })
+    }
+}
+
+/// Returns the expression applying the operator to both operands
+fn binary(left: Expr, op: BinaryOperator, right: Expr) -> Expr {
+    Expr::Binary {
+        left: Box::new(left),
+        op,
+        right: Box::new(right),
+    }
+}
@@ -0,0 +1,425 @@
+//! # Sorter
+//!
+//! Sorts the rows of a select by the terms of its `order by` clause.
+//! Rows are held in memory until they take up more than a limit, then they
+//! are sorted and written out as a run of temporary pages in a file of their
+//! own. Finally the runs are merged, reading a single page of each at a time.
+
+use crate::constants::{SORTER_MEMORY_LIMIT, SORTER_PAGE_SIZE};
+use crate::table::{read_u32, write_u32, Value};
+use std::cmp::Ordering;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{self, AtomicUsize};
+
+/// Used to give every temporary file of the process a different name
+static SPILL_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A row to be sorted, along with the values it is sorted by
+pub struct SortRecord {
+    pub keys: Vec<Value>,
+    pub values: Vec<Value>,
+}
+
+/// A struct collecting records and returning them in sorted order.
+/// Records with equal keys are returned in the order they were added.
+pub struct Sorter {
+    /// The direction of every key, true for descending
+    descending: Vec<bool>,
+    memory_limit: usize,
+    records: Vec<SortRecord>,
+    /// The encoded size of the records held in memory
+    memory_used: usize,
+    spill_file: Option<SpillFile>,
+    runs: Vec<Run>,
+}
+
+/// A sorted run of records stored in consecutive pages of the temporary file
+struct Run {
+    first_page: u64,
+    num_records: usize,
+}
+
+/// The temporary file holding the runs, removed once the sort is done
+struct SpillFile {
+    file: File,
+    path: PathBuf,
+    num_pages: u64,
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl Sorter {
+    /// Returns an empty sorter which spills to disk
+    /// once its records take up more than `SORTER_MEMORY_LIMIT` bytes
+    ///
+    /// # Arguments
+    ///
+    /// * `descending` - For every key, whether it is sorted in descending order
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rsqlite::table::Value;
+    /// use rsqlite::vm::sorter::{SortRecord, Sorter};
+    /// let mut sorter = Sorter::new(vec![true]);
+    /// for i in 1..4 {
+    ///     sorter.push(SortRecord {
+    ///         keys: vec![Value::Integer(i)],
+    ///         values: vec![Value::Integer(i * 10)],
+    ///     });
+    /// }
+    /// let rows: Vec<_> = sorter.sorted().collect();
+    /// assert_eq!(rows[0], vec![Value::Integer(30)]);
+    /// ```
+    pub fn new(descending: Vec<bool>) -> Self {
+        Sorter::with_memory_limit(descending, SORTER_MEMORY_LIMIT)
+    }
+
+    /// Returns an empty sorter which spills to disk
+    /// once its records take up more than the given number of bytes
+    ///
+    /// # Arguments
+    ///
+    /// * `descending` - For every key, whether it is sorted in descending order
+    /// * `memory_limit` - The number of bytes of records to hold in memory
+    pub fn with_memory_limit(descending: Vec<bool>, memory_limit: usize) -> Self {
+        Sorter {
+            descending,
+            memory_limit,
+            records: Vec::new(),
+            memory_used: 0,
+            spill_file: None,
+            runs: Vec::new(),
+        }
+    }
+
+    /// Adds a record to be sorted
+    ///
+    /// # Arguments
+    ///
+    /// * `record` - The record, holding one key for every sort direction
+    pub fn push(&mut self, record: SortRecord) {
+        self.memory_used += record_size(&record);
+        self.records.push(record);
+        if self.memory_used > self.memory_limit {
+            self.spill();
+        }
+    }
+
+    /// Returns the values of all the records, ordered by their keys
+    pub fn sorted(mut self) -> SortedRows {
+        if self.runs.is_empty() {
+            let descending = &self.descending;
+            self.records
+                .sort_by(|a, b| compare_keys(&a.keys, &b.keys, descending));
+            return SortedRows {
+                descending: self.descending,
+                records: self.records.into_iter(),
+                spill_file: None,
+                readers: Vec::new(),
+                heads: Vec::new(),
+            };
+        }
+
+        if !self.records.is_empty() {
+            self.spill();
+        }
+        let mut spill_file = self.spill_file.take().unwrap();
+        let mut readers: Vec<RunReader> = self
+            .runs
+            .iter()
+            .map(|run| RunReader {
+                next_page: run.first_page,
+                page: Vec::new(),
+                position: 0,
+                remaining: run.num_records,
+            })
+            .collect();
+        let heads = readers
+            .iter_mut()
+            .map(|reader| reader.next_record(&mut spill_file.file))
+            .collect();
+
+        SortedRows {
+            descending: self.descending,
+            records: Vec::new().into_iter(),
+            spill_file: Some(spill_file),
+            readers,
+            heads,
+        }
+    }
+
+    /// Sorts the records held in memory and writes them as a new run
+    /// at the end of the temporary file, creating the file if needed
+    fn spill(&mut self) {
+        let descending = &self.descending;
+        self.records
+            .sort_by(|a, b| compare_keys(&a.keys, &b.keys, descending));
+
+        let mut bytes = Vec::with_capacity(self.memory_used);
+        for record in &self.records {
+            encode_record(record, &mut bytes);
+        }
+        // Every run starts on a page of its own
+        let page_size = SORTER_PAGE_SIZE as usize;
+        bytes.resize(bytes.len().div_ceil(page_size) * page_size, 0);
+
+        let spill_file = self.spill_file.get_or_insert_with(SpillFile::create);
+        let first_page = spill_file.num_pages;
+        if spill_file
+            .file
+            .seek(SeekFrom::Start(first_page * SORTER_PAGE_SIZE as u64))
+            .is_err()
+            || spill_file.file.write_all(&bytes).is_err()
+        {
+            println!("Error writing temporary file.");
+            std::process::exit(1);
+        }
+        spill_file.num_pages += (bytes.len() / page_size) as u64;
+
+        self.runs.push(Run {
+            first_page,
+            num_records: self.records.len(),
+        });
+        self.records.clear();
+        self.memory_used = 0;
+    }
+}
+
+impl SpillFile {
+    /// Creates a new temporary file in the temporary directory of the system
+    fn create() -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "rsqlite-sort-{}-{}",
+            std::process::id(),
+            SPILL_FILE_COUNTER.fetch_add(1, atomic::Ordering::Relaxed)
+        ));
+        let file = match OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(file) => file,
+            Err(_) => {
+                println!("Error creating temporary file.");
+                std::process::exit(1);
+            }
+        };
+        SpillFile {
+            file,
+            path,
+            num_pages: 0,
+        }
+    }
+}
+
+/// An iterator over the values of sorted records
+pub struct SortedRows {
+    descending: Vec<bool>,
+    /// The records sorted in memory, used when nothing was spilled
+    records: std::vec::IntoIter<SortRecord>,
+    spill_file: Option<SpillFile>,
+    readers: Vec<RunReader>,
+    /// The next record of every run
+    heads: Vec<Option<SortRecord>>,
+}
+
+impl Iterator for SortedRows {
+    type Item = Vec<Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let spill_file = match &mut self.spill_file {
+            Some(spill_file) => spill_file,
+            None => return self.records.next().map(|record| record.values),
+        };
+
+        // Take the smallest head, earlier runs win ties to keep the sort stable
+        let mut smallest: Option<usize> = None;
+        for (i, head) in self.heads.iter().enumerate() {
+            let record = match head {
+                Some(record) => record,
+                None => continue,
+            };
+            let is_smaller = match smallest {
+                Some(j) => {
+                    let other = self.heads[j].as_ref().unwrap();
+                    compare_keys(&record.keys, &other.keys, &self.descending) == Ordering::Less
+                }
+                None => true,
+            };
+            if is_smaller {
+                smallest = Some(i);
+            }
+        }
+
+        let i = smallest?;
+        let next = self.readers[i].next_record(&mut spill_file.file);
+        let record = std::mem::replace(&mut self.heads[i], next).unwrap();
+        Some(record.values)
+    }
+}
+
+/// A struct reading the records of a run, one page at a time
+struct RunReader {
+    next_page: u64,
+    page: Vec<u8>,
+    /// The offset of the next byte to read in the current page
+    position: usize,
+    /// The number of records left in the run
+    remaining: usize,
+}
+
+impl RunReader {
+    /// Returns the next record of the run, or `None` at its end
+    fn next_record(&mut self, file: &mut File) -> Option<SortRecord> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        let num_keys = read_u32(&self.read(file, 4), 0) as usize;
+        let num_values = read_u32(&self.read(file, 4), 0) as usize;
+        let keys = (0..num_keys).map(|_| self.read_value(file)).collect();
+        let values = (0..num_values).map(|_| self.read_value(file)).collect();
+        Some(SortRecord { keys, values })
+    }
+
+    /// Decodes a single value
+    fn read_value(&mut self, file: &mut File) -> Value {
+        let tag = self.read(file, 1)[0];
+        match tag {
+            VALUE_NULL => Value::Null,
+            VALUE_INTEGER => Value::Integer(i64::from_le_bytes(self.read_array(file))),
+            VALUE_REAL => Value::Real(f64::from_le_bytes(self.read_array(file))),
+            VALUE_TEXT => {
+                let len = read_u32(&self.read(file, 4), 0) as usize;
+                Value::Text(String::from_utf8_lossy(&self.read(file, len)).into_owned())
+            }
+            _ => {
+                let len = read_u32(&self.read(file, 4), 0) as usize;
+                Value::Blob(self.read(file, len))
+            }
+        }
+    }
+
+    /// Reads eight bytes
+    fn read_array(&mut self, file: &mut File) -> [u8; 8] {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&self.read(file, 8));
+        bytes
+    }
+
+    /// Reads the given number of bytes, loading the following pages as needed
+    fn read(&mut self, file: &mut File, len: usize) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(len);
+        while bytes.len() < len {
+            if self.position == self.page.len() {
+                self.load_next_page(file);
+            }
+            let count = (len - bytes.len()).min(self.page.len() - self.position);
+            bytes.extend_from_slice(&self.page[self.position..self.position + count]);
+            self.position += count;
+        }
+        bytes
+    }
+
+    /// Replaces the current page with the next page of the run
+    fn load_next_page(&mut self, file: &mut File) {
+        self.page.resize(SORTER_PAGE_SIZE as usize, 0);
+        if file
+            .seek(SeekFrom::Start(self.next_page * SORTER_PAGE_SIZE as u64))
+            .is_err()
+            || file.read_exact(&mut self.page).is_err()
+        {
+            println!("Error reading temporary file.");
+            std::process::exit(1);
+        }
+        self.next_page += 1;
+        self.position = 0;
+    }
+}
+
+// Every value of a record is stored as one of the following tags,
+// followed by 8 bytes for numbers or by the length and bytes of text and blobs.
+
+const VALUE_NULL: u8 = 0;
+const VALUE_INTEGER: u8 = 1;
+const VALUE_REAL: u8 = 2;
+const VALUE_TEXT: u8 = 3;
+const VALUE_BLOB: u8 = 4;
+
+/// Compares the keys of two records, one sort direction per key
+///
+/// # Arguments
+///
+/// * `a` - The keys of the first record
+/// * `b` - The keys of the second record
+/// * `descending` - For every key, whether it is sorted in descending order
+pub fn compare_keys(a: &[Value], b: &[Value], descending: &[bool]) -> Ordering {
+    for ((a, b), &descending) in a.iter().zip(b).zip(descending) {
+        let ordering = a.compare(b);
+        if ordering != Ordering::Equal {
+            return if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            };
+        }
+    }
+    Ordering::Equal
+}
+
+/// Returns the number of bytes the record takes up once encoded
+fn record_size(record: &SortRecord) -> usize {
+    let value_size = |value: &Value| match value {
+        Value::Null => 1,
+        Value::Integer(_) | Value::Real(_) => 9,
+        Value::Text(text) => 5 + text.len(),
+        Value::Blob(bytes) => 5 + bytes.len(),
+    };
+    8 + record
+        .keys
+        .iter()
+        .chain(&record.values)
+        .map(value_size)
+        .sum::<usize>()
+}
+
+/// Appends the encoded record to the bytes
+fn encode_record(record: &SortRecord, bytes: &mut Vec<u8>) {
+    let mut header = [0u8; 8];
+    write_u32(&mut header, 0, record.keys.len() as u32);
+    write_u32(&mut header, 4, record.values.len() as u32);
+    bytes.extend_from_slice(&header);
+
+    for value in record.keys.iter().chain(&record.values) {
+        match value {
+            Value::Null => bytes.push(VALUE_NULL),
+            Value::Integer(value) => {
+                bytes.push(VALUE_INTEGER);
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            Value::Real(value) => {
+                bytes.push(VALUE_REAL);
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            Value::Text(text) => {
+                bytes.push(VALUE_TEXT);
+                bytes.extend_from_slice(&(text.len() as u32).to_le_bytes());
+                bytes.extend_from_slice(text.as_bytes());
+            }
+            Value::Blob(blob) => {
+                bytes.push(VALUE_BLOB);
+                bytes.extend_from_slice(&(blob.len() as u32).to_le_bytes());
+                bytes.extend_from_slice(blob);
+            }
+        }
+    }
+}
@@ -1,29 +1,285 @@
 //! # Statement
 //!
-//! An abstract interface for handling SQL statements
+//! The abstract syntax tree of SQL statements, produced by the parser
 
-use crate::table::Row;
+use crate::table::schema::Schema;
+use std::fmt;
 
-/// Enum to indicate the type of SQL statement
-pub enum StatementType {
-    Insert,
-    Select,
+/// Enum to hold a constant written in a statement
+#[derive(Clone, PartialEq, Debug)]
+pub enum Literal {
+    Null,
+    Integer(i64),
+    Real(f64),
+    String(String),
+    Blob(Vec<u8>),
+}
+
+/// Enum to indicate an operator applied to a single operand
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum UnaryOperator {
+    /// `-`
+    Negate,
+    /// `NOT`
+    Not,
+}
+
+/// Enum to indicate an operator applied to two operands
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BinaryOperator {
+    Equals,
+    NotEquals,
+    Less,
+    LessEquals,
+    Greater,
+    GreaterEquals,
+    And,
+    Or,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    /// `||`, joins the text of both operands
+    Concat,
+}
+
+/// Enum to indicate a function computing a single value from the rows of a group
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+    GroupConcat,
+}
+
+impl AggregateFunction {
+    /// Returns the function with the given name, ignoring case
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the function
+    pub fn from_name(name: &str) -> Option<Self> {
+        let function = match name.to_ascii_lowercase().as_str() {
+            "count" => AggregateFunction::Count,
+            "sum" => AggregateFunction::Sum,
+            "avg" => AggregateFunction::Avg,
+            "min" => AggregateFunction::Min,
+            "max" => AggregateFunction::Max,
+            "group_concat" => AggregateFunction::GroupConcat,
+            _ => return None,
+        };
+        Some(function)
+    }
+
+    /// Returns the smallest and largest number of arguments the function takes
+    pub fn num_args(&self) -> (usize, usize) {
+        match self {
+            // No argument stands for `count(*)`
+            AggregateFunction::Count => (0, 1),
+            AggregateFunction::GroupConcat => (1, 2),
+            _ => (1, 1),
+        }
+    }
+}
+
+impl fmt::Display for AggregateFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            AggregateFunction::Count => "count",
+            AggregateFunction::Sum => "sum",
+            AggregateFunction::Avg => "avg",
+            AggregateFunction::Min => "min",
+            AggregateFunction::Max => "max",
+            AggregateFunction::GroupConcat => "group_concat",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Enum to hold an expression
+#[derive(Clone, PartialEq, Debug)]
+pub enum Expr {
+    Literal(Literal),
+    /// The value of the named column in the current row
+    Column(String),
+    Unary {
+        op: UnaryOperator,
+        operand: Box<Expr>,
+    },
+    Binary {
+        left: Box<Expr>,
+        op: BinaryOperator,
+        right: Box<Expr>,
+    },
+    /// `<expr> IS NULL`, or `<expr> IS NOT NULL` when negated
+    IsNull {
+        operand: Box<Expr>,
+        negated: bool,
+    },
+    /// A call of an aggregate function, `count(*)` has no arguments
+    Aggregate {
+        function: AggregateFunction,
+        args: Vec<Expr>,
+    },
+}
+
+impl Expr {
+    /// Calls the function with the name of every column the expression refers to
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - The function to call
+    pub fn visit_columns<F: FnMut(&str)>(&self, f: &mut F) {
+        match self {
+            Expr::Literal(_) => (),
+            Expr::Column(name) => f(name),
+            Expr::Unary { operand, .. } | Expr::IsNull { operand, .. } => operand.visit_columns(f),
+            Expr::Binary { left, right, .. } => {
+                left.visit_columns(f);
+                right.visit_columns(f);
+            }
+            Expr::Aggregate { args, .. } => args.iter().for_each(|arg| arg.visit_columns(f)),
+        }
+    }
+
+    /// Calls the function with every call of an aggregate function in the expression,
+    /// without looking into the arguments of those calls
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - The function to call
+    pub fn visit_aggregates<'a, F: FnMut(&'a Expr)>(&'a self, f: &mut F) {
+        match self {
+            Expr::Literal(_) | Expr::Column(_) => (),
+            Expr::Unary { operand, .. } | Expr::IsNull { operand, .. } => {
+                operand.visit_aggregates(f)
+            }
+            Expr::Binary { left, right, .. } => {
+                left.visit_aggregates(f);
+                right.visit_aggregates(f);
+            }
+            Expr::Aggregate { .. } => f(self),
+        }
+    }
+}
+
+/// Struct that holds an insert statement, one list of expressions per row
+#[derive(Clone, PartialEq, Debug)]
+pub struct Insert {
+    pub table_name: String,
+    pub rows: Vec<Vec<Expr>>,
+}
+
+/// Enum to hold an entry of the list of columns a select returns
+#[derive(Clone, PartialEq, Debug)]
+pub enum ResultColumn {
+    /// `*`, every column of the table
+    Star,
+    /// An expression, named by its alias or by its text in the statement
+    Expr { expr: Expr, name: String },
+}
+
+/// Struct that holds an entry of the `order by` clause of a select
+#[derive(Clone, PartialEq, Debug)]
+pub struct OrderingTerm {
+    /// An expression, or the alias of a result column
+    pub expr: Expr,
+    pub descending: bool,
+}
+
+/// Struct that holds a select statement
+#[derive(Clone, PartialEq, Debug)]
+pub struct Select {
+    pub columns: Vec<ResultColumn>,
+    pub table_name: String,
+    /// Only rows for which the condition is true are returned
+    pub where_clause: Option<Expr>,
+    /// The rows are divided into groups with equal values of these expressions
+    pub group_by: Vec<Expr>,
+    /// Only groups for which the condition is true are returned
+    pub having: Option<Expr>,
+    /// The rows are returned in the order of the key when empty
+    pub order_by: Vec<OrderingTerm>,
+    /// The maximum number of rows to return, all rows if negative
+    pub limit: Option<Expr>,
+    /// The number of rows to skip before the first one returned
+    pub offset: Option<Expr>,
+}
+
+/// Struct that holds an update statement
+#[derive(Clone, PartialEq, Debug)]
+pub struct Update {
+    pub table_name: String,
+    /// Pairs of a column name and the expression computing its new value
+    pub assignments: Vec<(String, Expr)>,
+    /// Only rows for which the condition is true are updated, all rows without one
+    pub where_clause: Option<Expr>,
+}
+
+/// Struct that holds a delete statement
+#[derive(Clone, PartialEq, Debug)]
+pub struct Delete {
+    pub table_name: String,
+    /// Only rows for which the condition is true are deleted, all rows without one
+    pub where_clause: Option<Expr>,
+}
+
+/// Struct that holds a create index statement
+#[derive(Clone, PartialEq, Debug)]
+pub struct CreateIndex {
+    pub name: String,
+    pub table_name: String,
+    /// The names of the indexed columns, in the order entries are sorted by
+    pub columns: Vec<String>,
+    /// No two rows may have the same values in the indexed columns
+    pub unique: bool,
+    /// The text of the statement, stored in the catalog
+    pub sql: String,
+}
+
+/// Struct that holds a pragma statement, which reads or changes a setting of the database
+#[derive(Clone, PartialEq, Debug)]
+pub struct Pragma {
+    pub name: String,
+    /// The new value of the setting, the setting is read without one
+    pub value: Option<Expr>,
+}
+
+/// Enum that holds a parsed SQL statement
+#[derive(Clone, Debug)]
+pub enum Statement {
+    /// `create table`, the schema is checked while parsing
+    CreateTable(Schema),
+    CreateIndex(CreateIndex),
+    /// `drop index`, holding the name of the index
+    DropIndex(String),
+    Insert(Insert),
+    Select(Select),
+    Update(Update),
+    Delete(Delete),
+    Pragma(Pragma),
+    /// `begin`, starting a transaction
+    Begin,
+    /// `commit`, ending a transaction and keeping its changes
+    Commit,
+    /// `rollback`, ending a transaction and undoing its changes
+    Rollback,
+    /// A statement made only of whitespace and comments
     Empty,
 }
 
-/// Struct that holds the type of SQL statement and relevant data
-pub struct Statement {
-    pub stmt_type: StatementType,
-    pub row_to_insert: Row,
+impl Default for Statement {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Statement {
-    /// Returns a `Statement` struct with an empty statement
-    /// and empty row
+    /// Returns an empty statement
     pub fn new() -> Self {
-        Statement {
-            stmt_type: StatementType::Empty,
-            row_to_insert: Row::new(),
-        }
+        Statement::Empty
     }
 }
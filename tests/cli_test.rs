@@ -1,6 +1,9 @@
 use assert_cmd::Command;
 use predicates::prelude::*;
 
+/// Statement creating the table most of the tests insert into
+const CREATE_USERS: &str = "create table users (id integer, username text(32), email text(255))\n";
+
 fn clear_db_file(filename: &str) {
     std::process::Command::new("rm")
         .arg("-rf")
@@ -11,52 +14,69 @@ fn clear_db_file(filename: &str) {
 
 #[test]
 fn insert_single_row() -> Result<(), Box<dyn std::error::Error>> {
-    clear_db_file("test.db");
+    clear_db_file("insert_single_row.db");
     let mut cmd = Command::cargo_bin("rsqlite").unwrap();
-    cmd.arg("test.db")
-        .write_stdin("insert 1 alice foo@example.com\n.exit\n")
+    cmd.arg("insert_single_row.db")
+        .write_stdin(format!(
+            "{}insert into users values (1, 'alice', 'foo@example.com')\n.exit\n",
+            CREATE_USERS
+        ))
         .assert()
         .success()
-        .stdout(predicate::eq("db > Executed.\ndb > "));
-    clear_db_file("test.db");
+        .stdout(predicate::eq("db > Executed.\ndb > Executed.\ndb > "));
+    clear_db_file("insert_single_row.db");
     Ok(())
 }
 
 #[test]
-fn insert_more_than_max_rows() -> Result<(), Box<dyn std::error::Error>> {
-    clear_db_file("test.db");
+fn insert_more_than_one_page_of_rows() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("insert_more_than_one_page_of_rows.db");
     let mut cmd = Command::cargo_bin("rsqlite").unwrap();
-    let mut cmd_str = String::new();
+    let mut cmd_str = String::from(CREATE_USERS);
 
     for i in 1..1402 {
-        cmd_str.push_str(&format!("insert {} user{} person{}@example.com\n", i, i, i));
+        cmd_str.push_str(&format!(
+            "insert into users values ({}, 'user{}', 'person{}@example.com')\n",
+            i, i, i
+        ));
     }
 
-    cmd_str.push_str(".exit\n");
+    cmd_str.push_str("select * from users\n.exit\n");
 
-    let assert = cmd.arg("test.db").write_stdin(cmd_str).assert();
+    let assert = cmd
+        .arg("insert_more_than_one_page_of_rows.db")
+        .write_stdin(cmd_str)
+        .assert();
     let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
     let op: Vec<&str> = output_str.split('\n').collect();
 
-    assert_eq!(op[op.len() - 2], "db > Error: Table full.");
-    clear_db_file("test.db");
+    assert_eq!(op[1401], "db > Executed.");
+    assert_eq!(op[1402], "db > (1, user1, person1@example.com)");
+    assert_eq!(op[2802], "(1401, user1401, person1401@example.com)");
+    clear_db_file("insert_more_than_one_page_of_rows.db");
     Ok(())
 }
 
 #[test]
 fn insert_max_length_fields() -> Result<(), Box<dyn std::error::Error>> {
-    clear_db_file("test.db");
+    clear_db_file("insert_max_length_fields.db");
     let mut cmd = Command::cargo_bin("rsqlite").unwrap();
     let long_username = "a".repeat(32);
     let long_email = "a".repeat(255);
     let op_str = &format!("db > (1, {}, {})", long_username, long_email);
-    let expected_op: Vec<&str> = vec!["db > Executed.", op_str, "Executed.", "db > "];
+    let expected_op: Vec<&str> = vec![
+        "db > Executed.",
+        "db > Executed.",
+        op_str,
+        "Executed.",
+        "db > ",
+    ];
 
     let assert = cmd
-        .arg("test.db")
+        .arg("insert_max_length_fields.db")
         .write_stdin(format!(
-            "insert 1 {} {}\nselect\n.exit\n",
-            long_username, long_email
+            "{}insert into users values (1, '{}', '{}')\nselect * from users\n.exit\n",
+            CREATE_USERS, long_username, long_email
         ))
         .assert();
 
@@ -64,23 +84,28 @@ fn insert_max_length_fields() -> Result<(), Box<dyn std::error::Error>> {
     let op: Vec<&str> = output_str.split('\n').collect();
 
     assert_eq!(op, expected_op);
-    clear_db_file("test.db");
+    clear_db_file("insert_max_length_fields.db");
     Ok(())
 }
 
 #[test]
 fn insert_large_fields() -> Result<(), Box<dyn std::error::Error>> {
-    clear_db_file("test.db");
+    clear_db_file("insert_large_fields.db");
     let mut cmd = Command::cargo_bin("rsqlite").unwrap();
     let long_username = "a".repeat(33);
     let long_email = "a".repeat(256);
-    let expected_op: Vec<&str> = vec!["db > String is too long.", "db > Executed.", "db > "];
+    let expected_op: Vec<&str> = vec![
+        "db > Executed.",
+        "db > String is too long.",
+        "db > Executed.",
+        "db > ",
+    ];
 
     let assert = cmd
-        .arg("test.db")
+        .arg("insert_large_fields.db")
         .write_stdin(format!(
-            "insert 1 {} {}\nselect\n.exit\n",
-            long_username, long_email
+            "{}insert into users values (1, '{}', '{}')\nselect * from users\n.exit\n",
+            CREATE_USERS, long_username, long_email
         ))
         .assert();
 
@@ -88,38 +113,55 @@ fn insert_large_fields() -> Result<(), Box<dyn std::error::Error>> {
     let op: Vec<&str> = output_str.split('\n').collect();
 
     assert_eq!(op, expected_op);
-    clear_db_file("test.db");
+    clear_db_file("insert_large_fields.db");
     Ok(())
 }
 
 #[test]
 fn insert_negative_id() -> Result<(), Box<dyn std::error::Error>> {
-    clear_db_file("test.db");
+    clear_db_file("insert_negative_id.db");
     let mut cmd = Command::cargo_bin("rsqlite").unwrap();
-    let expected_op: Vec<&str> = vec!["db > ID must be positive.", "db > Executed.", "db > "];
+    let expected_op: Vec<&str> = vec![
+        "db > Executed.",
+        "db > Executed.",
+        "db > Executed.",
+        "db > (-5000000000, big, big@example.com)",
+        "(-1, test, test@example.com)",
+        "(1, one, one@example.com)",
+        "Executed.",
+        "db > ",
+    ];
 
     let assert = cmd
-        .arg("test.db")
-        .write_stdin("insert -1 test test@example.com\nselect\n.exit\n")
+        .arg("insert_negative_id.db")
+        .write_stdin(format!(
+            "{}insert into users values (-1, 'test', 'test@example.com'), (1, 'one', 'one@example.com')\n\
+             insert into users values (-5000000000, 'big', 'big@example.com')\n\
+             select * from users\n.exit\n",
+            CREATE_USERS
+        ))
         .assert();
 
     let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
     let op: Vec<&str> = output_str.split('\n').collect();
 
     assert_eq!(op, expected_op);
-    clear_db_file("test.db");
+    clear_db_file("insert_negative_id.db");
     Ok(())
 }
 
 #[test]
 fn check_persistence() -> Result<(), Box<dyn std::error::Error>> {
-    clear_db_file("test.db");
+    clear_db_file("check_persistence.db");
     let mut cmd = Command::cargo_bin("rsqlite").unwrap();
-    let expected_op1: Vec<&str> = vec!["db > Executed.", "db > "];
+    let expected_op1: Vec<&str> = vec!["db > Executed.", "db > Executed.", "db > "];
 
     let assert = cmd
-        .arg("test.db")
-        .write_stdin("insert 1 user1 user1@example.com\n.exit\n")
+        .arg("check_persistence.db")
+        .write_stdin(format!(
+            "{}insert into users values (1, 'user1', 'user1@example.com')\n.exit\n",
+            CREATE_USERS
+        ))
         .assert();
 
     let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
@@ -129,7 +171,10 @@ fn check_persistence() -> Result<(), Box<dyn std::error::Error>> {
 
     let expected_op2: Vec<&str> = vec!["db > (1, user1, user1@example.com)", "Executed.", "db > "];
 
-    let assert = cmd.arg("test.db").write_stdin("select\n.exit\n").assert();
+    let assert = cmd
+        .arg("check_persistence.db")
+        .write_stdin("select * from users\n.exit\n")
+        .assert();
 
     println!("{:#?}", assert);
 
@@ -137,6 +182,1805 @@ fn check_persistence() -> Result<(), Box<dyn std::error::Error>> {
     let op2: Vec<&str> = output_str.split('\n').collect();
 
     assert_eq!(op2, expected_op2);
-    clear_db_file("test.db");
+    clear_db_file("check_persistence.db");
+    Ok(())
+}
+
+#[test]
+fn select_returns_rows_sorted_by_id() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("select_returns_rows_sorted_by_id.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let expected_op: Vec<&str> = vec![
+        "db > Executed.",
+        "db > Executed.",
+        "db > Executed.",
+        "db > Executed.",
+        "db > (1, user1, person1@example.com)",
+        "(2, user2, person2@example.com)",
+        "(3, user3, person3@example.com)",
+        "Executed.",
+        "db > ",
+    ];
+
+    let assert = cmd
+        .arg("select_returns_rows_sorted_by_id.db")
+        .write_stdin(format!(
+            "{}insert into users values (3, 'user3', 'person3@example.com')\n\
+             insert into users values (1, 'user1', 'person1@example.com')\n\
+             insert into users values (2, 'user2', 'person2@example.com')\n\
+             select * from users\n.exit\n",
+            CREATE_USERS
+        ))
+        .assert();
+
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').collect();
+
+    assert_eq!(op, expected_op);
+    clear_db_file("select_returns_rows_sorted_by_id.db");
+    Ok(())
+}
+
+#[test]
+fn print_btree_with_three_leaf_nodes() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("print_btree_with_three_leaf_nodes.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let mut cmd_str = String::from(CREATE_USERS);
+
+    // Rows as large as the table allows, 13 of them fit into a leaf
+    let username = "u".repeat(32);
+    let email = "e".repeat(255);
+    for i in 1..22 {
+        cmd_str.push_str(&format!(
+            "insert into users values ({}, '{}', '{}')\n",
+            i, username, email
+        ));
+    }
+
+    cmd_str.push_str(".btree\n.exit\n");
+
+    let assert = cmd
+        .arg("print_btree_with_three_leaf_nodes.db")
+        .write_stdin(cmd_str)
+        .assert();
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').skip(22).collect();
+
+    let mut expected_op = vec!["Tree:", "- internal (size 2)", "  - leaf (size 7)"];
+    let keys: Vec<String> = (1..22).map(|i| format!("    - {}", i)).collect();
+    expected_op.extend(keys[0..7].iter().map(|k| k.as_str()));
+    expected_op.extend(vec!["  - key 7", "  - leaf (size 7)"]);
+    expected_op.extend(keys[7..14].iter().map(|k| k.as_str()));
+    expected_op.extend(vec!["  - key 14", "  - leaf (size 7)"]);
+    expected_op.extend(keys[14..21].iter().map(|k| k.as_str()));
+    expected_op.push("db > ");
+
+    assert_eq!(op[0], "db > Tree:");
+    assert_eq!(op[1..], expected_op[1..]);
+    clear_db_file("print_btree_with_three_leaf_nodes.db");
+    Ok(())
+}
+
+#[test]
+fn short_rows_share_a_leaf() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("short_rows_share_a_leaf.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let mut cmd_str = String::from(CREATE_USERS);
+
+    // Records only take the bytes their values need, so short rows of a
+    // table with wide columns still fit a hundred to a leaf
+    for i in 1..101 {
+        cmd_str.push_str(&format!(
+            "insert into users values ({}, 'u{}', 'e{}')\n",
+            i, i, i
+        ));
+    }
+    cmd_str.push_str(".btree\nselect * from users where id = 100\n.exit\n");
+
+    let assert = cmd
+        .arg("short_rows_share_a_leaf.db")
+        .write_stdin(cmd_str)
+        .assert();
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').skip(101).collect();
+
+    assert_eq!(op[0], "db > Tree:");
+    assert_eq!(op[1], "- leaf (size 100)");
+    assert_eq!(op[102], "db > (100, u100, e100)");
+    clear_db_file("short_rows_share_a_leaf.db");
+    Ok(())
+}
+
+#[test]
+fn split_internal_nodes_and_persist() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("split_internal_nodes_and_persist.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let mut cmd_str = String::from(CREATE_USERS);
+
+    // Enough rows, in descending order, for the root internal node to split
+    for i in (1..5001).rev() {
+        cmd_str.push_str(&format!(
+            "insert into users values ({}, 'user{}', 'person{}@example.com')\n",
+            i, i, i
+        ));
+    }
+
+    cmd_str.push_str(".exit\n");
+
+    cmd.arg("split_internal_nodes_and_persist.db")
+        .write_stdin(cmd_str)
+        .assert()
+        .success();
+
+    let assert = cmd
+        .arg("split_internal_nodes_and_persist.db")
+        .write_stdin("select * from users\n.exit\n")
+        .assert();
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let ids: Vec<u32> = output_str
+        .split('\n')
+        .filter_map(|line| line.trim_start_matches("db > ").strip_prefix('('))
+        .map(|line| line.split(',').next().unwrap().parse().unwrap())
+        .collect();
+
+    assert_eq!(ids, (1..5001).collect::<Vec<u32>>());
+    clear_db_file("split_internal_nodes_and_persist.db");
+    Ok(())
+}
+
+#[test]
+fn select_from_empty_table() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("select_from_empty_table.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let expected_op: Vec<&str> = vec!["db > Executed.", "db > Executed.", "db > "];
+
+    let assert = cmd
+        .arg("select_from_empty_table.db")
+        .write_stdin(format!("{}select * from users\n.exit\n", CREATE_USERS))
+        .assert();
+
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').collect();
+
+    assert_eq!(op, expected_op);
+    clear_db_file("select_from_empty_table.db");
+    Ok(())
+}
+
+#[test]
+fn insert_duplicate_id() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("insert_duplicate_id.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let expected_op: Vec<&str> = vec![
+        "db > Executed.",
+        "db > Executed.",
+        "db > Error: Duplicate key.",
+        "db > (1, user1, person1@example.com)",
+        "Executed.",
+        "db > ",
+    ];
+
+    let assert = cmd
+        .arg("insert_duplicate_id.db")
+        .write_stdin(format!(
+            "{}insert into users values (1, 'user1', 'person1@example.com')\n\
+             insert into users values (1, 'user1', 'person1@example.com')\n\
+             select * from users\n.exit\n",
+            CREATE_USERS
+        ))
+        .assert();
+
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').collect();
+
+    assert_eq!(op, expected_op);
+    clear_db_file("insert_duplicate_id.db");
+    Ok(())
+}
+
+#[test]
+fn insert_duplicate_id_after_split() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("insert_duplicate_id_after_split.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let mut cmd_str = String::from(CREATE_USERS);
+
+    for i in 1..101 {
+        cmd_str.push_str(&format!(
+            "insert into users values ({}, 'user{}', 'person{}@example.com')\n",
+            i, i, i
+        ));
+    }
+
+    // Ids from the first, a middle and the last leaf
+    for i in &[1, 50, 100] {
+        cmd_str.push_str(&format!(
+            "insert into users values ({}, 'user{}', 'person{}@example.com')\n",
+            i, i, i
+        ));
+    }
+
+    cmd_str.push_str(".exit\n");
+
+    let assert = cmd
+        .arg("insert_duplicate_id_after_split.db")
+        .write_stdin(cmd_str)
+        .assert();
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').collect();
+
+    assert_eq!(op[101..104], ["db > Error: Duplicate key."; 3]);
+    clear_db_file("insert_duplicate_id_after_split.db");
+    Ok(())
+}
+
+#[test]
+fn database_file_starts_with_header() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("database_file_starts_with_header.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+
+    cmd.arg("database_file_starts_with_header.db")
+        .write_stdin(format!(
+            "{}insert into users values (1, 'user1', 'person1@example.com')\n.exit\n",
+            CREATE_USERS
+        ))
+        .assert()
+        .success();
+
+    let bytes = std::fs::read("database_file_starts_with_header.db")?;
+
+    assert_eq!(&bytes[0..16], b"rsqlite format\0\0");
+    // Header page, catalog page and a single leaf page
+    assert_eq!(bytes.len(), 3 * 4096);
+    clear_db_file("database_file_starts_with_header.db");
+    Ok(())
+}
+
+#[test]
+fn open_file_which_is_not_a_database() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("open_file_which_is_not_a_database.db");
+    std::fs::write("open_file_which_is_not_a_database.db", vec![b'x'; 4096])?;
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+
+    cmd.arg("open_file_which_is_not_a_database.db")
+        .write_stdin("select * from users\n.exit\n")
+        .assert()
+        .failure()
+        .stdout(predicate::eq("Error: file is not a database.\n"));
+
+    clear_db_file("open_file_which_is_not_a_database.db");
+    Ok(())
+}
+
+#[test]
+fn open_file_from_future_version() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("open_file_from_future_version.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+
+    cmd.arg("open_file_from_future_version.db")
+        .write_stdin(".exit\n")
+        .assert()
+        .success();
+
+    // Overwrite the format version stored right after the magic string
+    let mut bytes = std::fs::read("open_file_from_future_version.db")?;
+    bytes[16..20].copy_from_slice(&99u32.to_le_bytes());
+    std::fs::write("open_file_from_future_version.db", bytes)?;
+
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    cmd.arg("open_file_from_future_version.db")
+        .write_stdin("select * from users\n.exit\n")
+        .assert()
+        .failure()
+        .stdout(predicate::eq(
+            "Error: unsupported database format version 99.\n",
+        ));
+
+    clear_db_file("open_file_from_future_version.db");
+    Ok(())
+}
+
+#[test]
+fn open_file_from_old_version() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("open_file_from_old_version.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+
+    cmd.arg("open_file_from_old_version.db")
+        .write_stdin(format!(
+            "{}insert into users values (1, 'alice', 'a@x.com')\n.exit\n",
+            CREATE_USERS
+        ))
+        .assert()
+        .success();
+
+    // A file written before the last change of the layout must not be misread
+    let mut bytes = std::fs::read("open_file_from_old_version.db")?;
+    bytes[16..20].copy_from_slice(&1u32.to_le_bytes());
+    std::fs::write("open_file_from_old_version.db", bytes)?;
+
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    cmd.arg("open_file_from_old_version.db")
+        .write_stdin("select * from users\n.exit\n")
+        .assert()
+        .failure()
+        .stdout(predicate::eq(
+            "Error: unsupported database format version 1.\n",
+        ));
+
+    clear_db_file("open_file_from_old_version.db");
+    Ok(())
+}
+
+#[test]
+fn integers_are_stored_little_endian() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("integers_are_stored_little_endian.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+
+    cmd.arg("integers_are_stored_little_endian.db")
+        .write_stdin(format!(
+            "{}insert into users values (16909060, 'user1', 'person1@example.com')\n.exit\n",
+            CREATE_USERS
+        ))
+        .assert()
+        .success();
+
+    let bytes = std::fs::read("integers_are_stored_little_endian.db")?;
+
+    // Format version and page size in the header
+    assert_eq!(&bytes[16..20], &[10, 0, 0, 0]);
+    assert_eq!(&bytes[20..24], &[0x00, 0x10, 0x00, 0x00]);
+    // Offset of the only cell of the root leaf on page 2
+    let page = &bytes[2 * 4096..3 * 4096];
+    let offset = u32::from_le_bytes([page[22], page[23], page[24], page[25]]) as usize;
+    // The cell holds the key, the length of the record and the record, whose
+    // header is followed by the id, which takes 4 bytes
+    let cell = &page[offset..];
+    assert_eq!(&cell[0..8], &[0x04, 0x03, 0x02, 0x01, 0, 0, 0, 0]);
+    assert_eq!(&cell[8..12], &[32, 0, 0, 0]);
+    assert_eq!(&cell[12..14], &[4, 4]);
+    assert_eq!(&cell[16..20], &[0x04, 0x03, 0x02, 0x01]);
+    clear_db_file("integers_are_stored_little_endian.db");
+    Ok(())
+}
+
+#[test]
+fn create_table_with_custom_columns() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("create_table_with_custom_columns.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let expected_op: Vec<&str> = vec![
+        "db > Executed.",
+        "db > Executed.",
+        "db > Executed.",
+        "db > (Widget, 3, blue)",
+        "(Gadget, 7, red)",
+        "Executed.",
+        "db > ",
+    ];
+
+    let assert = cmd
+        .arg("create_table_with_custom_columns.db")
+        .write_stdin(
+            "create table products (name text(16), sku integer primary key, color varchar(8))\n\
+             insert into products values ('Gadget', 7, 'red')\n\
+             insert into products values ('Widget', 3, 'blue')\n\
+             select * from products\n.exit\n",
+        )
+        .assert();
+
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').collect();
+
+    assert_eq!(op, expected_op);
+    clear_db_file("create_table_with_custom_columns.db");
+    Ok(())
+}
+
+#[test]
+fn schema_persists_across_sessions() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("schema_persists_across_sessions.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+
+    cmd.arg("schema_persists_across_sessions.db")
+        .write_stdin(
+            "create table points (x integer, label text(4))\ninsert into points values (1, 'a')\n.exit\n",
+        )
+        .assert()
+        .success();
+
+    let expected_op: Vec<&str> = vec![
+        "db > Executed.",
+        "db > Error: Table already exists.",
+        "db > String is too long.",
+        "db > (1, a)",
+        "(2, b)",
+        "Executed.",
+        "db > ",
+    ];
+
+    let assert = cmd
+        .arg("schema_persists_across_sessions.db")
+        .write_stdin(
+            "insert into points values (2, 'b')\n\
+             create table points (x integer)\n\
+             insert into points values (3, 'abcde')\n\
+             select * from points\n.exit\n",
+        )
+        .assert();
+
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').collect();
+
+    assert_eq!(op, expected_op);
+    clear_db_file("schema_persists_across_sessions.db");
+    Ok(())
+}
+
+#[test]
+fn statements_before_create_table() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("statements_before_create_table.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let expected_op: Vec<&str> = vec![
+        "db > Error: No such table.",
+        "db > Error: No such table.",
+        "db > ",
+    ];
+
+    let assert = cmd
+        .arg("statements_before_create_table.db")
+        .write_stdin("insert into users values (1, 'user1', 'person1@example.com')\nselect * from users\n.exit\n")
+        .assert();
+
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').collect();
+
+    assert_eq!(op, expected_op);
+    clear_db_file("statements_before_create_table.db");
+    Ok(())
+}
+
+#[test]
+fn create_invalid_tables() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("create_invalid_tables.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let expected_op: Vec<&str> = vec![
+        "db > Syntax error at line 1, column 19: unknown type 'date'.",
+        "db > Syntax error at line 1, column 17: primary key must be an integer.",
+        "db > Syntax error at line 1, column 28: duplicate column name 'a'.",
+        "db > ",
+    ];
+
+    let assert = cmd
+        .arg("create_invalid_tables.db")
+        .write_stdin(
+            "create table t (a date)\n\
+             create table t (name text(8) primary key, id integer)\n\
+             create table t (a integer, a integer)\n\
+             .exit\n",
+        )
+        .assert();
+
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').collect();
+
+    assert_eq!(op, expected_op);
+    clear_db_file("create_invalid_tables.db");
+    Ok(())
+}
+
+#[test]
+fn select_from_catalog_table() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("select_from_catalog_table.db");
+    let catalog_row = "db > (table, users, 2, \
+         create table users (id integer, username text(32), email text(255)))";
+
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let expected_op: Vec<&str> = vec![
+        "db > Executed.",
+        "db > Executed.",
+        "db > Error: No such table.",
+        "db > Error: Table already exists.",
+        "db > ",
+    ];
+    let assert = cmd
+        .arg("select_from_catalog_table.db")
+        .write_stdin(format!(
+            "select * from rsqlite_schema\n{}select * from sessions\n\
+             create table rsqlite_schema (id integer)\n.exit\n",
+            CREATE_USERS
+        ))
+        .assert();
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').collect();
+    assert_eq!(op, expected_op);
+
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let expected_op: Vec<&str> = vec![catalog_row, "Executed.", "db > Executed.", "db > "];
+    let assert = cmd
+        .arg("select_from_catalog_table.db")
+        .write_stdin("select * from rsqlite_schema\nselect * from users\n.exit\n")
+        .assert();
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').collect();
+    assert_eq!(op, expected_op);
+
+    clear_db_file("select_from_catalog_table.db");
+    Ok(())
+}
+
+#[test]
+fn multiple_tables_in_one_file() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("multiple_tables_in_one_file.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let mut cmd_str = String::from(CREATE_USERS);
+    cmd_str.push_str("create table sessions (token integer, user_id integer)\n");
+    for i in 1..101 {
+        cmd_str.push_str(&format!(
+            "insert into users values ({}, 'user{}', 'person{}@example.com')\n\
+             insert into sessions values ({}, {})\n",
+            i,
+            i,
+            i,
+            1000 + i,
+            i
+        ));
+    }
+    cmd_str.push_str(".exit\n");
+    cmd.arg("multiple_tables_in_one_file.db")
+        .write_stdin(cmd_str)
+        .assert()
+        .success();
+
+    let expected_op: Vec<&str> = vec![
+        "db > (1001, 1)",
+        "(1002, 2)",
+        "(1100, 100)",
+        "Executed.",
+        "db > Error: Table may not be modified.",
+        "db > Error: No such table.",
+        "db > (table, users, 2, \
+         create table users (id integer, username text(32), email text(255)))",
+        "(table, sessions, 3, create table sessions (token integer, user_id integer))",
+        "Executed.",
+        "db > ",
+    ];
+    let assert = cmd
+        .arg("multiple_tables_in_one_file.db")
+        .write_stdin(
+            "select * from sessions\n\
+             insert into rsqlite_schema values ('table', 't', 9, 'x')\n\
+             insert into audit values (1)\n\
+             select * from rsqlite_schema\n\
+             .exit\n",
+        )
+        .assert();
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').collect();
+
+    assert_eq!(op.len(), 97 + expected_op.len());
+    assert_eq!(op[..2], expected_op[..2]);
+    assert_eq!(op[99..], expected_op[2..]);
+
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let assert = cmd
+        .arg("multiple_tables_in_one_file.db")
+        .write_stdin("select * from users\n.exit\n")
+        .assert();
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').collect();
+
+    assert_eq!(op[0], "db > (1, user1, person1@example.com)");
+    assert_eq!(op[99], "(100, user100, person100@example.com)");
+    assert_eq!(op[100], "Executed.");
+
+    clear_db_file("multiple_tables_in_one_file.db");
+    Ok(())
+}
+
+#[test]
+fn parse_quoted_strings_keywords_and_comments() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("parse_quoted_strings_keywords_and_comments.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let expected_op: Vec<&str> = vec![
+        "db > Executed.",
+        "db > Executed.",
+        "db > Executed.",
+        "db > (-9223372036854775808, smallest)",
+        "(1, hello, world)",
+        "(2, it's  spaced)",
+        "Executed.",
+        "db > (-9223372036854775808, 9.223372036854776e18)",
+        "Executed.",
+        "db > Syntax error at line 1, column 1: expected a statement but found 'se'.",
+        "db > Syntax error at line 1, column 14: expected a name but found end of input.",
+        "db > Syntax error at line 1, column 27: unterminated string.",
+        "db > ",
+    ];
+
+    let assert = cmd
+        .arg("parse_quoted_strings_keywords_and_comments.db")
+        .write_stdin(
+            "CREATE TABLE notes (id INTEGER PRIMARY KEY, body TEXT(64));\n\
+             Insert  Into notes VALUES (1, 'hello, world'), (2, 'it''s  spaced') -- two rows\n\
+             insert into notes values (-9223372036854775808, 'smallest')\n\
+             /* everything */ SELECT * FROM Notes;\n\
+             select -9223372036854775808, 9223372036854775808 from notes where id = 1\n\
+             se\n\
+             select * from\n\
+             insert into notes values ('oops)\n\
+             .exit\n",
+        )
+        .assert();
+
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').collect();
+
+    assert_eq!(op, expected_op);
+    clear_db_file("parse_quoted_strings_keywords_and_comments.db");
+    Ok(())
+}
+
+#[test]
+fn select_with_where_clause() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("select_with_where_clause.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let expected_op: Vec<&str> = vec![
+        "db > Executed.",
+        "db > Executed.",
+        "db > (2, user2, person2@example.com)",
+        "Executed.",
+        "db > (2, user2, person2@example.com)",
+        "(3, user3, person3@example.com)",
+        "Executed.",
+        "db > (1, user1, person1@example.com)",
+        "(4, user4, person4@example.com)",
+        "Executed.",
+        "db > Executed.",
+        "db > (3, user3, person3@example.com)",
+        "Executed.",
+        "db > Error: No such column: age.",
+        "db > Syntax error at line 1, column 31: expected an expression but found end of input.",
+        "db > ",
+    ];
+
+    let assert = cmd
+        .arg("select_with_where_clause.db")
+        .write_stdin(format!(
+            "{}insert into users values \
+             (1, 'user1', 'person1@example.com'), (2, 'user2', 'person2@example.com'), \
+             (3, 'user3', 'person3@example.com'), (4, 'user4', 'person4@example.com')\n\
+             select * from users where id = 2\n\
+             select * from users where id >= 2 AND id < 4\n\
+             select * from users where NOT (id > 1 and id <= 3) or username != username\n\
+             select * from users where email IS NULL\n\
+             select * from users where (id <> 1 and username = 'user3') or null\n\
+             select * from users where age = 1\n\
+             select * from users where id =\n\
+             .exit\n",
+            CREATE_USERS
+        ))
+        .assert();
+
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').collect();
+
+    assert_eq!(op, expected_op);
+    clear_db_file("select_with_where_clause.db");
+    Ok(())
+}
+
+#[test]
+fn select_columns_and_expressions() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("select_columns_and_expressions.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let expected_op: Vec<&str> = vec![
+        "db > Executed.",
+        "db > Executed.",
+        "db > (user1, 1)",
+        "(user2, 2)",
+        "Executed.",
+        "db > db > id, handle, id * 10 + 1, id / 2, id / 0",
+        "(2, @user2, 21, 1, NULL)",
+        "Executed.",
+        "db > id, username, email, half",
+        "(1, user1, person1@example.com, 0.5)",
+        "Executed.",
+        "db > Error: No such column: name.",
+        "db > ",
+    ];
+
+    let assert = cmd
+        .arg("select_columns_and_expressions.db")
+        .write_stdin(format!(
+            "{}insert into users values \
+             (1, 'user1', 'person1@example.com'), (2, 'user2', 'person2@example.com')\n\
+             select username, id from users\n\
+             .headers on\n\
+             select id, '@' || username AS handle, id * 10 + 1, id / 2, id / 0 \
+             from users where id = 2\n\
+             select *, id / 2.0 half from users where id = 1\n\
+             select name from users\n\
+             .exit\n",
+            CREATE_USERS
+        ))
+        .assert();
+
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').collect();
+
+    assert_eq!(op, expected_op);
+    clear_db_file("select_columns_and_expressions.db");
+    Ok(())
+}
+
+#[test]
+fn delete_rows_and_reuse_freed_pages() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("delete_rows_and_reuse_freed_pages.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let mut cmd_str = String::from(CREATE_USERS);
+    for i in 1..1401 {
+        cmd_str.push_str(&format!(
+            "insert into users values ({}, 'user{}', 'person{}@example.com')\n",
+            i, i, i
+        ));
+    }
+    cmd_str.push_str(
+        "delete from users where id > 3 and id != 1400\n\
+         delete from users where id = 1400\n\
+         delete from users where id = 1400\n\
+         select id, username from users\n\
+         .btree\n\
+         delete from rsqlite_schema\n\
+         delete from audit where id = 1\n\
+         delete from users where name = 'x'\n\
+         .exit\n",
+    );
+
+    let assert = cmd
+        .arg("delete_rows_and_reuse_freed_pages.db")
+        .write_stdin(cmd_str)
+        .assert();
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').collect();
+
+    let expected_op: Vec<&str> = vec![
+        "db > Executed. 1396 rows affected.",
+        "db > Executed. 1 row affected.",
+        "db > Executed. 0 rows affected.",
+        "db > (1, user1)",
+        "(2, user2)",
+        "(3, user3)",
+        "Executed.",
+        "db > Tree:",
+        "- leaf (size 3)",
+        "  - 1",
+        "  - 2",
+        "  - 3",
+        "db > Error: Table may not be modified.",
+        "db > Error: No such table.",
+        "db > Error: No such column: name.",
+        "db > ",
+    ];
+    assert_eq!(op[1401..], expected_op[..]);
+
+    // Rows inserted again go into the pages freed by the delete
+    let file_length = std::fs::metadata("delete_rows_and_reuse_freed_pages.db")?.len();
+    let mut cmd_str = String::new();
+    for i in 4..1401 {
+        cmd_str.push_str(&format!(
+            "insert into users values ({}, 'user{}', 'person{}@example.com')\n",
+            i, i, i
+        ));
+    }
+    cmd_str.push_str("select * from users where id % 700 = 0\n.exit\n");
+
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let assert = cmd
+        .arg("delete_rows_and_reuse_freed_pages.db")
+        .write_stdin(cmd_str)
+        .assert();
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').collect();
+
+    assert_eq!(op[1397], "db > (700, user700, person700@example.com)");
+    assert_eq!(op[1398], "(1400, user1400, person1400@example.com)");
+    assert_eq!(
+        std::fs::metadata("delete_rows_and_reuse_freed_pages.db")?.len(),
+        file_length
+    );
+    clear_db_file("delete_rows_and_reuse_freed_pages.db");
+    Ok(())
+}
+
+#[test]
+fn update_rows_and_keys() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("update_rows_and_keys.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let mut cmd_str = String::from(CREATE_USERS);
+    for i in 1..501 {
+        cmd_str.push_str(&format!(
+            "insert into users values ({}, 'user{}', 'person{}@example.com')\n",
+            i, i, i
+        ));
+    }
+    cmd_str.push_str(
+        "update users set username = username || '-renamed', email = 'x' where id % 100 = 0\n\
+         update users set id = id + 1000 where id > 250\n\
+         update users set id = 1 where id = 2\n\
+         update users set id = 'three' where id = 3\n\
+         update users set username = username || '-is-now-longer-than-thirty-two-bytes' where id = 1\n\
+         update users set id = 2 - id, username = id where id < 3\n\
+         update users set missing = 1\n\
+         update rsqlite_schema set name = 'x'\n\
+         select * from users where id < 3 or id % 100 = 0\n\
+         .exit\n",
+    );
+
+    let assert = cmd
+        .arg("update_rows_and_keys.db")
+        .write_stdin(cmd_str)
+        .assert();
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').collect();
+
+    let expected_op: Vec<&str> = vec![
+        "db > Executed. 5 rows affected.",
+        "db > Executed. 250 rows affected.",
+        "db > Error: Duplicate key.",
+        "db > Error: Value does not match the type of its column.",
+        "db > String is too long.",
+        "db > Executed. 2 rows affected.",
+        "db > Error: No such column: missing.",
+        "db > Error: Table may not be modified.",
+        "db > (0, 2, person2@example.com)",
+        "(1, 1, person1@example.com)",
+        "(100, user100-renamed, x)",
+        "(200, user200-renamed, x)",
+        "(1300, user300-renamed, x)",
+        "(1400, user400-renamed, x)",
+        "(1500, user500-renamed, x)",
+        "Executed.",
+        "db > ",
+    ];
+    assert_eq!(op[501..], expected_op[..]);
+
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let assert = cmd
+        .arg("update_rows_and_keys.db")
+        .write_stdin("select id from users where id > 248 and id < 1252\n.exit\n")
+        .assert();
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').collect();
+
+    assert_eq!(
+        op,
+        vec!["db > (249)", "(250)", "(1251)", "Executed.", "db > "]
+    );
+    clear_db_file("update_rows_and_keys.db");
+    Ok(())
+}
+
+#[test]
+fn select_with_order_by_limit_and_offset() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("select_with_order_by_limit_and_offset.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let mut cmd_str = String::from(CREATE_USERS);
+    // Long emails make the rows sorted by email take up more memory
+    // than the sorter holds, so they are sorted in runs on disk
+    let padding = "x".repeat(200);
+    for i in 1..1001 {
+        cmd_str.push_str(&format!(
+            "insert into users values ({}, 'user{}', '{}{}@example.com')\n",
+            i,
+            i,
+            padding,
+            i % 7
+        ));
+    }
+    cmd_str.push_str(
+        "select id, substr from users order by email desc, id desc limit 3\n\
+         select id from users order by email desc, id desc limit 3\n\
+         select id, id % 3 as r from users order by r desc, id limit 2 offset 1\n\
+         select id from users order by id desc limit 2 offset 1\n\
+         select id from users order by username limit 3\n\
+         select id from users where id > 990 limit 2\n\
+         select id from users limit 2 offset -5\n\
+         select id from users order by id limit 'a'\n\
+         select id from users limit id\n\
+         .exit\n",
+    );
+
+    let assert = cmd
+        .arg("select_with_order_by_limit_and_offset.db")
+        .write_stdin(cmd_str)
+        .assert();
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').collect();
+
+    let expected_op: Vec<&str> = vec![
+        "db > Error: No such column: substr.",
+        "db > (1000)",
+        "(993)",
+        "(986)",
+        "Executed.",
+        "db > (5, 2)",
+        "(8, 2)",
+        "Executed.",
+        "db > (999)",
+        "(998)",
+        "Executed.",
+        "db > (1)",
+        "(10)",
+        "(100)",
+        "Executed.",
+        "db > (991)",
+        "(992)",
+        "Executed.",
+        "db > (1)",
+        "(2)",
+        "Executed.",
+        "db > Error: Value does not match the type of its column.",
+        "db > Error: No such column: id.",
+        "db > ",
+    ];
+    assert_eq!(op[1001..], expected_op[..]);
+    clear_db_file("select_with_order_by_limit_and_offset.db");
+    Ok(())
+}
+
+#[test]
+fn select_with_aggregates_and_group_by() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("select_with_aggregates_and_group_by.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let cmd_str = "create table staff (id integer, name text(16), dept text(8), salary integer)\n\
+                   select count(*), count(id), sum(salary), avg(salary), max(name) from staff\n\
+                   insert into staff values (1, 'ann', 'ops', 10), (2, 'bob', 'dev', 20), \
+                   (3, 'cid', 'ops', 30), (4, 'dee', 'hr', 5), (5, 'eve', 'dev', 40)\n\
+                   select count(*), sum(salary), avg(salary), min(name), max(salary) from staff\n\
+                   select group_concat(name), group_concat(id, '-') from staff where id < 4\n\
+                   select dept, count(*) as n, sum(salary) from staff group by dept order by n desc, dept\n\
+                   select dept, max(salary) - min(salary) from staff group by dept having count(*) > 1\n\
+                   select dept from staff group by dept order by sum(salary) limit 1 offset 1\n\
+                   select count(*) + 1 from staff where id > 10\n\
+                   select sum(salary + 9223372036854775000) from staff where id < 3\n\
+                   select dept, sum(salary + 9223372036854775000) from staff group by dept\n\
+                   select id from staff where sum(salary) > 10\n\
+                   select sum(max(salary)) from staff\n\
+                   select total(salary) from staff\n\
+                   select count(id, salary) from staff\n\
+                   .exit\n";
+
+    let assert = cmd
+        .arg("select_with_aggregates_and_group_by.db")
+        .write_stdin(cmd_str)
+        .assert();
+
+    assert.success().stdout(
+        "db > Executed.\n\
+         db > (0, 0, NULL, NULL, NULL)\n\
+         Executed.\n\
+         db > Executed.\n\
+         db > (5, 105, 21.0, ann, 40)\n\
+         Executed.\n\
+         db > (ann,bob,cid, 1-2-3)\n\
+         Executed.\n\
+         db > (dev, 2, 60)\n\
+         (ops, 2, 40)\n\
+         (hr, 1, 5)\n\
+         Executed.\n\
+         db > (dev, 20)\n\
+         (ops, 20)\n\
+         Executed.\n\
+         db > (ops)\n\
+         Executed.\n\
+         db > (1)\n\
+         Executed.\n\
+         db > Error: Integer overflow.\n\
+         db > Error: Integer overflow.\n\
+         db > Error: Misuse of aggregate function sum().\n\
+         db > Error: Misuse of aggregate function max().\n\
+         db > Syntax error at line 1, column 8: no such function 'total'.\n\
+         db > Syntax error at line 1, column 8: wrong number of arguments to function 'count'.\n\
+         db > ",
+    );
+
+    clear_db_file("select_with_aggregates_and_group_by.db");
+    Ok(())
+}
+
+#[test]
+fn create_use_and_drop_indexes() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("create_use_and_drop_indexes.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let mut cmd_str = String::from(CREATE_USERS);
+    cmd_str.push_str(
+        "create index users_username on users (username)\n\
+         create unique index users_email on users (email)\n",
+    );
+    // Enough rows for the indexes to split into several levels
+    for i in 1..2001 {
+        cmd_str.push_str(&format!(
+            "insert into users values ({}, 'user{}', 'person{}@example.com')\n",
+            i,
+            i % 100,
+            i
+        ));
+    }
+    cmd_str.push_str(
+        "insert into users values (2001, 'user1', 'person7@example.com')\n\
+         update users set email = 'person8@example.com' where id = 9\n\
+         update users set email = id || '@example.org' where id > 1990\n\
+         delete from users where username = 'user3' and id < 1000\n\
+         create unique index users_username_2 on users (username)\n\
+         create index users on users (id)\n\
+         create index users_email on users (id)\n\
+         create index broken on users (age)\n\
+         .exit\n",
+    );
+
+    let assert = cmd
+        .arg("create_use_and_drop_indexes.db")
+        .write_stdin(cmd_str)
+        .assert();
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').skip(2003).collect();
+    let expected_op: Vec<&str> = vec![
+        "db > Error: Duplicate values in unique index users_email.",
+        "db > Error: Duplicate values in unique index users_email.",
+        "db > Executed. 10 rows affected.",
+        "db > Executed. 10 rows affected.",
+        "db > Error: Duplicate values in unique index users_username_2.",
+        "db > Error: Table already exists.",
+        "db > Error: Index already exists.",
+        "db > Error: No such column: age.",
+        "db > ",
+    ];
+    assert_eq!(op, expected_op);
+
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let assert = cmd
+        .arg("create_use_and_drop_indexes.db")
+        .write_stdin(
+            "select id from users where username = 'user3'\n\
+             select id from users where email >= '1995@' and email < '1997@'\n\
+             select count(*) from users where username > 'user97'\n\
+             select type, name, rootpage from rsqlite_schema\n\
+             drop index users_email\n\
+             drop index users_email\n\
+             select id from users where email = 'person1989@example.com'\n\
+             select type, name from rsqlite_schema\n\
+             .exit\n",
+        )
+        .assert();
+
+    let mut expected_op = String::from("db > ");
+    for id in (1003..2001).step_by(100) {
+        expected_op.push_str(&format!("({})\n", id));
+    }
+    expected_op.push_str(
+        "Executed.\n\
+         db > (1995)\n\
+         (1996)\n\
+         Executed.\n\
+         db > (40)\n\
+         Executed.\n\
+         db > (table, users, 2)\n\
+         (index, users_username, 3)\n\
+         (index, users_email, 4)\n\
+         Executed.\n\
+         db > Executed.\n\
+         db > Error: No such index.\n\
+         db > (1989)\n\
+         Executed.\n\
+         db > (table, users)\n\
+         (index, users_username)\n\
+         Executed.\n\
+         db > ",
+    );
+    assert.success().stdout(expected_op);
+
+    clear_db_file("create_use_and_drop_indexes.db");
+    Ok(())
+}
+
+#[test]
+fn unique_indexes_allow_repeated_nulls() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("unique_indexes_allow_repeated_nulls.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    cmd.arg("unique_indexes_allow_repeated_nulls.db")
+        .write_stdin(
+            "create table t (id integer, v text(8), w integer)\n\
+             insert into t values (1, 'a', 1), (2, null, 1), (3, null, 2)\n\
+             create unique index t_v on t (v)\n\
+             insert into t values (4, null, 3)\n\
+             insert into t values (5, 'a', 3)\n\
+             update t set v = null where id = 1\n\
+             create unique index t_v_w on t (v, w)\n\
+             select count(*) from t where v is null\n\
+             .exit\n",
+        )
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "db > Executed.\ndb > Executed.\ndb > Executed.\ndb > Executed.\n\
+             db > Error: Duplicate values in unique index t_v.\n\
+             db > Executed. 1 row affected.\ndb > Executed.\ndb > (4)\nExecuted.\ndb > ",
+        ));
+    clear_db_file("unique_indexes_allow_repeated_nulls.db");
+    Ok(())
+}
+
+#[test]
+fn store_typed_values_with_affinity() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("store_typed_values_with_affinity.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let expected_op: Vec<&str> = vec![
+        "db > Executed.",
+        "db > Executed.",
+        "db > Executed.",
+        "db > Executed.",
+        "db > Error: Value does not match the type of its column.",
+        "db > String is too long.",
+        "db > Syntax error at line 1, column 38: malformed blob x'abc'.",
+        "db > Error: Value does not match the type of its column.",
+        "db > Executed.",
+        "db > (1, 9.99, apple, x'00ff10', -3)",
+        "(2, 4.0, 12.5, ab, 7)",
+        "(3, NULL, NULL, 42, 2.5)",
+        "Executed.",
+        "db > (1, 19.98, -2)",
+        "(2, 8.0, 8)",
+        "Executed.",
+        "db > (1, x'00ff10')",
+        "(2, ab)",
+        "Executed.",
+        "db > ",
+    ];
+
+    let assert = cmd
+        .arg("store_typed_values_with_affinity.db")
+        .write_stdin(
+            "create table items (id integer, price real, name text(10), data blob(6), qty integer)\n\
+             insert into items values (1, 9.99, 'apple', x'00ff10', -3)\n\
+             insert into items values ('2', '4', 12.5, 'ab', '7.0')\n\
+             insert into items values (3, null, null, 42, 2.5)\n\
+             insert into items values (4, 'cheap', 'x', null, 1)\n\
+             insert into items values (5, 1, 'y', x'00112233445566', 1)\n\
+             insert into items values (6, 1, 'y', x'abc', 1)\n\
+             insert into items values (null, 1, 'no key', null, 1)\n\
+             create index items_data on items (data)\n\
+             select * from items\n\
+             select id, price * 2, qty + 1 from items where qty < 0 or price > 3\n\
+             select id, data from items where data > 'a'\n\
+             .exit\n",
+        )
+        .assert();
+
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').collect();
+
+    assert_eq!(op, expected_op);
+    clear_db_file("store_typed_values_with_affinity.db");
+    Ok(())
+}
+
+#[test]
+fn find_negative_ids_through_indexes() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("find_negative_ids_through_indexes.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    cmd.arg("find_negative_ids_through_indexes.db")
+        .write_stdin(
+            "create table t (id integer, n integer, v text(4))\n\
+             create index t_n on t (n)\n\
+             create unique index t_v on t (v)\n\
+             insert into t values (-1, 0, 'a'), (-132, 0, 'b'), (3, 0, 'c')\n\
+             select id from t where n = 0\n\
+             insert into t values (2, 1, 'a')\n\
+             update t set v = 'b' where id = 3\n\
+             delete from t where n = 0\n\
+             select count(*) from t\n\
+             .exit\n",
+        )
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "db > Executed.\ndb > Executed.\ndb > Executed.\ndb > Executed.\n\
+             db > (-132)\n(-1)\n(3)\nExecuted.\n\
+             db > Error: Duplicate values in unique index t_v.\n\
+             db > Error: Duplicate values in unique index t_v.\n\
+             db > Executed. 3 rows affected.\ndb > (0)\nExecuted.\ndb > ",
+        ));
+    clear_db_file("find_negative_ids_through_indexes.db");
+    Ok(())
+}
+
+#[test]
+fn declare_text_and_blob_without_size() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("declare_text_and_blob_without_size.db");
+    let long_text = "a".repeat(5000);
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    cmd.arg("declare_text_and_blob_without_size.db")
+        .write_stdin(format!(
+            "create table docs (id integer primary key, body text, data blob)\n\
+             insert into docs values (1, '{}', x'0102')\n\
+             select id, data from docs where body = '{}'\n\
+             create unique index docs_body on docs (body)\n\
+             .exit\n",
+            long_text, long_text
+        ))
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "db > Executed.\ndb > Executed.\ndb > (1, x'0102')\nExecuted.\n\
+             db > Executed.\ndb > ",
+        ));
+
+    // Long values sharing their first bytes are told apart by the index
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    cmd.arg("declare_text_and_blob_without_size.db")
+        .write_stdin(format!(
+            "insert into docs values (2, '{}b', null)\n\
+             insert into docs values (3, '{}', null)\n\
+             update docs set body = '{}' where id = 2\n\
+             select id from docs where body = '{}b'\n\
+             select id from docs where body > '{}'\n\
+             .exit\n",
+            long_text, long_text, long_text, long_text, long_text
+        ))
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "db > Executed.\n\
+             db > Error: Duplicate values in unique index docs_body.\n\
+             db > Error: Duplicate values in unique index docs_body.\n\
+             db > (2)\nExecuted.\n\
+             db > (2)\nExecuted.\n\
+             db > ",
+        ));
+
+    // The values are compared whole when the index is created
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    cmd.arg("declare_text_and_blob_without_size.db")
+        .write_stdin(format!(
+            "drop index docs_body\n\
+             insert into docs values (3, '{}', null)\n\
+             create unique index docs_body on docs (body)\n\
+             create index docs_body on docs (body)\n\
+             select id from docs where body = '{}' order by id\n\
+             .exit\n",
+            long_text, long_text
+        ))
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "db > Executed.\ndb > Executed.\n\
+             db > Error: Duplicate values in unique index docs_body.\n\
+             db > Executed.\n\
+             db > (1)\n(3)\nExecuted.\n\
+             db > ",
+        ));
+    clear_db_file("declare_text_and_blob_without_size.db");
+    Ok(())
+}
+
+#[test]
+fn store_values_in_overflow_pages() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("store_values_in_overflow_pages.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+
+    // Values spanning several pages, the rest of them going into overflow pages
+    let body = "a".repeat(30000) + "b";
+    let data = "ab".repeat(10000);
+    let assert = cmd
+        .arg("store_values_in_overflow_pages.db")
+        .write_stdin(format!(
+            "create table docs (id integer, body text(100000), data blob(20000))\n\
+             insert into docs values (1, '{}', x'{}')\n\
+             insert into docs values (2, 'short', null)\n\
+             update docs set body = body || 'c' where id = 1\n\
+             select id from docs where body > 'b'\n\
+             .exit\n",
+            body, data
+        ))
+        .assert();
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').collect();
+    assert_eq!(
+        op,
+        vec![
+            "db > Executed.",
+            "db > Executed.",
+            "db > Executed.",
+            "db > Executed. 1 row affected.",
+            "db > (2)",
+            "Executed.",
+            "db > ",
+        ]
+    );
+
+    // The values are read back whole after reopening the file
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let assert = cmd
+        .arg("store_values_in_overflow_pages.db")
+        .write_stdin("select * from docs\n.exit\n")
+        .assert();
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').collect();
+    assert_eq!(op[0], format!("db > (1, {}c, x'{}')", body, data));
+    assert_eq!(op[1], "(2, short, NULL)");
+
+    // The overflow pages of a deleted row are reused
+    let file_length = std::fs::metadata("store_values_in_overflow_pages.db")?.len();
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    cmd.arg("store_values_in_overflow_pages.db")
+        .write_stdin(format!(
+            "delete from docs where id = 1\n\
+             insert into docs values (3, '{}', x'{}')\n\
+             .exit\n",
+            body, data
+        ))
+        .assert()
+        .success();
+    assert_eq!(
+        std::fs::metadata("store_values_in_overflow_pages.db")?.len(),
+        file_length
+    );
+    clear_db_file("store_values_in_overflow_pages.db");
+    Ok(())
+}
+
+#[test]
+fn create_table_with_long_definition() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("create_table_with_long_definition.db");
+    // The create statement is too large for a leaf node of the catalog
+    let columns: Vec<String> = (0..200)
+        .map(|i| format!("column_number_{} text(16)", i))
+        .collect();
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    cmd.arg("create_table_with_long_definition.db")
+        .write_stdin(format!(
+            "create table wide (id integer, {})\n.exit\n",
+            columns.join(", ")
+        ))
+        .assert()
+        .success()
+        .stdout(predicate::eq("db > Executed.\ndb > "));
+
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    cmd.arg("create_table_with_long_definition.db")
+        .write_stdin(format!(
+            "insert into wide values (1{}, 'last')\n\
+             select id, column_number_199 from wide\n.exit\n",
+            ", null".repeat(199)
+        ))
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "db > Executed.\ndb > (1, last)\nExecuted.\ndb > ",
+        ));
+    clear_db_file("create_table_with_long_definition.db");
+    Ok(())
+}
+
+#[test]
+fn choose_page_size() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("choose_page_size.db");
+
+    // A new file made of pages of the size given on the command line
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let assert = cmd
+        .args(["--page-size", "512", "choose_page_size.db"])
+        .write_stdin(format!(
+            "pragma page_size\n{}insert into users values (1, 'user1', 'person1@example.com')\n\
+             pragma page_size = 1024\n\
+             pragma page_size = 1000\n\
+             pragma encoding\n\
+             .exit\n",
+            CREATE_USERS
+        ))
+        .assert();
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').collect();
+    assert_eq!(
+        op,
+        vec![
+            "db > (512)",
+            "Executed.",
+            "db > Executed.",
+            "db > Executed.",
+            "db > Error: Page size can only be changed before tables are created.",
+            "db > Error: Page size must be a power of two between 512 and 65536.",
+            "db > Error: Unknown pragma: encoding.",
+            "db > ",
+        ]
+    );
+
+    let bytes = std::fs::read("choose_page_size.db")?;
+    assert_eq!(&bytes[20..24], &512u32.to_le_bytes());
+    assert_eq!(bytes.len(), 3 * 512);
+
+    // The file keeps its page size, whatever the command line says
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    cmd.args(["--page-size", "8192", "choose_page_size.db"])
+        .write_stdin("pragma page_size\nselect * from users\n.exit\n")
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "db > (512)\nExecuted.\ndb > (1, user1, person1@example.com)\nExecuted.\ndb > ",
+        ));
+    clear_db_file("choose_page_size.db");
+
+    // The page size of an empty database is changed by a pragma
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    cmd.arg("choose_page_size.db")
+        .write_stdin(format!("pragma page_size = 65536\n{}.exit\n", CREATE_USERS))
+        .assert()
+        .success();
+    let bytes = std::fs::read("choose_page_size.db")?;
+    assert_eq!(&bytes[20..24], &65536u32.to_le_bytes());
+    assert_eq!(bytes.len(), 3 * 65536);
+
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    cmd.args(["--page-size", "1000", "choose_page_size.db"])
+        .assert()
+        .failure()
+        .stdout(predicate::eq(
+            "Page size must be a power of two between 512 and 65536.\n",
+        ));
+    clear_db_file("choose_page_size.db");
+    Ok(())
+}
+
+#[test]
+fn evict_pages_from_a_small_cache() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("evict_pages_from_a_small_cache.db");
+
+    // The tree spans far more pages than the cache holds
+    let mut cmd_str = format!(
+        "pragma cache_size\npragma cache_size = 0\npragma cache_size = 4\npragma cache_size\n\
+         {}create index by_name on users (username)\n",
+        CREATE_USERS
+    );
+    for i in 1..=1000 {
+        cmd_str.push_str(&format!(
+            "insert into users values ({}, 'user{}', 'person{}@example.com')\n",
+            i, i, i
+        ));
+    }
+    cmd_str.push_str(
+        "delete from users where id > 900\n\
+         select count(*) from users\n\
+         select * from users where username = 'user777'\n\
+         .exit\n",
+    );
+
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let assert = cmd
+        .args(["--page-size", "512", "evict_pages_from_a_small_cache.db"])
+        .write_stdin(cmd_str)
+        .assert();
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').collect();
+    assert_eq!(
+        op[..5],
+        [
+            "db > (2000)",
+            "Executed.",
+            "db > Error: Cache size must be a positive number of pages.",
+            "db > Executed.",
+            "db > (4)",
+        ]
+    );
+    assert_eq!(
+        op[op.len() - 6..],
+        [
+            "db > Executed. 100 rows affected.",
+            "db > (900)",
+            "Executed.",
+            "db > (777, user777, person777@example.com)",
+            "Executed.",
+            "db > ",
+        ]
+    );
+
+    // Pages evicted before the exit were written to the file
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    cmd.arg("evict_pages_from_a_small_cache.db")
+        .write_stdin(
+            "pragma cache_size\nselect count(*) from users\n\
+             select * from users where username = 'user900'\n.exit\n",
+        )
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "db > (2000)\nExecuted.\ndb > (900)\nExecuted.\n\
+             db > (900, user900, person900@example.com)\nExecuted.\ndb > ",
+        ));
+    clear_db_file("evict_pages_from_a_small_cache.db");
+    Ok(())
+}
+
+#[test]
+fn write_only_changed_pages() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("write_only_changed_pages.db");
+    let mut cmd_str = String::from(CREATE_USERS);
+    for i in 1..=200 {
+        cmd_str.push_str(&format!(
+            "insert into users values ({}, 'user{}', 'person{}@example.com')\n",
+            i, i, i
+        ));
+    }
+    cmd_str.push_str(".exit\n");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    cmd.arg("write_only_changed_pages.db")
+        .write_stdin(cmd_str)
+        .assert()
+        .success();
+    let modified = std::fs::metadata("write_only_changed_pages.db")?.modified()?;
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    // Reading loads pages without changing them, so nothing is written
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    cmd.arg("write_only_changed_pages.db")
+        .write_stdin("select count(*) from users\nselect * from users where id = 150\n.exit\n")
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "db > (200)\nExecuted.\ndb > (150, user150, person150@example.com)\nExecuted.\ndb > ",
+        ));
+    assert_eq!(
+        std::fs::metadata("write_only_changed_pages.db")?.modified()?,
+        modified
+    );
+
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    cmd.arg("write_only_changed_pages.db")
+        .write_stdin("insert into users values (201, 'user201', 'person201@example.com')\n.exit\n")
+        .assert()
+        .success();
+    assert_ne!(
+        std::fs::metadata("write_only_changed_pages.db")?.modified()?,
+        modified
+    );
+    clear_db_file("write_only_changed_pages.db");
+    Ok(())
+}
+
+#[test]
+fn roll_back_interrupted_transaction() -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::{BufRead, BufReader, Write};
+
+    clear_db_file("roll_back_interrupted_transaction.db");
+    clear_db_file("roll_back_interrupted_transaction.db-journal");
+    let mut cmd_str = String::from(CREATE_USERS);
+    for i in 1..=50 {
+        cmd_str.push_str(&format!(
+            "insert into users values ({}, 'user{}', 'person{}@example.com')\n",
+            i, i, i
+        ));
+    }
+    cmd_str.push_str(".exit\n");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    cmd.arg("roll_back_interrupted_transaction.db")
+        .write_stdin(cmd_str)
+        .assert()
+        .success();
+    let committed = std::fs::read("roll_back_interrupted_transaction.db")?;
+
+    // A small cache writes changed pages to the file before the commit,
+    // then the process is killed instead of committing
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin("rsqlite"))
+        .arg("roll_back_interrupted_transaction.db")
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()?;
+    let mut cmd_str = String::from("pragma cache_size = 2\nbegin\n");
+    for i in 51..=500 {
+        cmd_str.push_str(&format!(
+            "insert into users values ({}, 'user{}', 'person{}@example.com')\n",
+            i, i, i
+        ));
+    }
+    cmd_str.push_str("select count(*) from users\n");
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(cmd_str.as_bytes())?;
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut line = String::new();
+    while !line.contains("(500)") {
+        line.clear();
+        assert_ne!(stdout.read_line(&mut line)?, 0);
+    }
+    assert_ne!(
+        std::fs::read("roll_back_interrupted_transaction.db")?,
+        committed
+    );
+    assert!(std::path::Path::new("roll_back_interrupted_transaction.db-journal").exists());
+    child.kill()?;
+    child.wait()?;
+
+    // Opening the file again replays the journal
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    cmd.arg("roll_back_interrupted_transaction.db")
+        .write_stdin("select count(*) from users\n.exit\n")
+        .assert()
+        .success()
+        .stdout(predicate::eq("db > (50)\nExecuted.\ndb > "));
+    assert!(!std::path::Path::new("roll_back_interrupted_transaction.db-journal").exists());
+    assert_eq!(
+        std::fs::read("roll_back_interrupted_transaction.db")?,
+        committed
+    );
+    clear_db_file("roll_back_interrupted_transaction.db");
+    Ok(())
+}
+
+#[test]
+fn write_ahead_log_mode() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("write_ahead_log_mode.db");
+    clear_db_file("write_ahead_log_mode.db-wal");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let assert = cmd
+        .arg("write_ahead_log_mode.db")
+        .write_stdin(format!(
+            "pragma journal_mode = memory\npragma journal_mode = wal\npragma journal_mode\n\
+             begin\n{}insert into users values (1, 'user1', 'person1@example.com')\n\
+             insert into users values (2, 'user2', 'person2@example.com')\n\
+             commit\npragma page_size = 1024\n.exit\n",
+            CREATE_USERS
+        ))
+        .assert();
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').collect();
+    assert_eq!(
+        op,
+        vec![
+            "db > Error: Journal mode must be delete or wal.",
+            "db > Executed.",
+            "db > (wal)",
+            "Executed.",
+            "db > Executed.",
+            "db > Executed.",
+            "db > Executed.",
+            "db > Executed.",
+            "db > Executed.",
+            "db > Error: Page size cannot be changed in WAL mode.",
+            "db > ",
+        ]
+    );
+
+    // The commit went to the log, the file only holds the header and the catalog
+    assert_eq!(std::fs::read("write_ahead_log_mode.db")?.len(), 2 * 4096);
+    let mut log = std::fs::read("write_ahead_log_mode.db-wal")?;
+    assert_eq!(log.len(), 16 + 3 * (16 + 4096));
+
+    // A frame torn by a crash is ignored
+    log.extend_from_slice(&[0xff; 100]);
+    std::fs::write("write_ahead_log_mode.db-wal", log)?;
+
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    cmd.arg("write_ahead_log_mode.db")
+        .write_stdin("select * from users\npragma wal_checkpoint\n.exit\n")
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "db > (1, user1, person1@example.com)\n(2, user2, person2@example.com)\nExecuted.\n\
+             db > (0, 3, 3)\nExecuted.\ndb > ",
+        ));
+    assert_eq!(std::fs::read("write_ahead_log_mode.db")?.len(), 3 * 4096);
+    assert_eq!(std::fs::read("write_ahead_log_mode.db-wal")?.len(), 16);
+
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    cmd.arg("write_ahead_log_mode.db")
+        .write_stdin("insert into users values (3, 'user3', 'person3@example.com')\npragma journal_mode = delete\n.exit\n")
+        .assert()
+        .success()
+        .stdout(predicate::eq("db > Executed.\ndb > Executed.\ndb > "));
+    assert!(!std::path::Path::new("write_ahead_log_mode.db-wal").exists());
+
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    cmd.arg("write_ahead_log_mode.db")
+        .write_stdin(
+            "select * from users where id = 3\npragma journal_mode\npragma wal_checkpoint\n.exit\n",
+        )
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "db > (3, user3, person3@example.com)\nExecuted.\ndb > (delete)\nExecuted.\n\
+             db > (0, -1, -1)\nExecuted.\ndb > ",
+        ));
+    clear_db_file("write_ahead_log_mode.db");
+    Ok(())
+}
+
+#[test]
+fn begin_commit_and_rollback_transactions() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("begin_commit_and_rollback_transactions.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    let assert = cmd
+        .arg("begin_commit_and_rollback_transactions.db")
+        .write_stdin(format!(
+            "commit\nbegin transaction\nbegin\n{}\
+             insert into users values (1, 'user1', 'person1@example.com')\n\
+             pragma journal_mode = wal\nrollback\nselect * from users\n\
+             {}begin\ninsert into users values (2, 'user2', 'person2@example.com')\n\
+             commit transaction\nrollback\n\
+             insert into users values (3, 'user3', 'person3@example.com')\n\
+             begin\ninsert into users values (4, 'user4', 'person4@example.com')\n\
+             select * from users\n.exit\n",
+            CREATE_USERS, CREATE_USERS
+        ))
+        .assert();
+    let output_str = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let op: Vec<&str> = output_str.split('\n').collect();
+    assert_eq!(
+        op,
+        vec![
+            "db > Error: No transaction is active.",
+            "db > Executed.",
+            "db > Error: Cannot start a transaction within a transaction.",
+            "db > Executed.",
+            "db > Executed.",
+            "db > Error: Cannot change the journal mode within a transaction.",
+            "db > Executed.",
+            "db > Error: No such table.",
+            "db > Executed.",
+            "db > Executed.",
+            "db > Executed.",
+            "db > Executed.",
+            "db > Error: No transaction is active.",
+            "db > Executed.",
+            "db > Executed.",
+            "db > Executed.",
+            "db > (2, user2, person2@example.com)",
+            "(3, user3, person3@example.com)",
+            "(4, user4, person4@example.com)",
+            "Executed.",
+            "db > ",
+        ]
+    );
+
+    // The transaction left active at the exit was rolled back,
+    // the statement run on its own before it was committed
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    cmd.arg("begin_commit_and_rollback_transactions.db")
+        .write_stdin("select * from users\n.exit\n")
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "db > (2, user2, person2@example.com)\n(3, user3, person3@example.com)\n\
+             Executed.\ndb > ",
+        ));
+    clear_db_file("begin_commit_and_rollback_transactions.db");
+    Ok(())
+}
+
+#[test]
+fn failed_insert_in_transaction_changes_nothing() -> Result<(), Box<dyn std::error::Error>> {
+    clear_db_file("failed_insert_in_transaction_changes_nothing.db");
+    let mut cmd = Command::cargo_bin("rsqlite").unwrap();
+    cmd.arg("failed_insert_in_transaction_changes_nothing.db")
+        .write_stdin(format!(
+            "{}create unique index users_email on users (email)\n\
+             insert into users values (1, 'user1', 'person1@example.com')\n\
+             begin\n\
+             insert into users values (2, 'user2', 'person2@example.com'), (1, 'user3', 'c')\n\
+             insert into users values (3, 'user3', 'd'), (3, 'user4', 'e')\n\
+             insert into users values (4, 'user4', 'f'), (5, 'user5', 'person1@example.com')\n\
+             insert into users values (6, 'user6', 'g'), (7, 'user7', 'g')\n\
+             commit\n\
+             select * from users\n\
+             .exit\n",
+            CREATE_USERS
+        ))
+        .assert()
+        .success()
+        .stdout(predicate::eq(
+            "db > Executed.\ndb > Executed.\ndb > Executed.\ndb > Executed.\n\
+             db > Error: Duplicate key.\n\
+             db > Error: Duplicate key.\n\
+             db > Error: Duplicate values in unique index users_email.\n\
+             db > Error: Duplicate values in unique index users_email.\n\
+             db > Executed.\n\
+             db > (1, user1, person1@example.com)\nExecuted.\ndb > ",
+        ));
+    clear_db_file("failed_insert_in_transaction_changes_nothing.db");
     Ok(())
 }